@@ -6,7 +6,7 @@ use ch_core::{
     node_contraction::NodeContractor,
     prelude::PriorityParams,
     search::ch_search::CHSearch,
-    util::test_graphs::graph_saarland,
+    util::test_graphs::graph_saarland_cached,
 };
 use indicatif::ProgressBar;
 use rand::{rngs::StdRng, Rng};
@@ -15,9 +15,9 @@ fn main() {
     env_logger::init();
     const ITERATIONS: usize = 1_000;
     let mut g = if let Some(path) = std::env::args().nth(1) {
-        Graph::from_pbf_with_simplification(Path::new(&path)).expect("Invalid path")
+        Graph::from_pbf_cached(Path::new(&path), true).expect("Invalid path")
     } else {
-        graph_saarland()
+        graph_saarland_cached()
     };
 
     let num_nodes = g.nodes.len();
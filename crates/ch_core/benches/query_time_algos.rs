@@ -1,22 +1,26 @@
-use std::{collections::BinaryHeap, fs::File, path::Path};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
 
 use ch_core::{
-    constants::Weight,
     contraction_params::ContractionParams,
-    graph::{node_index, NodeIndex},
+    graph::{node_index, Graph, NodeIndex},
     node_contraction::NodeContractor,
     prelude::PriorityParams,
     search::bidir_dijkstra::BidirDijkstra,
+    search::{
+        astar::AStar,
+        bidir_astar::BidirAStar,
+        ch_search::CHSearch,
+        dijkstra::Dijkstra,
+        landmarks::{LandmarkSelection, Landmarks, DEFAULT_NUM_LANDMARKS},
+    },
     util::{
         cli,
-        test_graphs::{graph_saarland, graph_vaterstetten},
+        test_graphs::{graph_saarland_cached, graph_vaterstetten},
     },
 };
-use ch_core::{graph::Graph, search::dijkstra::Candidate};
-use ch_core::{
-    search::{astar::AStar, ch_search::CHSearch, dijkstra::Dijkstra},
-    util::math::straight_line,
-};
 use indicatif::ProgressBar;
 use plotly::{
     box_plot::BoxPoints,
@@ -26,7 +30,6 @@ use plotly::{
     BoxPlot, Layout, Plot, Scatter,
 };
 use rand::prelude::*;
-use rustc_hash::FxHashMap;
 use std::io::Write;
 
 // Randomly select a source node s.
@@ -44,12 +47,25 @@ use std::io::Write;
 fn main() {
     const ITERATIONS: usize = 1_000;
 
-    let mut g = if let Some(path) = std::env::args().find(|p| p.ends_with(".pbf")) {
-        Graph::from_pbf_with_simplification(Path::new(&path)).expect("Invalid path")
+    let pbf = std::env::args().find(|p| p.ends_with(".pbf"));
+    let mut g = if let Some(path) = &pbf {
+        Graph::from_pbf_cached(Path::new(path), true).expect("Invalid path")
     } else {
-        graph_saarland()
+        graph_saarland_cached()
     };
 
+    // Landmarks are stored next to the pbf file
+    let landmarks_path = pbf.map(PathBuf::from).unwrap_or_else(|| {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../osm_reader/test_data/saarland_pp.osm.pbf")
+    });
+    let landmarks = Landmarks::load_or_compute(
+        &g,
+        &landmarks_path,
+        DEFAULT_NUM_LANDMARKS,
+        LandmarkSelection::Avoid,
+    )
+    .expect("Couldn't compute landmarks");
+
     let mut params = ContractionParams::default();
     params = params.priority_params(PriorityParams {
         edge_difference_coeff: 190,
@@ -75,17 +91,20 @@ fn main() {
     let mut timings_dijk = vec![vec![]; num_ranks];
     let mut timings_astar = vec![vec![]; num_ranks];
     let mut timings_bidir = vec![vec![]; num_ranks];
+    let mut timings_bidir_alt = vec![vec![]; num_ranks];
     let mut timings_ch = vec![vec![]; num_ranks];
 
     let mut nodes_settled_dijk = vec![vec![]; num_ranks];
     let mut nodes_settled_astar = vec![vec![]; num_ranks];
     let mut nodes_settled_bidir = vec![vec![]; num_ranks];
+    let mut nodes_settled_bidir_alt = vec![vec![]; num_ranks];
     let mut nodes_settled_ch = vec![vec![]; num_ranks];
 
     let mut ch = CHSearch::new(&overlay_graph);
     let mut dijk = Dijkstra::new(&g);
     let mut bidir = BidirDijkstra::new(&g);
     let mut astar = AStar::new(&g);
+    let mut bidir_alt = BidirAStar::with_landmarks(&g, &landmarks);
 
     let mut rng: StdRng = rand::SeedableRng::seed_from_u64(187);
 
@@ -95,7 +114,7 @@ fn main() {
         // Generate random start node
         let source = node_index(rng.gen_range(0..num_nodes));
         // Find targets
-        let targets = calculate_st_queries(source, &g, rank_start, rank_end);
+        let targets = calculate_st_queries(&mut dijk, source, rank_start, rank_end);
 
         // Measure query times and #nodes settled for Dijsktra-, AStar- and CH-query
         for (target, rank) in targets {
@@ -105,7 +124,9 @@ fn main() {
             timings_dijk[idx].push(dijk.stats.duration.unwrap().as_micros() as f64);
             nodes_settled_dijk[idx].push(dijk.stats.nodes_settled as f64);
 
-            astar.search(source, target, straight_line).unwrap();
+            astar
+                .search(source, target, |src, dst| g.straight_line(src, dst))
+                .unwrap();
             timings_astar[idx].push(astar.stats.duration.unwrap().as_micros() as f64);
             nodes_settled_astar[idx].push(astar.stats.nodes_settled as f64);
 
@@ -113,6 +134,10 @@ fn main() {
             timings_bidir[idx].push(bidir.stats.duration.unwrap().as_micros() as f64);
             nodes_settled_bidir[idx].push(bidir.stats.nodes_settled as f64);
 
+            bidir_alt.search(source, target).unwrap();
+            timings_bidir_alt[idx].push(bidir_alt.stats.duration.unwrap().as_micros() as f64);
+            nodes_settled_bidir_alt[idx].push(bidir_alt.stats.nodes_settled as f64);
+
             ch.search(source, target).unwrap();
             timings_ch[idx].push(ch.stats.duration.unwrap().as_micros() as f64);
             nodes_settled_ch[idx].push(ch.stats.nodes_settled as f64);
@@ -137,6 +162,7 @@ fn main() {
     write_stats(&mut file, &mut timings_dijk, &nodes_settled_dijk);
     write_stats(&mut file, &mut timings_bidir, &nodes_settled_bidir);
     write_stats(&mut file, &mut timings_astar, &nodes_settled_astar);
+    write_stats(&mut file, &mut timings_bidir_alt, &nodes_settled_bidir_alt);
     write_stats(&mut file, &mut timings_ch, &nodes_settled_ch);
 
     // Create plots
@@ -175,6 +201,14 @@ fn main() {
         .line(Line::new().width(0.7))
         .whisker_width(8.);
 
+    let trace_bidir_alt =
+        BoxPlot::new_xy(x.clone(), timings_bidir_alt.into_iter().flatten().collect())
+            .name("Bidir. ALT")
+            .marker(marker.clone())
+            .box_points(BoxPoints::Outliers)
+            .line(Line::new().width(0.7))
+            .whisker_width(8.);
+
     let trace_ch = BoxPlot::new_xy(x.clone(), timings_ch.into_iter().flatten().collect())
         .name("CHs")
         .marker(marker)
@@ -185,6 +219,7 @@ fn main() {
     plot.add_trace(trace_dijk);
     plot.add_trace(trace_astar);
     plot.add_trace(trace_bidir);
+    plot.add_trace(trace_bidir_alt);
 
     let y_axis_log = Axis::new()
         .title(Title::new("Query-Time [μs]"))
@@ -211,6 +246,7 @@ fn main() {
             Rgb::new(39, 136, 229),
             Rgb::new(0, 77, 64),
             Rgb::new(255, 193, 7),
+            Rgb::new(117, 112, 179),
         ])
         .margin(Margin::default().top(2).right(15).pad(10))
         .legend(
@@ -285,6 +321,16 @@ fn main() {
     .mode(Mode::Lines)
     .name("Bidir. Dijkstra");
 
+    let trace_bidir_alt = Scatter::new(
+        x.clone(),
+        nodes_settled_bidir_alt
+            .into_iter()
+            .map(|n| mean(&n))
+            .collect(),
+    )
+    .mode(Mode::Lines)
+    .name("Bidir. ALT");
+
     let trace_ch = Scatter::new(x, nodes_settled_ch.into_iter().map(|n| mean(&n)).collect())
         .mode(Mode::Lines)
         .name("CHs");
@@ -292,6 +338,7 @@ fn main() {
     plot.add_trace(trace_dijk);
     plot.add_trace(trace_astar);
     plot.add_trace(trace_bidir);
+    plot.add_trace(trace_bidir_alt);
     plot.add_trace(trace_ch);
 
     let y_axis = Axis::new()
@@ -355,65 +402,19 @@ fn mean(numbers: &[f64]) -> f64 {
     sum / numbers.len() as f64
 }
 
+/// Finds the nodes with Dijkstra rank `2^k` from `source` for all `k` in `rank_start..=rank_end`
 fn calculate_st_queries(
+    dijkstra: &mut Dijkstra,
     source: NodeIndex,
-    g: &Graph,
     rank_start: u32,
     rank_end: u32,
 ) -> Vec<(NodeIndex, usize)> {
-    let mut ranks = (rank_start..=rank_end)
-        .rev()
-        .map(|k| 2usize.pow(k))
-        .collect::<Vec<usize>>();
+    let tree = dijkstra.one_to_all(source);
 
-    let mut node_data: FxHashMap<NodeIndex, (Weight, Option<NodeIndex>)> = FxHashMap::default();
-    let mut node_ranks: FxHashMap<NodeIndex, usize> = FxHashMap::default();
-
-    node_data.insert(source, (0.0, None));
-
-    let mut queue = BinaryHeap::new();
-    let mut nodes_settled = 0;
-
-    queue.push(Candidate::new(source, 0.0));
-    let mut next_rank = ranks.pop().unwrap();
-
-    while let Some(Candidate { weight, node_idx }) = queue.pop() {
-        nodes_settled += 1;
-
-        if nodes_settled >= next_rank {
-            node_ranks.insert(node_idx, next_rank);
-            if let Some(r) = ranks.pop() {
-                next_rank = r;
-            } else {
-                break;
-            }
-        }
-
-        for (_, edge) in g
-            .neighbors_outgoing(node_idx)
-            .filter(|(edge_idx, _)| edge_idx.index() < g.edges.len() - g.num_shortcuts)
-        {
-            let new_distance = weight + edge.weight;
-            if new_distance
-                < node_data
-                    .get(&edge.target)
-                    .unwrap_or(&(std::f64::INFINITY, None))
-                    .0
-            {
-                node_data.insert(edge.target, (new_distance, Some(node_idx)));
-                queue.push(Candidate::new(edge.target, new_distance));
-            }
-        }
-    }
-
-    let mut targets = node_ranks
-        .iter()
-        .map(|(node_idx, rank)| (*node_idx, *rank))
-        .collect::<Vec<(NodeIndex, usize)>>();
-
-    targets.sort_by(|(_, rank1), (_, rank2)| rank1.cmp(rank2));
-
-    targets
+    (rank_start..=rank_end)
+        .map(|k| 2usize.pow(k))
+        .map_while(|rank| Some((tree.node_with_rank(rank)?, rank)))
+        .collect()
 }
 
 fn superscript_digits(number: u32) -> String {
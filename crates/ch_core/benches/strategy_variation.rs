@@ -6,16 +6,16 @@ use ch_core::{
     graph::Graph,
     node_contraction::NodeContractor,
     prelude::PriorityParams,
-    util::test_graphs::graph_saarland,
+    util::test_graphs::graph_saarland_cached,
 };
 
 fn main() {
     env_logger::init();
 
     let g = if let Some(path) = std::env::args().nth(1) {
-        Graph::from_pbf_with_simplification(Path::new(&path)).expect("Invalid path")
+        Graph::from_pbf_cached(Path::new(&path), true).expect("Invalid path")
     } else {
-        graph_saarland()
+        graph_saarland_cached()
     };
 
     // Only Self Update
@@ -5,7 +5,7 @@ use ch_core::{
     graph::{node_index, Graph},
     node_contraction::NodeContractor,
     search::ch_search::CHSearch,
-    util::test_graphs::graph_saarland,
+    util::test_graphs::graph_saarland_cached,
 };
 
 fn main() {
@@ -13,9 +13,9 @@ fn main() {
     const ITERATIONS: usize = 1_000;
 
     let g = if let Some(path) = std::env::args().nth(1) {
-        Graph::from_pbf_with_simplification(Path::new(&path)).expect("Invalid path")
+        Graph::from_pbf_cached(Path::new(&path), true).expect("Invalid path")
     } else {
-        graph_saarland()
+        graph_saarland_cached()
     };
 
     let configs = vec![(0, 500), (50, 500), (500, 500)];
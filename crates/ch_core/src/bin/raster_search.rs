@@ -1,8 +1,9 @@
 use std::time::Instant;
 
 use ch_core::{
-    contraction_params::ContractionParams, graph::node_index, node_contraction::NodeContractor,
-    prelude::PriorityParams, search::ch_search::CHSearch, util::test_graphs::graph_saarland,
+    components::ComponentFilter, contraction_params::ContractionParams, graph::node_index,
+    node_contraction::NodeContractor, prelude::PriorityParams, search::ch_search::CHSearch,
+    util::test_graphs::graph_saarland_cached,
 };
 
 use rand::prelude::*;
@@ -10,7 +11,9 @@ use rand::prelude::*;
 fn main() {
     env_logger::init();
 
-    let g = graph_saarland();
+    // Random queries between different components would fail and skew the average
+    let mut g = graph_saarland_cached();
+    g.filter_components(ComponentFilter::Largest);
 
     let num_nodes = g.nodes().count();
 
@@ -0,0 +1,420 @@
+//! Strongly connected components of a [`Graph`].
+//!
+//! OSM extracts contain islands and one-way traps which are not reachable from the rest of the
+//! network. Queries between them fail only after exploring everything reachable from the
+//! source. The import can either remove everything except the largest component or tag the
+//! nodes with their component, so searches for unreachable targets return immediately. See
+//! [`ComponentFilter`].
+//!
+//! # Examples
+//! ```
+//! use ch_core::prelude::*;
+//! use ch_core::components::ComponentFilter;
+//!
+//! let mut g = generate_simple_graph();
+//! let components = g.strongly_connected_components();
+//! println!("{}", components.stats());
+//!
+//! g.filter_components(ComponentFilter::Largest);
+//! assert_eq!(components.stats().largest, g.nodes.len());
+//! ```
+use std::{cmp::Reverse, fmt::Display, str::FromStr};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::graph::{Graph, NodeIndex};
+
+/// What to do with the strongly connected components of an imported graph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ComponentFilter {
+    /// Keep the graph as it is
+    #[default]
+    None,
+    /// Remove all nodes outside of the largest component
+    Largest,
+    /// Keep all nodes, but tag them with their component
+    Tag,
+}
+
+impl FromStr for ComponentFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(ComponentFilter::None),
+            "largest" => Ok(ComponentFilter::Largest),
+            "tag" => Ok(ComponentFilter::Tag),
+            _ => Err(format!("Unknown component filter: {}", s)),
+        }
+    }
+}
+
+impl Display for ComponentFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComponentFilter::None => write!(f, "none"),
+            ComponentFilter::Largest => write!(f, "largest"),
+            ComponentFilter::Tag => write!(f, "tag"),
+        }
+    }
+}
+
+/// Strongly connected components of a graph.
+///
+/// The components are numbered in reverse topological order: if an edge leads from component
+/// `x` to component `y`, then `x >= y`. Together with the weakly connected components this
+/// rules out many unreachable targets without a search, see [`Components::may_reach`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Components {
+    /// Strongly connected component of every node
+    component: Vec<usize>,
+    /// Number of nodes of every strongly connected component
+    sizes: Vec<usize>,
+    /// Weakly connected component of every node
+    weak: Vec<usize>,
+}
+
+impl Components {
+    /// Strongly connected component of `node`
+    pub fn component(&self, node: NodeIndex) -> usize {
+        self.component[node.index()]
+    }
+
+    /// Weakly connected component of `node`
+    pub fn weak_component(&self, node: NodeIndex) -> usize {
+        self.weak[node.index()]
+    }
+
+    /// Returns true if `a` and `b` are in the same component, i.e. they are reachable from
+    /// each other
+    pub fn same_component(&self, a: NodeIndex, b: NodeIndex) -> bool {
+        self.component(a) == self.component(b)
+    }
+
+    /// Returns false if there is no path from `source` to `target`. A return value of `true`
+    /// only guarantees a path if both nodes are in the same component.
+    pub fn may_reach(&self, source: NodeIndex, target: NodeIndex) -> bool {
+        self.weak[source.index()] == self.weak[target.index()]
+            && self.component(source) >= self.component(target)
+    }
+
+    /// Number of nodes of every component
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    /// The component with the most nodes
+    pub fn largest(&self) -> Option<usize> {
+        (0..self.sizes.len()).max_by_key(|id| (self.sizes[*id], Reverse(*id)))
+    }
+
+    /// Number of components
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// Iterator over all nodes of `component`
+    pub fn nodes(&self, component: usize) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.component
+            .iter()
+            .enumerate()
+            .filter(move |(_, c)| **c == component)
+            .map(|(node, _)| NodeIndex::new(node))
+    }
+
+    pub fn stats(&self) -> ComponentStats {
+        ComponentStats {
+            num_nodes: self.component.len(),
+            num_components: self.sizes.len(),
+            num_weak_components: self.weak.iter().max().map_or(0, |max| max + 1),
+            largest: self.largest().map_or(0, |id| self.sizes[id]),
+            num_singletons: self.sizes.iter().filter(|size| **size == 1).count(),
+        }
+    }
+}
+
+/// Statistics about the component sizes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ComponentStats {
+    pub num_nodes: usize,
+    pub num_components: usize,
+    pub num_weak_components: usize,
+    /// Number of nodes of the largest component
+    pub largest: usize,
+    /// Number of components consisting of a single node
+    pub num_singletons: usize,
+}
+
+impl Display for ComponentStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Components:\t#Strong: {}, #Weak: {}, Largest: {} ({:.2}%), #Singletons: {}",
+            self.num_components,
+            self.num_weak_components,
+            self.largest,
+            self.largest as f64 / self.num_nodes.max(1) as f64 * 100.0,
+            self.num_singletons
+        )
+    }
+}
+
+/// Computes the strongly connected components with Tarjan's algorithm and the weakly connected
+/// components. The recursion is replaced by an explicit stack, so the size of the graph is not
+/// limited by the call stack.
+pub fn strongly_connected_components(g: &Graph) -> Components {
+    const UNVISITED: usize = usize::MAX;
+
+    let num_nodes = g.nodes.len();
+    let mut index = vec![UNVISITED; num_nodes];
+    let mut low_link = vec![0; num_nodes];
+    let mut on_stack = vec![false; num_nodes];
+    let mut stack = Vec::new();
+    let mut component = vec![UNVISITED; num_nodes];
+    let mut sizes = Vec::new();
+    let mut next_index = 0;
+
+    // Replaces the recursive calls, contains the node and the position in its outgoing edges
+    let mut call_stack: Vec<(usize, usize)> = Vec::new();
+
+    for root in 0..num_nodes {
+        if index[root] != UNVISITED {
+            continue;
+        }
+
+        index[root] = next_index;
+        low_link[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        call_stack.push((root, 0));
+
+        while let Some(&(node, pos)) = call_stack.last() {
+            if let Some(edge_idx) = g.edges_out[node].get(pos) {
+                call_stack.last_mut().unwrap().1 += 1;
+
+                let target = g.edges[edge_idx.index()].target.index();
+                if index[target] == UNVISITED {
+                    index[target] = next_index;
+                    low_link[target] = next_index;
+                    next_index += 1;
+                    stack.push(target);
+                    on_stack[target] = true;
+                    call_stack.push((target, 0));
+                } else if on_stack[target] {
+                    low_link[node] = low_link[node].min(index[target]);
+                }
+                continue;
+            }
+
+            // All outgoing edges are explored
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                low_link[parent] = low_link[parent].min(low_link[node]);
+            }
+
+            if low_link[node] == index[node] {
+                let id = sizes.len();
+                let mut size = 0;
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component[member] = id;
+                    size += 1;
+                    if member == node {
+                        break;
+                    }
+                }
+                sizes.push(size);
+            }
+        }
+    }
+
+    // Tarjan's algorithm finishes a component only after all components reachable from it,
+    // so the ids are already in reverse topological order
+    let components = Components {
+        component,
+        sizes,
+        weak: weakly_connected_components(g),
+    };
+    info!("{}", components.stats());
+    components
+}
+
+/// Weakly connected component of every node, i.e. ignoring the direction of the edges
+fn weakly_connected_components(g: &Graph) -> Vec<usize> {
+    const UNVISITED: usize = usize::MAX;
+
+    let mut component = vec![UNVISITED; g.nodes.len()];
+    let mut num_components = 0;
+    let mut stack = Vec::new();
+
+    for root in 0..g.nodes.len() {
+        if component[root] != UNVISITED {
+            continue;
+        }
+
+        component[root] = num_components;
+        stack.push(NodeIndex::new(root));
+        while let Some(node) = stack.pop() {
+            let neighbors = g
+                .neighbors_outgoing(node)
+                .map(|(_, edge)| edge.target)
+                .chain(g.neighbors_incoming(node).map(|(_, edge)| edge.source));
+            for neighbor in neighbors {
+                if component[neighbor.index()] == UNVISITED {
+                    component[neighbor.index()] = num_components;
+                    stack.push(neighbor);
+                }
+            }
+        }
+        num_components += 1;
+    }
+
+    component
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        edge,
+        graph::{node_index, Edge, Node},
+        node_contraction::NodeContractor,
+        overlay_graph::OverlayGraph,
+        search::Dijkstra,
+        util::test_graphs::{generate_complex_graph, generate_simple_graph},
+    };
+
+    use super::*;
+
+    #[test]
+    fn complex_graph_is_strongly_connected() {
+        let g = generate_complex_graph();
+        let components = g.strongly_connected_components();
+
+        assert_eq!(1, components.len());
+        assert_eq!(&[11], components.sizes());
+    }
+
+    #[test]
+    fn one_way_traps_are_separate_components() {
+        // E -> A -> C <-> B, C <-> D
+        let g = generate_simple_graph();
+        let components = g.strongly_connected_components();
+
+        assert_eq!(&[3, 1, 1], components.sizes());
+        assert!(components.same_component(node_index(1), node_index(3)));
+        assert!(!components.same_component(node_index(0), node_index(2)));
+        assert_eq!(
+            vec![node_index(1), node_index(2), node_index(3)],
+            components.nodes(0).collect::<Vec<_>>()
+        );
+
+        let stats = components.stats();
+        assert_eq!(5, stats.num_nodes);
+        assert_eq!(3, stats.largest);
+        assert_eq!(2, stats.num_singletons);
+    }
+
+    #[test]
+    fn long_path_does_not_overflow_the_stack() {
+        let mut g = Graph::new();
+        let num_nodes = 200_000;
+        for i in 0..num_nodes {
+            g.add_node(Node::new(i, 0.0, 0.0));
+        }
+        for i in 1..num_nodes {
+            g.add_edge(edge!(node_index(i - 1) => node_index(i), 1.0));
+        }
+        g.add_edge(edge!(node_index(num_nodes - 1) => node_index(0), 1.0));
+
+        assert_eq!(&[num_nodes], g.strongly_connected_components().sizes());
+    }
+
+    #[test]
+    fn keep_largest_component() {
+        let mut g = generate_simple_graph();
+        g.filter_components(ComponentFilter::Largest);
+
+        assert_eq!(3, g.nodes.len());
+        assert_eq!(4, g.edges.len());
+        assert_eq!(1, g.strongly_connected_components().len());
+    }
+
+    #[test]
+    fn tagged_components_short_circuit_queries() {
+        let mut g = generate_simple_graph();
+        g.filter_components(ComponentFilter::Tag);
+        assert_eq!(5, g.nodes.len());
+
+        let mut dijkstra = Dijkstra::new(&g);
+        // B is reachable from E, but not the other way around
+        assert!(dijkstra.search(node_index(4), node_index(1)).is_some());
+        assert!(dijkstra.search(node_index(1), node_index(4)).is_none());
+        assert_eq!(0, dijkstra.stats.nodes_settled);
+    }
+
+    #[test]
+    fn adding_nodes_and_edges_drops_tagged_components() {
+        let mut g = generate_simple_graph();
+        g.filter_components(ComponentFilter::Tag);
+
+        // B -> F -> E connects B to E
+        let f = g.add_node(Node::new(5, 0.0, 0.0));
+        g.add_edge(Edge::new(node_index(1), f, 1.0));
+        g.add_edge(Edge::new(f, node_index(4), 1.0));
+        assert!(g.components.is_none());
+
+        let mut dijkstra = Dijkstra::new(&g);
+        assert_eq!(
+            2.0,
+            dijkstra
+                .search(node_index(1), node_index(4))
+                .unwrap()
+                .weight
+        );
+        assert!(dijkstra.search(node_index(4), f).is_some());
+    }
+
+    #[test]
+    fn keep_tagged_components_in_files() {
+        let mut g = generate_simple_graph();
+        g.filter_components(ComponentFilter::Tag);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tagged_components.graph.bin");
+        g.encode(&path).unwrap();
+        let g_imported = Graph::from_decode(&path).unwrap();
+        assert_eq!(g.components, g_imported.components);
+
+        let overlay_graph = NodeContractor::new(&mut g).run();
+        let path = dir.path().join("tagged_components.bin");
+        overlay_graph.encode(&path).unwrap();
+        let overlay_graph_imported = OverlayGraph::from_decode(&path).unwrap();
+        assert!(overlay_graph_imported.road_graph().components.is_some());
+        assert!(!overlay_graph_imported
+            .road_graph()
+            .may_reach(node_index(1), node_index(4)));
+    }
+
+    #[test]
+    fn islands_are_unreachable() {
+        let mut g = generate_simple_graph();
+        let f = g.add_node(Node::new(5, 0.0, 0.0));
+        let g_ = g.add_node(Node::new(6, 0.0, 0.0));
+        g.add_edges(edge!(f, g_, 1.0));
+
+        let components = g.strongly_connected_components();
+        assert_eq!(2, components.stats().num_weak_components);
+        assert!(components.may_reach(f, g_));
+        assert!(components.may_reach(node_index(4), node_index(1)));
+        for node in 0..5 {
+            assert!(!components.may_reach(f, node_index(node)));
+            assert!(!components.may_reach(node_index(node), f));
+        }
+    }
+}
@@ -1,7 +1,8 @@
 //! Parameters for the contraction algorithm
+use serde::{Deserialize, Serialize};
 
 /// Parameters for the contraction algorithm
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ContractionParams {
     pub(crate) priority_params: PriorityParams,
     // Limit for lazy updates
@@ -42,7 +43,7 @@ impl Default for ContractionParams {
 }
 
 /// Coefficients for the priority function
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PriorityParams {
     pub edge_difference_coeff: i32,
     pub contracted_neighbors_coeff: i32,
@@ -11,13 +11,17 @@
 //! let update_strategy = UpdateStrategy::new().set_periodic_updates(true);
 //!
 //! let contraction_strategy = ContractionStrategy::LazyUpdate(update_strategy);
+//!
+//! // Stop after 95% of the nodes are contracted and keep the rest as core
+//! let update_strategy = UpdateStrategy::new().set_core_size(CoreSize::ContractedFraction(0.95));
 //! ```
 use crate::graph::NodeIndex;
 
 /// Strategy which is used while contracting nodes.
 #[derive(Clone, Copy, Debug)]
 pub enum ContractionStrategy<'a> {
-    /// Nodes are contracted in the `exact` order of the given slice.
+    /// Nodes are contracted in the `exact` order of the given slice. All nodes are contracted,
+    /// a core is only left with [`UpdateStrategy::set_core_size`].
     FixedOrder(&'a [NodeIndex]),
     /// The order gets updated according to the chosen [UpdateStrategy] while the contraction process is running.
     LazyUpdate(UpdateStrategy),
@@ -56,6 +60,8 @@ pub struct UpdateStrategy {
     update_periodic: bool,
 
     periodic_update_data: PeriodicUpdateData,
+
+    core_size: Option<CoreSize>,
 }
 
 impl Default for UpdateStrategy {
@@ -65,6 +71,33 @@ impl Default for UpdateStrategy {
             update_local: true,
             update_periodic: false,
             periodic_update_data: PeriodicUpdateData::default(),
+            core_size: None,
+        }
+    }
+}
+
+/// Determines when the contraction stops. The remaining nodes are left uncontracted and form
+/// the core of the [`OverlayGraph`](crate::overlay_graph::OverlayGraph).
+///
+/// Only used with [`ContractionStrategy::LazyUpdate`], a [`ContractionStrategy::FixedOrder`]
+/// always contracts every node of the given order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoreSize {
+    /// Stop after the given fraction (`0.0..=1.0`) of all nodes is contracted
+    ContractedFraction(f64),
+    /// Stop as soon as only the given number of nodes is left
+    Nodes(usize),
+}
+
+impl CoreSize {
+    /// Number of nodes in the core of a graph with `num_nodes` nodes
+    pub fn num_core_nodes(&self, num_nodes: usize) -> usize {
+        match *self {
+            CoreSize::ContractedFraction(fraction) => {
+                let contracted = (num_nodes as f64 * fraction.clamp(0.0, 1.0)).round() as usize;
+                num_nodes - contracted.min(num_nodes)
+            }
+            CoreSize::Nodes(nodes) => nodes.min(num_nodes),
         }
     }
 }
@@ -89,6 +122,11 @@ impl UpdateStrategy {
         self.update_periodic
     }
 
+    /// Returns the size of the uncontracted core. `None` if all nodes are contracted.
+    pub fn core_size(&self) -> Option<CoreSize> {
+        self.core_size
+    }
+
     /// Enable or disable the `Just In Time`-Strategy
     pub fn set_update_jit(mut self, lazy_update_self: bool) -> Self {
         self.update_jit = lazy_update_self;
@@ -107,6 +145,12 @@ impl UpdateStrategy {
         self
     }
 
+    /// Stop the contraction early and leave a core of `core_size` uncontracted nodes
+    pub fn set_core_size(mut self, core_size: CoreSize) -> Self {
+        self.core_size = Some(core_size);
+        self
+    }
+
     #[allow(dead_code)]
     fn set_periodic_updates_trigger(mut self, trigger: usize) -> Self {
         self.periodic_update_data.trigger = trigger;
@@ -0,0 +1,323 @@
+//! Road networks of the [9th DIMACS implementation challenge](http://www.diag.uniroma1.it/challenge9/format.shtml).
+//!
+//! A network consists of a graph file (`.gr`) and an optional coordinate file (`.co`):
+//! ```text
+//! c comment
+//! p sp <#nodes> <#arcs>
+//! a <source> <target> <weight>
+//!
+//! p aux sp co <#nodes>
+//! v <node> <longitude * 10^6> <latitude * 10^6>
+//! ```
+//! The nodes are numbered from `1` to `#nodes` and become the [`Node::id`] of the imported
+//! nodes. Weights have to be positive. They are no travel times of a known speed, so the
+//! imported graph has no [`Graph::metric`] and A* falls back to a zero estimate.
+//!
+//! The format only allows integer weights. On export the weights are multiplied by a scale
+//! factor, rounded and raised to at least `1`, so short edges don't end up with weight `0`.
+//! The scale is stored in the comment `c weight scale <scale>`, which other tools ignore, and
+//! the weights are divided by it on import.
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context};
+use log::{info, warn};
+
+use crate::graph::{node_index, Edge, Graph, Node};
+
+use super::original_edges;
+
+/// Scale of the coordinates in `.co` files
+const COORDINATE_SCALE: f64 = 1e6;
+
+/// Comment containing the scale of the weights
+const WEIGHT_SCALE_COMMENT: &str = "c weight scale";
+
+/// Reads a graph from a `.gr` file and, if given, the coordinates from a `.co` file. Without
+/// coordinates all nodes are placed at `(0, 0)`.
+pub fn read_file(path_to_gr: &Path, path_to_co: Option<&Path>) -> anyhow::Result<Graph> {
+    let open = |path: &Path| -> anyhow::Result<_> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(BufReader::new(file))
+    };
+
+    let co = path_to_co.map(open).transpose()?;
+    read(open(path_to_gr)?, co)
+}
+
+/// Reads a graph from the contents of a `.gr` and a `.co` file, see [`read_file`].
+pub fn read(gr: impl BufRead, co: Option<impl BufRead>) -> anyhow::Result<Graph> {
+    let mut g: Option<Graph> = None;
+    let mut num_arcs = 0;
+    let mut weight_scale = 1.0;
+
+    for (i, line) in gr.lines().enumerate() {
+        let line = line?;
+        if let Some(scale) = line.strip_prefix(WEIGHT_SCALE_COMMENT) {
+            weight_scale = parse(Some(scale.trim()))
+                .ok()
+                .filter(|scale: &f64| scale.is_finite() && *scale > 0.0)
+                .with_context(|| format!("Line {}: Invalid weight scale {}", i + 1, scale))?;
+            continue;
+        }
+        parse_line(&line, |kind, fields| match (kind, &mut g) {
+            ("p", None) => {
+                if fields.next() != Some("sp") {
+                    bail!("Expected problem type sp");
+                }
+                let num_nodes: usize = parse(fields.next())?;
+                num_arcs = parse(fields.next())?;
+
+                let mut graph = Graph::with_capacity(num_nodes, num_arcs);
+                for id in 1..=num_nodes {
+                    graph.add_node(Node::new(id, 0.0, 0.0));
+                }
+                g = Some(graph);
+                Ok(())
+            }
+            ("a", Some(g)) => {
+                let source = node(g, parse(fields.next())?)?;
+                let target = node(g, parse(fields.next())?)?;
+                let weight: f64 = parse(fields.next())?;
+                if !weight.is_finite() || weight <= 0.0 {
+                    bail!("Invalid weight {}", weight);
+                }
+                g.add_edge(Edge::new(
+                    node_index(source),
+                    node_index(target),
+                    weight / weight_scale,
+                ));
+                Ok(())
+            }
+            _ => bail!("Unexpected line"),
+        })
+        .with_context(|| format!("Line {}: {}", i + 1, line))?;
+    }
+
+    let mut g = g.context("Missing problem line")?;
+    if g.edges.len() != num_arcs {
+        warn!("Expected {} arcs, found {}", num_arcs, g.edges.len());
+    }
+
+    if let Some(co) = co {
+        for (i, line) in co.lines().enumerate() {
+            let line = line?;
+            parse_line(&line, |kind, fields| match kind {
+                "p" => Ok(()),
+                "v" => {
+                    let id = node(&g, parse(fields.next())?)?;
+                    let lon: f64 = parse(fields.next())?;
+                    let lat: f64 = parse(fields.next())?;
+                    g.nodes[id].lat = lat / COORDINATE_SCALE;
+                    g.nodes[id].lon = lon / COORDINATE_SCALE;
+                    Ok(())
+                }
+                _ => bail!("Unexpected line"),
+            })
+            .with_context(|| format!("Line {} of coordinates: {}", i + 1, line))?;
+        }
+    }
+
+    info!(
+        "Graph has {} nodes and {} edges",
+        g.nodes.len(),
+        g.edges.len()
+    );
+    Ok(g)
+}
+
+/// Writes the original edges of `g` to a `.gr` file and the coordinates to a `.co` file. See
+/// [`write`] for `weight_scale`.
+pub fn write_file(
+    g: &Graph,
+    path_to_gr: &Path,
+    path_to_co: &Path,
+    weight_scale: f64,
+) -> anyhow::Result<()> {
+    write(
+        g,
+        BufWriter::new(File::create(path_to_gr)?),
+        BufWriter::new(File::create(path_to_co)?),
+        weight_scale,
+    )
+}
+
+/// Writes the original edges of `g` in the `.gr` format into `gr` and the coordinates in the
+/// `.co` format into `co`. The weights are multiplied by `weight_scale` and rounded to
+/// integers of at least `1`, e.g. a scale of `1000` keeps travel times in seconds accurate to
+/// milliseconds.
+pub fn write(
+    g: &Graph,
+    mut gr: impl Write,
+    mut co: impl Write,
+    weight_scale: f64,
+) -> anyhow::Result<()> {
+    if !weight_scale.is_finite() || weight_scale <= 0.0 {
+        bail!("Invalid weight scale {}", weight_scale);
+    }
+
+    let edges = original_edges(g);
+    if let Some(source) = &g.source {
+        writeln!(gr, "c Graph of {}", source)?;
+    }
+    if weight_scale != 1.0 {
+        writeln!(gr, "{} {}", WEIGHT_SCALE_COMMENT, weight_scale)?;
+    }
+    writeln!(gr, "p sp {} {}", g.nodes.len(), edges.len())?;
+    for edge in edges {
+        writeln!(
+            gr,
+            "a {} {} {}",
+            edge.source.index() + 1,
+            edge.target.index() + 1,
+            ((edge.weight * weight_scale).round() as u64).max(1)
+        )?;
+    }
+    gr.flush()?;
+
+    writeln!(co, "p aux sp co {}", g.nodes.len())?;
+    for (i, node) in g.nodes.iter().enumerate() {
+        writeln!(
+            co,
+            "v {} {} {}",
+            i + 1,
+            (node.lon * COORDINATE_SCALE).round() as i64,
+            (node.lat * COORDINATE_SCALE).round() as i64
+        )?;
+    }
+    co.flush()?;
+
+    Ok(())
+}
+
+/// Calls `f` with the line type and the remaining fields. Skips empty lines and comments.
+fn parse_line<'a>(
+    line: &'a str,
+    f: impl FnOnce(&str, &mut std::str::SplitWhitespace<'a>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut fields = line.split_whitespace();
+    match fields.next() {
+        None | Some("c") => Ok(()),
+        Some(kind) => f(kind, &mut fields),
+    }
+}
+
+fn parse<T: FromStr>(field: Option<&str>) -> anyhow::Result<T> {
+    let field = field.context("Missing field")?;
+    field
+        .parse()
+        .map_err(|_| anyhow!("Invalid field {}", field))
+}
+
+/// Index of the node with the 1-based DIMACS `id`
+fn node(g: &Graph, id: usize) -> anyhow::Result<usize> {
+    if id == 0 || id > g.nodes.len() {
+        bail!("Node {} does not exist", id);
+    }
+    Ok(id - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_path(extension: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("test_data/sample.{}", extension))
+    }
+
+    #[test]
+    fn read_sample() {
+        let g = Graph::from_dimacs(&sample_path("gr"), Some(&sample_path("co"))).unwrap();
+
+        assert_eq!(5, g.nodes.len());
+        assert_eq!(7, g.edges.len());
+        assert_eq!(3, g.nodes[2].id);
+        assert_eq!(48.137154, g.nodes[0].lat);
+        assert_eq!(11.576124, g.nodes[0].lon);
+
+        let edge = &g.edges[0];
+        assert_eq!((node_index(0), node_index(1)), (edge.source, edge.target));
+        assert_eq!(803.0, edge.weight);
+        assert_eq!(None, g.metric);
+    }
+
+    #[test]
+    fn read_without_coordinates() {
+        let g = Graph::from_dimacs(&sample_path("gr"), None).unwrap();
+
+        assert_eq!(5, g.nodes.len());
+        assert!(g
+            .nodes
+            .iter()
+            .all(|node| node.lat == 0.0 && node.lon == 0.0));
+    }
+
+    #[test]
+    fn write_and_read() {
+        let g = Graph::from_dimacs(&sample_path("gr"), Some(&sample_path("co"))).unwrap();
+
+        let mut gr = Vec::new();
+        let mut co = Vec::new();
+        write(&g, &mut gr, &mut co, 1.0).unwrap();
+        let imported = read(gr.as_slice(), Some(co.as_slice())).unwrap();
+
+        assert_eq!(g.nodes.len(), imported.nodes.len());
+        for (a, b) in g.nodes.iter().zip(&imported.nodes) {
+            assert_eq!((a.id, a.lat, a.lon), (b.id, b.lat, b.lon));
+        }
+        assert_eq!(g.edges.len(), imported.edges.len());
+        for (a, b) in g.edges.iter().zip(&imported.edges) {
+            assert_eq!(
+                (a.source, a.target, a.weight),
+                (b.source, b.target, b.weight)
+            );
+        }
+    }
+
+    #[test]
+    fn write_and_read_small_weights() {
+        let mut g = Graph::new();
+        g.add_node(Node::new(1, 0.0, 0.0));
+        g.add_node(Node::new(2, 0.0, 0.0));
+        g.add_edge(Edge::new(node_index(0), node_index(1), 0.25));
+        g.add_edge(Edge::new(node_index(1), node_index(0), 1.5));
+
+        let write_and_read = |weight_scale| {
+            let mut gr = Vec::new();
+            write(&g, &mut gr, Vec::new(), weight_scale).unwrap();
+            let imported = read(gr.as_slice(), None::<&[u8]>).unwrap();
+            imported
+                .edges
+                .iter()
+                .map(|edge| edge.weight)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![0.25, 1.5], write_and_read(1000.0));
+        // Without a scale the weights are rounded, but never to 0
+        assert_eq!(vec![1.0, 2.0], write_and_read(1.0));
+        assert!(write(&g, Vec::new(), Vec::new(), 0.0).is_err());
+    }
+
+    #[test]
+    fn invalid_files_are_errors() {
+        let read_gr = |gr: &str| read(gr.as_bytes(), None::<&[u8]>);
+
+        assert!(read_gr("c no problem line\n").is_err());
+        assert!(read_gr("a 1 2 3\n").is_err());
+        assert!(read_gr("p sp 2 1\na 1 3 5\n").is_err());
+        assert!(read_gr("p sp 2 1\na 1 2 five\n").is_err());
+        assert!(read_gr("p sp 2 1\nx 1 2 5\n").is_err());
+        assert!(read_gr("c weight scale 0\np sp 2 1\na 1 2 5\n").is_err());
+
+        let zero_weight = read_gr("p sp 2 2\na 1 2 5\na 2 1 0\n").err().unwrap();
+        assert!(format!("{:#}", zero_weight).contains("Line 3"));
+        assert!(read_gr("p sp 2 1\na 1 2 -5\n").is_err());
+        assert!(read_gr("p sp 2 1\n\nc comment\na 1 2 5\n").is_ok());
+    }
+}
@@ -0,0 +1,405 @@
+//! [GraphML](http://graphml.graphdrawing.org/), an XML format for graphs understood by most
+//! graph tools.
+//!
+//! The coordinates of a node are read from the data keys `lat`/`lon` or `y`/`x` (as written
+//! by OSMnx), nodes without coordinates are placed at `(0, 0)`. The [`Node::id`] is read from
+//! the data key `osm_id` or the node id, if it is a number. The weight of an edge is read from
+//! the data key `weight`, `length` or `cost` and has to be positive. Undirected edges are added
+//! in both directions. The imported graph has no [`Graph::metric`], since the weights are no
+//! travel times of a known speed.
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context};
+use log::info;
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use rustc_hash::FxHashMap;
+
+use crate::graph::{node_index, Edge, Graph, Node};
+
+use super::original_edges;
+
+const LAT_KEYS: [&str; 2] = ["lat", "y"];
+const LON_KEYS: [&str; 2] = ["lon", "x"];
+const ID_KEYS: [&str; 1] = ["osm_id"];
+const WEIGHT_KEYS: [&str; 3] = ["weight", "length", "cost"];
+
+/// Reads a graph from a GraphML file
+pub fn read_file(path: &Path) -> anyhow::Result<Graph> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    read(BufReader::new(file)).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Node or edge with its data
+struct Item {
+    kind: ItemKind,
+    /// Line of the start tag
+    line: usize,
+    /// Pairs of key id and value
+    data: Vec<(String, String)>,
+}
+
+enum ItemKind {
+    Node {
+        id: String,
+    },
+    Edge {
+        source: String,
+        target: String,
+        directed: Option<bool>,
+    },
+}
+
+/// Reads a graph from GraphML
+pub fn read(input: impl BufRead) -> anyhow::Result<Graph> {
+    let mut reader = Reader::from_reader(LineCounter {
+        inner: input,
+        lines: 0,
+    });
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+
+    // Name of the attribute by key id
+    let mut keys: FxHashMap<String, String> = FxHashMap::default();
+    let mut directed = true;
+    let mut items = Vec::new();
+    let mut item: Option<Item> = None;
+    let mut data_key: Option<String> = None;
+
+    loop {
+        let event = reader.read_event_into(&mut buf)?;
+        let is_empty = matches!(event, Event::Empty(_));
+        match event {
+            Event::Start(element) | Event::Empty(element) => match element.name().as_ref() {
+                b"key" => {
+                    let id = attribute(&element, "id")?.context("Key without id")?;
+                    let name = attribute(&element, "attr.name")?.unwrap_or_else(|| id.clone());
+                    keys.insert(id, name);
+                }
+                b"graph" => {
+                    directed = attribute(&element, "edgedefault")?.as_deref() != Some("undirected");
+                }
+                b"node" => {
+                    let id = attribute(&element, "id")?.context("Node without id")?;
+                    item = Some(Item {
+                        kind: ItemKind::Node { id },
+                        line: reader.get_ref().line(),
+                        data: Vec::new(),
+                    });
+                }
+                b"edge" => {
+                    let source = attribute(&element, "source")?.context("Edge without source")?;
+                    let target = attribute(&element, "target")?.context("Edge without target")?;
+                    let directed = attribute(&element, "directed")?.map(|value| value == "true");
+                    item = Some(Item {
+                        kind: ItemKind::Edge {
+                            source,
+                            target,
+                            directed,
+                        },
+                        line: reader.get_ref().line(),
+                        data: Vec::new(),
+                    });
+                }
+                b"data" if !is_empty => {
+                    data_key = attribute(&element, "key")?;
+                }
+                _ => {}
+            },
+            Event::Text(text) => {
+                if let (Some(item), Some(key)) = (&mut item, &data_key) {
+                    item.data.push((key.clone(), text.unescape()?.into_owned()));
+                }
+            }
+            Event::End(element) => match element.name().as_ref() {
+                b"data" => data_key = None,
+                b"node" | b"edge" => items.extend(item.take()),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+
+        // Empty nodes and edges have no end tag
+        if is_empty {
+            items.extend(item.take());
+        }
+        buf.clear();
+    }
+
+    build_graph(items, &keys, directed)
+}
+
+fn build_graph(
+    items: Vec<Item>,
+    keys: &FxHashMap<String, String>,
+    directed_by_default: bool,
+) -> anyhow::Result<Graph> {
+    // Value of the first data entry whose key has one of the `names`
+    let value = |data: &[(String, String)], names: &[&str]| -> Option<String> {
+        data.iter()
+            .find(|(key, _)| names.contains(&keys.get(key).unwrap_or(key).as_str()))
+            .map(|(_, value)| value.clone())
+    };
+    let parse = |value: String| -> anyhow::Result<f64> {
+        value
+            .parse()
+            .map_err(|_| anyhow!("Invalid number {}", value))
+    };
+
+    let mut g = Graph::new();
+    let mut node_ids: FxHashMap<String, usize> = FxHashMap::default();
+    for item in &items {
+        if let ItemKind::Node { id } = &item.kind {
+            let osm_id = match value(&item.data, &ID_KEYS) {
+                Some(osm_id) => osm_id
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid osm id {}", osm_id))?,
+                None => id.parse().unwrap_or(g.nodes.len()),
+            };
+            let lat = value(&item.data, &LAT_KEYS).map_or(Ok(0.0), parse)?;
+            let lon = value(&item.data, &LON_KEYS).map_or(Ok(0.0), parse)?;
+
+            node_ids.insert(id.clone(), g.nodes.len());
+            g.add_node(Node::new(osm_id, lat, lon));
+        }
+    }
+
+    for item in &items {
+        if let ItemKind::Edge {
+            source,
+            target,
+            directed,
+        } = &item.kind
+        {
+            let node = |id: &String| {
+                node_ids
+                    .get(id)
+                    .copied()
+                    .with_context(|| format!("Node {} does not exist", id))
+            };
+            let (source_idx, target_idx) = (node(source)?, node(target)?);
+            let weight = value(&item.data, &WEIGHT_KEYS)
+                .with_context(|| format!("Edge {} -> {} has no weight", source, target))
+                .and_then(parse)?;
+            if !weight.is_finite() || weight <= 0.0 {
+                bail!(
+                    "Line {}: Edge {} -> {} has invalid weight {}",
+                    item.line,
+                    source,
+                    target,
+                    weight
+                );
+            }
+
+            g.add_edge(Edge::new(
+                node_index(source_idx),
+                node_index(target_idx),
+                weight,
+            ));
+            if !directed.unwrap_or(directed_by_default) {
+                g.add_edge(Edge::new(
+                    node_index(target_idx),
+                    node_index(source_idx),
+                    weight,
+                ));
+            }
+        }
+    }
+
+    info!(
+        "Graph has {} nodes and {} edges",
+        g.nodes.len(),
+        g.edges.len()
+    );
+    Ok(g)
+}
+
+/// Counts the newlines read from `inner`
+struct LineCounter<R> {
+    inner: R,
+    lines: usize,
+}
+
+impl<R> LineCounter<R> {
+    /// Current 1-based line
+    fn line(&self) -> usize {
+        self.lines + 1
+    }
+}
+
+impl<R: Read> Read for LineCounter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.lines += buf[..len].iter().filter(|byte| **byte == b'\n').count();
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for LineCounter<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The consumed bytes are still buffered, so this doesn't read
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.lines += buf[..amt].iter().filter(|byte| **byte == b'\n').count();
+        }
+        self.inner.consume(amt)
+    }
+}
+
+/// Unescaped value of the attribute `name` of `element`
+fn attribute(element: &BytesStart, name: &str) -> anyhow::Result<Option<String>> {
+    match element.try_get_attribute(name)? {
+        Some(attribute) => Ok(Some(attribute.unescape_value()?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+/// Writes the original edges of `g` to a GraphML file
+pub fn write_file(g: &Graph, path: &Path) -> anyhow::Result<()> {
+    write(g, BufWriter::new(File::create(path)?))
+}
+
+/// Writes the original edges of `g` as GraphML into `out`. The nodes are named by their index,
+/// the [`Node::id`] is written as `osm_id`.
+pub fn write(g: &Graph, mut out: impl Write) -> anyhow::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="osm_id" for="node" attr.name="osm_id" attr.type="long"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="lat" for="node" attr.name="lat" attr.type="double"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="lon" for="node" attr.name="lon" attr.type="double"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="weight" for="edge" attr.name="weight" attr.type="double"/>"#
+    )?;
+    writeln!(out, r#"  <graph id="G" edgedefault="directed">"#)?;
+
+    for (i, node) in g.nodes.iter().enumerate() {
+        writeln!(
+            out,
+            r#"    <node id="n{}"><data key="osm_id">{}</data><data key="lat">{}</data><data key="lon">{}</data></node>"#,
+            i, node.id, node.lat, node.lon
+        )?;
+    }
+    for edge in original_edges(g) {
+        writeln!(
+            out,
+            r#"    <edge source="n{}" target="n{}"><data key="weight">{}</data></edge>"#,
+            edge.source.index(),
+            edge.target.index(),
+            edge.weight
+        )?;
+    }
+
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::test_graphs::generate_complex_graph;
+
+    use super::*;
+
+    #[test]
+    fn read_sample() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/sample.graphml");
+        let g = Graph::from_graphml(&path).unwrap();
+
+        assert_eq!(4, g.nodes.len());
+        assert_eq!(
+            vec![21585446, 21585447, 26168195, 1],
+            g.nodes.iter().map(|node| node.id).collect::<Vec<_>>()
+        );
+        assert_eq!((48.137154, 11.576124), (g.nodes[0].lat, g.nodes[0].lon));
+        assert_eq!((0.0, 0.0), (g.nodes[3].lat, g.nodes[3].lon));
+
+        // One one-way edge, two undirected edges
+        assert_eq!(5, g.edges.len());
+        let weights: Vec<_> = g
+            .edges
+            .iter()
+            .map(|edge| (edge.source.index(), edge.target.index(), edge.weight))
+            .collect();
+        assert_eq!(
+            vec![
+                (0, 1, 803.5),
+                (1, 2, 411.0),
+                (2, 1, 411.0),
+                (2, 3, 12.25),
+                (3, 2, 12.25)
+            ],
+            weights
+        );
+        assert_eq!(None, g.metric);
+    }
+
+    #[test]
+    fn write_and_read() {
+        let g = generate_complex_graph();
+
+        let mut out = Vec::new();
+        write(&g, &mut out).unwrap();
+        let imported = read(out.as_slice()).unwrap();
+
+        assert_eq!(g.nodes.len(), imported.nodes.len());
+        for (a, b) in g.nodes.iter().zip(&imported.nodes) {
+            assert_eq!((a.id, a.lat, a.lon), (b.id, b.lat, b.lon));
+        }
+        assert_eq!(g.edges.len(), imported.edges.len());
+        for (a, b) in g.edges.iter().zip(&imported.edges) {
+            assert_eq!(
+                (a.source, a.target, a.weight),
+                (b.source, b.target, b.weight)
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_files_are_errors() {
+        let edge_without_weight = r#"<graphml><graph edgedefault="directed">
+            <node id="a"/><node id="b"/><edge source="a" target="b"/>
+        </graph></graphml>"#;
+        assert!(read(edge_without_weight.as_bytes()).is_err());
+
+        let unknown_node = r#"<graphml><key id="d0" for="edge" attr.name="length"/>
+            <graph edgedefault="directed">
+            <node id="a"/><edge source="a" target="b"><data key="d0">1</data></edge>
+        </graph></graphml>"#;
+        assert!(read(unknown_node.as_bytes()).is_err());
+
+        let zero_weight = r#"<graphml><key id="d0" for="edge" attr.name="length"/>
+            <graph edgedefault="directed">
+            <node id="a"/><node id="b"/>
+            <edge source="a" target="b"><data key="d0">0</data></edge>
+        </graph></graphml>"#;
+        let error = read(zero_weight.as_bytes()).err().unwrap();
+        assert!(error.to_string().starts_with("Line 4:"), "{}", error);
+
+        let nan_weight = zero_weight.replace(">0<", ">NaN<");
+        assert!(read(nan_weight.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+//! Import and export of generic graph formats, used to compare against published results and
+//! to exchange graphs with other tools.
+//!
+//! - [`dimacs`]: road networks of the 9th DIMACS implementation challenge (`.gr` and `.co`)
+//! - [`graphml`]: GraphML, e.g. written by OSMnx or read by Gephi and yEd
+//!
+//! Only the original edges are exported, shortcuts are skipped.
+//!
+//! # Examples
+//! ```no_run
+//! use std::path::Path;
+//! use ch_core::prelude::*;
+//!
+//! let g = Graph::from_dimacs(Path::new("USA-road-d.NY.gr"), Some(Path::new("USA-road-d.NY.co")))
+//!     .expect("Failed to read DIMACS graph");
+//! g.export_graphml("USA-road-d.NY.graphml").expect("Failed to write GraphML");
+//! ```
+pub mod dimacs;
+pub mod graphml;
+
+use crate::graph::{Edge, Graph};
+
+/// All edges of `g` which are no shortcuts
+fn original_edges(g: &Graph) -> &[Edge] {
+    &g.edges[..g.edges.len() - g.num_shortcuts]
+}
@@ -1,13 +1,26 @@
 //! (Preperation-) Graph module. The directed graph is represented as an adjacency lists and used for node contraction.
-use crate::constants::{OSMId, Weight};
+use crate::{
+    components::{self, ComponentFilter, Components},
+    constants::{OSMId, Weight},
+    formats,
+    serialization::{self, BuildMetadata, Compression, FormatError, GRAPH_MAGIC},
+    util::math::Metric,
+    ways::WayTable,
+};
 use anyhow::{Context, Ok};
-use log::{debug, info};
-use osm_reader::*;
-use rustc_hash::FxHashMap;
+use log::{debug, info, warn};
+use osm_reader::{
+    clip::{BoundingBox, Region},
+    reader::PbfReader,
+    *,
+};
+use rustc_hash::{FxHashMap, FxHasher};
 use serde::{Deserialize, Serialize};
 use std::{
     fmt,
-    hash::Hash,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::Write,
     path::{Path, PathBuf},
 };
 
@@ -83,6 +96,7 @@ impl IndexType for u8 {
 
 /// Node identifier.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[repr(transparent)]
 pub struct NodeIndex<Idx = DefaultIdx>(Idx);
 
 impl NodeIndex {
@@ -117,6 +131,7 @@ pub fn node_index(index: usize) -> NodeIndex {
 #[derive(
     Debug, Copy, Clone, Default, PartialEq, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize,
 )]
+#[repr(transparent)]
 pub struct EdgeIndex<Idx = DefaultIdx>(Idx);
 
 impl<Idx: IndexType> From<Idx> for EdgeIndex<Idx> {
@@ -146,6 +161,7 @@ impl<Idx: IndexType> EdgeIndex<Idx> {
 
 /// Represents OSM Node type (<https://wiki.openstreetmap.org/wiki/Node>)
 #[derive(Debug, Deserialize, Serialize, Clone)]
+#[repr(C)]
 pub struct Node {
     pub id: OSMId,
     pub lat: f64,
@@ -160,6 +176,7 @@ impl Node {
 
 /// A weighted `Edge` which connects a `source` and a `target` node.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[repr(C)]
 pub struct Edge<Idx = DefaultIdx> {
     pub source: NodeIndex<Idx>,
     pub target: NodeIndex<Idx>,
@@ -190,6 +207,29 @@ pub struct Graph<Idx = DefaultIdx> {
     pub nodes: Vec<Node>,
     pub edges: Vec<Edge<Idx>>,
     pub num_shortcuts: usize,
+    /// Name of the file the graph was parsed from
+    #[serde(skip)]
+    pub source: Option<String>,
+    /// Strongly connected components if the graph was tagged with
+    /// [`ComponentFilter::Tag`]. Searches use them to skip unreachable targets.
+    #[serde(skip)]
+    pub components: Option<Components>,
+    /// OSM ways of the original edges. Empty if the graph was not imported from OSM.
+    #[serde(skip)]
+    pub ways: WayTable,
+    /// Index of the nodes by their [`Node::id`]. Maintained by [`Graph::add_node`], ids changed
+    /// later through [`Graph::nodes_mut`] are not reflected.
+    #[serde(skip)]
+    osm_ids: FxHashMap<OSMId, NodeIndex>,
+    /// Distances between the nodes and the straight line estimate of A*. Matches the
+    /// [`EarthModel`](osm_reader::geo::EarthModel) and speeds of the import. `None` if the
+    /// graph was not imported from OSM, its weights are no travel times then.
+    #[serde(skip)]
+    pub metric: Option<Metric>,
+    /// Fingerprint of the options the graph was imported with, see
+    /// [`Graph::from_pbf_reader_cached`]. `None` if the graph was not imported from OSM.
+    #[serde(skip)]
+    pub fingerprint: Option<u64>,
 }
 
 impl Graph {
@@ -201,6 +241,12 @@ impl Graph {
             nodes: Vec::new(),
             edges: Vec::new(),
             num_shortcuts: 0,
+            source: None,
+            components: None,
+            ways: WayTable::default(),
+            osm_ids: FxHashMap::default(),
+            metric: None,
+            fingerprint: None,
         }
     }
 
@@ -212,6 +258,12 @@ impl Graph {
             nodes: Vec::with_capacity(num_nodes),
             edges: Vec::with_capacity(num_edges),
             num_shortcuts: 0,
+            source: None,
+            components: None,
+            ways: WayTable::default(),
+            osm_ids: FxHashMap::with_capacity_and_hasher(num_nodes, Default::default()),
+            metric: None,
+            fingerprint: None,
         }
     }
 
@@ -235,10 +287,14 @@ impl Graph {
     /// Returns the index of the new created edge.
     pub fn add_shortcut(&mut self, edge: Edge) -> EdgeIndex {
         self.num_shortcuts += 1;
-        self.add_edge_internal(edge, false)
+        // Shortcuts don't change which nodes are reachable, so tagged components stay valid
+        let components = self.components.take();
+        let edge_idx = self.add_edge_internal(edge, false);
+        self.components = components;
+        edge_idx
     }
 
-    fn add_edge_internal(&mut self, edge: Edge, allow_weight_update: bool) -> EdgeIndex {
+    pub(crate) fn add_edge_internal(&mut self, edge: Edge, allow_weight_update: bool) -> EdgeIndex {
         let edge_idx = EdgeIndex::new(self.edges.len());
 
         assert!(
@@ -262,6 +318,8 @@ impl Graph {
             "Target node index ({}) does not exist",
             edge.target.index()
         );
+        // The new edge may connect components
+        self.components = None;
 
         // If an edge already exists between source and target but the new edge
         // has a lower weight, replace the old edge with the new one (update the weight)
@@ -304,10 +362,15 @@ impl Graph {
             std::any::type_name::<DefaultIdx>()
         );
 
+        // The tagged components don't know the new node
+        self.components = None;
+
         // Create new entry in adjacency list for new node
         self.edges_in.push(Vec::new());
         self.edges_out.push(Vec::new());
 
+        // The first node with an id is kept if ids are not unique
+        self.osm_ids.entry(node.id).or_insert(node_idx);
         self.nodes.push(node);
 
         node_idx
@@ -318,6 +381,36 @@ impl Graph {
         self.nodes.get(node_idx.index())
     }
 
+    /// Returns the index of the node with the OSM id `id`, e.g. to cross-check results against
+    /// openstreetmap.org. If several nodes have the same id, the first one is returned.
+    /// Straight line estimate of the weight from `src` to `dst` with the [`Graph::metric`].
+    /// Zero for graphs without metric, which keeps A* admissible for arbitrary weights.
+    pub fn straight_line(&self, src: &Node, dst: &Node) -> Weight {
+        self.metric
+            .map_or(0.0, |metric| metric.straight_line(src, dst))
+    }
+
+    pub fn node_by_osm_id(&self, id: OSMId) -> Option<NodeIndex> {
+        self.osm_ids.get(&id).copied()
+    }
+
+    /// Rebuilds the index of [`Graph::node_by_osm_id`], which is not serialized
+    pub(crate) fn rebuild_osm_ids(&mut self) {
+        // Inserting in reverse keeps the first node of duplicate ids like `add_node`
+        self.osm_ids = self
+            .nodes
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, node)| (node.id, NodeIndex::new(i)))
+            .collect();
+    }
+
+    /// Returns the OSM id of the node with the given index if it exists
+    pub fn osm_id(&self, node_idx: NodeIndex) -> Option<OSMId> {
+        self.node(node_idx).map(|node| node.id)
+    }
+
     /// Returns an iterator over all nodes of the graph
     pub fn nodes(&self) -> impl Iterator<Item = &Node> {
         self.nodes.iter()
@@ -356,6 +449,101 @@ impl Graph {
             .map(|edge_idx| (*edge_idx, &self.edges[edge_idx.index()]))
     }
 
+    /// Computes the strongly connected components. See [`crate::components`].
+    pub fn strongly_connected_components(&self) -> Components {
+        components::strongly_connected_components(self)
+    }
+
+    /// Applies `filter` to the strongly connected components of the graph. With
+    /// [`ComponentFilter::Largest`] all nodes outside of the largest component are removed, with
+    /// [`ComponentFilter::Tag`] the components are stored in [`Graph::components`].
+    pub fn filter_components(&mut self, filter: ComponentFilter) {
+        if filter == ComponentFilter::None {
+            return;
+        }
+
+        let components = self.strongly_connected_components();
+        match filter {
+            ComponentFilter::None => {}
+            ComponentFilter::Largest => {
+                let largest = components.largest();
+                *self = self.subgraph(|node| Some(components.component(node)) == largest);
+                info!(
+                    "Kept the largest component with {} nodes and {} edges",
+                    self.nodes.len(),
+                    self.edges.len()
+                );
+            }
+            ComponentFilter::Tag => self.components = Some(components),
+        }
+    }
+
+    /// Returns false if `target` is known to be unreachable from `source`. Always true if the
+    /// components are not tagged.
+    pub fn may_reach(&self, source: NodeIndex, target: NodeIndex) -> bool {
+        match &self.components {
+            Some(components) => components.may_reach(source, target),
+            None => true,
+        }
+    }
+
+    /// Returns the graph induced by the nodes for which `keep` returns true. Nodes are
+    /// renumbered in their original order. Shortcuts are not copied.
+    pub fn subgraph(&self, keep: impl Fn(NodeIndex) -> bool) -> Graph {
+        let mut new_index = vec![None; self.nodes.len()];
+        let mut g = Graph::new();
+        g.ways = self.ways.without_edges();
+        for (node_idx, node) in self.nodes().enumerate() {
+            if keep(NodeIndex::new(node_idx)) {
+                new_index[node_idx] = Some(g.add_node(node.clone()));
+            }
+        }
+
+        for (edge_idx, edge) in self.edges[..self.edges.len() - self.num_shortcuts]
+            .iter()
+            .enumerate()
+        {
+            if let (Some(source), Some(target)) = (
+                new_index[edge.source.index()],
+                new_index[edge.target.index()],
+            ) {
+                // The edges were already merged when they were added to this graph
+                let new_edge_idx =
+                    g.add_edge_internal(Edge::new(source, target, edge.weight), false);
+                if let Some(way_idx) = self.ways.way_idx(EdgeIndex::new(edge_idx)) {
+                    g.ways.set(new_edge_idx, way_idx);
+                }
+            }
+        }
+
+        g.source = self.source.clone();
+        g.metric = self.metric;
+        g
+    }
+
+    /// Returns the graph induced by the nodes inside `bbox`. Edges crossing the border are
+    /// removed, like [`CrossingWays::Split`] does for ways during the import.
+    ///
+    /// [`CrossingWays::Split`]: osm_reader::clip::CrossingWays::Split
+    pub fn subgraph_in_bbox(&self, bbox: &BoundingBox) -> Graph {
+        self.subgraph_in_region(&Region::BoundingBox(*bbox))
+    }
+
+    /// Returns the graph induced by the nodes inside `region`, e.g. a polygon read with
+    /// [`Polygon::from_file`](osm_reader::clip::Polygon::from_file).
+    pub fn subgraph_in_region(&self, region: &Region) -> Graph {
+        let g = self.subgraph(|node_idx| {
+            let node = &self.nodes[node_idx.index()];
+            region.contains(node.lat, node.lon)
+        });
+        info!(
+            "Clipped graph to {} nodes and {} edges",
+            g.nodes.len(),
+            g.edges.len()
+        );
+        g
+    }
+
     /// Prints some information about the graph
     pub fn print_info(&self) {
         println!(
@@ -415,7 +603,20 @@ impl Graph {
 
         let road_graph = RoadGraph::from_pbf(path_to_pbf).context("Could not parse pbf file")?;
 
-        parse_road_graph(road_graph)
+        let mut g = parse_road_graph(road_graph)?;
+        g.source = source_name(path_to_pbf);
+        Ok(g)
+    }
+
+    /// Parses a pbf file with the options of `reader`, e.g. on multiple threads
+    pub fn from_pbf_reader(reader: &PbfReader) -> anyhow::Result<Self> {
+        info!("Parsing pbf file: {:?}", reader.path());
+
+        let road_graph = reader.read().context("Could not parse pbf file")?;
+
+        let mut g = parse_road_graph(road_graph)?;
+        g.source = source_name(reader.path());
+        Ok(g)
     }
 
     pub fn from_pbf_with_simplification(path_to_pbf: &Path) -> anyhow::Result<Self> {
@@ -424,12 +625,155 @@ impl Graph {
         let road_graph = RoadGraph::from_pbf_with_simplification(path_to_pbf)
             .context("Could not parse pbf file")?;
 
-        parse_road_graph(road_graph)
+        let mut g = parse_road_graph(road_graph)?;
+        g.source = source_name(path_to_pbf);
+        Ok(g)
+    }
+
+    /// Parses a pbf file like [`Graph::from_pbf`] or [`Graph::from_pbf_with_simplification`],
+    /// but caches the result in a binary file next to the pbf file. See
+    /// [`Graph::from_pbf_reader_cached`].
+    pub fn from_pbf_cached(path_to_pbf: &Path, simplify: bool) -> anyhow::Result<Self> {
+        let reader = PbfReader::new(path_to_pbf).simplify(simplify);
+        Graph::from_pbf_reader_cached(&reader, ComponentFilter::None)
+    }
+
+    /// Parses a pbf file with the options of `reader`, applies `filter` and caches the result
+    /// in a binary file next to the pbf file. Subsequent calls load the cached graph, as long
+    /// as it is newer than the pbf file and was built with the same options. The options are
+    /// compared by the fingerprint in the header of the cache, see [`PbfReader::fingerprint`].
+    pub fn from_pbf_reader_cached(
+        reader: &PbfReader,
+        filter: ComponentFilter,
+    ) -> anyhow::Result<Self> {
+        let cache = cache_path(reader.path(), reader.is_simplified());
+        let fingerprint = build_fingerprint(reader, filter);
+
+        if is_cache_valid(reader.path(), &cache, fingerprint) {
+            match Graph::from_decode(&cache) {
+                Result::Ok(g) => {
+                    info!("Loaded cached graph from {:?}", cache);
+                    return Ok(g);
+                }
+                Err(e) => warn!("Failed to load cached graph {:?}: {}", cache, e),
+            }
+        }
+
+        let mut g = Graph::from_pbf_reader(reader)?;
+        g.filter_components(filter);
+        g.fingerprint = Some(fingerprint);
+
+        // Write to a temporary file first, so concurrent readers never see a partial file
+        let tmp = cache.with_extension(format!("{}.tmp", std::process::id()));
+        let written = g
+            .encode(&tmp)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| std::fs::rename(&tmp, &cache).map_err(anyhow::Error::from));
+        if let Err(e) = written {
+            // The cache is only an optimization, the graph is still usable
+            warn!("Failed to write graph cache {:?}: {}", cache, e);
+            let _ = std::fs::remove_file(&tmp);
+        }
+
+        Ok(g)
+    }
+
+    /// Writes the graph in the versioned binary format without compression.
+    /// See [`crate::serialization`].
+    ///
+    /// Returns the number of bytes written.
+    pub fn encode(&self, path: impl Into<PathBuf>) -> Result<usize, FormatError> {
+        self.encode_with_compression(path, Compression::None)
+    }
+
+    /// Writes the graph in the versioned binary format and compresses it with `compression`.
+    ///
+    /// Returns the number of bytes written.
+    pub fn encode_with_compression(
+        &self,
+        path: impl Into<PathBuf>,
+        compression: Compression,
+    ) -> Result<usize, FormatError> {
+        serialization::write_file(
+            &path.into(),
+            GRAPH_MAGIC,
+            &BuildMetadata {
+                fingerprint: self.fingerprint,
+                ..BuildMetadata::new(self.source.clone(), None)
+            },
+            compression,
+            &CompactGraph::from(self),
+        )
+    }
+
+    /// Reads a graph written by [`Graph::encode`]. The compression is detected from the header.
+    pub fn from_decode(path: impl Into<PathBuf>) -> Result<Self, FormatError> {
+        let mut body = serialization::read_file(&path.into(), GRAPH_MAGIC)?;
+
+        // Graph files were never written without a header
+        if body.header().version == 0 {
+            return Err(FormatError::InvalidMagic);
+        }
+        let mut g: Graph = body.decode::<CompactGraph>()?.into();
+        let header = body.finish()?;
+        g.source = header.metadata.source;
+        g.fingerprint = header.metadata.fingerprint;
+
+        Result::Ok(g)
+    }
+
+    /// Reads a road network of the 9th DIMACS challenge. See [`formats::dimacs`].
+    pub fn from_dimacs(path_to_gr: &Path, path_to_co: Option<&Path>) -> anyhow::Result<Self> {
+        info!("Parsing DIMACS file: {:?}", path_to_gr);
+        let mut g = formats::dimacs::read_file(path_to_gr, path_to_co)?;
+        g.source = source_name(path_to_gr);
+        Ok(g)
+    }
+
+    /// Writes the graph as `.gr` file and the coordinates as `.co` file. Shortcuts are skipped
+    /// and the weights are multiplied by `weight_scale` and rounded. See [`formats::dimacs`].
+    pub fn export_dimacs(
+        &self,
+        path_to_gr: &Path,
+        path_to_co: &Path,
+        weight_scale: f64,
+    ) -> anyhow::Result<()> {
+        formats::dimacs::write_file(self, path_to_gr, path_to_co, weight_scale)
+    }
+
+    /// Reads a GraphML file. See [`formats::graphml`].
+    pub fn from_graphml(path: &Path) -> anyhow::Result<Self> {
+        info!("Parsing GraphML file: {:?}", path);
+        let mut g = formats::graphml::read_file(path)?;
+        g.source = source_name(path);
+        Ok(g)
+    }
+
+    /// Writes the graph as GraphML. Shortcuts are skipped. See [`formats::graphml`].
+    pub fn export_graphml(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        formats::graphml::write_file(self, path.as_ref())
     }
 
     /// Writes all nodes as `nodes.csv` and edges as `edges.csv` to the current directory
     pub fn export_csv(&self) -> anyhow::Result<()> {
-        let mut wtr = csv::Writer::from_path("nodes.csv")?;
+        self.export_csv_to(".")
+    }
+
+    /// Writes all nodes as `nodes.csv` and edges as `edges.csv` to `dir`. The directory is
+    /// created if it does not exist.
+    pub fn export_csv_to(&self, dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        self.write_csv_into(
+            File::create(dir.join("nodes.csv"))?,
+            File::create(dir.join("edges.csv"))?,
+        )
+    }
+
+    /// Writes all nodes and edges as CSV into the given writers.
+    pub fn write_csv_into<W: Write>(&self, nodes: W, edges: W) -> anyhow::Result<()> {
+        let mut wtr = csv::Writer::from_writer(nodes);
 
         debug!("BEGIN writing nodes");
         for node in self.nodes() {
@@ -439,7 +783,7 @@ impl Graph {
         wtr.flush()?;
         debug!("FINISHED writing nodes");
 
-        let mut wtr = csv::Writer::from_path("edges.csv")?;
+        let mut wtr = csv::Writer::from_writer(edges);
         wtr.write_record(["source", "target", "weight"])?;
         debug!("BEGIN writing edges");
         for edge in self.edges() {
@@ -456,33 +800,91 @@ impl Graph {
     }
 }
 
-fn parse_road_graph(road_graph: RoadGraph) -> Result<Graph, anyhow::Error> {
-    let mut node_index: FxHashMap<i64, usize> =
-        FxHashMap::with_capacity_and_hasher(road_graph.get_nodes().len(), Default::default());
+fn cache_path(path_to_pbf: &Path, simplify: bool) -> PathBuf {
+    let suffix = if simplify {
+        "graph.bin"
+    } else {
+        "raw.graph.bin"
+    };
+    match path_to_pbf.extension() {
+        Some(ext) => path_to_pbf.with_extension(format!("{}.{}", ext.to_string_lossy(), suffix)),
+        None => path_to_pbf.with_extension(suffix),
+    }
+}
 
-    let mut g = Graph::with_capacity(road_graph.get_nodes().len(), road_graph.get_arcs().len());
+/// Fingerprint of the import options of `reader` and the component filter
+fn build_fingerprint(reader: &PbfReader, filter: ComponentFilter) -> u64 {
+    let mut hasher = FxHasher::default();
+    reader.fingerprint().hash(&mut hasher);
+    filter.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn is_cache_valid(path_to_pbf: &Path, cache: &Path, fingerprint: u64) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    let is_newer = match (modified(path_to_pbf), modified(cache)) {
+        (Some(pbf), Some(cache)) => cache >= pbf,
+        _ => false,
+    };
+    if !is_newer {
+        return false;
+    }
+
+    match serialization::read_header(cache, GRAPH_MAGIC) {
+        Result::Ok(header) if header.metadata.fingerprint == Some(fingerprint) => true,
+        Result::Ok(_) => {
+            info!("Graph cache {:?} was built with other options", cache);
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+fn source_name(path: &Path) -> Option<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
 
-    for (i, (id, [lat, lon])) in road_graph.get_nodes().iter().enumerate() {
-        let node = Node::new(*id as usize, *lat, *lon);
-        node_index.insert(*id, i);
+fn parse_road_graph(road_graph: RoadGraph) -> Result<Graph, anyhow::Error> {
+    let mut g = Graph::with_capacity(road_graph.get_nodes().len(), road_graph.get_arcs().len());
+    g.metric = Some(Metric::new(
+        road_graph.get_earth_model(),
+        road_graph.get_max_speed(),
+    ));
+
+    let osm_id =
+        |id: &i64| OSMId::try_from(*id).with_context(|| format!("Node has invalid OSM id {}", id));
+    for (id, [lat, lon]) in road_graph.get_nodes().iter() {
+        let node = Node::new(osm_id(id)?, *lat, *lon);
         g.add_node(node);
     }
+    let node_index = |g: &Graph, id: &i64| {
+        g.node_by_osm_id(osm_id(id)?)
+            .with_context(|| format!("Arc references unknown node {}", id))
+    };
 
+    // Index of every way in the way table
+    let mut way_index: FxHashMap<i64, usize> = FxHashMap::default();
     for Arc {
         source,
         target,
         weight,
+        way_id,
     } in road_graph.get_arcs()
     {
         if *weight <= 0.0 {
             continue;
         }
-        let edge: Edge = Edge::new(
-            NodeIndex::new(node_index[source]),
-            NodeIndex::new(node_index[target]),
-            *weight,
-        );
-        g.add_edge(edge);
+        let edge: Edge = Edge::new(node_index(&g, source)?, node_index(&g, target)?, *weight);
+        let edge_idx = g.add_edge(edge);
+
+        if let Some(way) = road_graph.get_ways().get(way_id) {
+            let way_idx = *way_index
+                .entry(*way_id)
+                .or_insert_with(|| g.ways.add_way(way.clone()));
+            g.ways.set(edge_idx, way_idx);
+        }
     }
 
     info!("Finished parsing pbf file");
@@ -500,6 +902,55 @@ impl Default for Graph {
     }
 }
 
+/// Space efficient representation of a [`Graph`] used for serialization. The adjacency
+/// lists are delta encoded.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CompactGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    edges_in: Vec<Vec<i64>>,
+    edges_out: Vec<Vec<i64>>,
+    num_shortcuts: usize,
+    ways: WayTable,
+    metric: Option<Metric>,
+    components: Option<Components>,
+}
+
+impl From<&Graph> for CompactGraph {
+    fn from(g: &Graph) -> Self {
+        CompactGraph {
+            nodes: g.nodes.clone(),
+            edges: g.edges.clone(),
+            edges_in: serialization::delta_encode(&g.edges_in),
+            edges_out: serialization::delta_encode(&g.edges_out),
+            num_shortcuts: g.num_shortcuts,
+            ways: g.ways.clone(),
+            metric: g.metric,
+            components: g.components.clone(),
+        }
+    }
+}
+
+impl From<CompactGraph> for Graph {
+    fn from(g: CompactGraph) -> Self {
+        let mut graph = Graph {
+            edges_in: serialization::delta_decode(g.edges_in),
+            edges_out: serialization::delta_decode(g.edges_out),
+            nodes: g.nodes,
+            edges: g.edges,
+            num_shortcuts: g.num_shortcuts,
+            source: None,
+            components: g.components,
+            ways: g.ways,
+            osm_ids: FxHashMap::default(),
+            metric: g.metric,
+            fingerprint: None,
+        };
+        graph.rebuild_osm_ids();
+        graph
+    }
+}
+
 /// Macro to create a edge from source to target with a weight
 /// ```
 /// // Returns two edges. One for each direction
@@ -531,6 +982,8 @@ macro_rules! node {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::test_graphs::{generate_complex_graph, minimal_osm_path};
+    use osm_reader::geo::EarthModel;
 
     #[test]
     fn read_from_csv() {
@@ -556,6 +1009,180 @@ mod tests {
         assert_eq!(graph.edges_out.len(), 2);
     }
 
+    #[test]
+    fn read_from_xml() {
+        let path = minimal_osm_path();
+        let graph: Graph = Graph::from_pbf(&path).unwrap();
+
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.edges.len(), 2);
+    }
+
+    #[test]
+    fn keep_ways_of_edges() {
+        let path = minimal_osm_path();
+        let g = Graph::from_pbf(&path).unwrap();
+
+        assert_eq!(1, g.ways.len());
+        for edge_idx in 0..g.edges.len() {
+            let way = g.ways.way(EdgeIndex::new(edge_idx)).unwrap();
+            assert_eq!(12, way.id);
+            assert_eq!(osm_reader::RoadType::Secondary, way.road_type);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let graph_path = dir.path().join("minimal_ways.graph.bin");
+        g.encode(&graph_path).unwrap();
+        let g_imported = Graph::from_decode(&graph_path).unwrap();
+        assert_eq!(g.ways, g_imported.ways);
+
+        let subgraph = g.subgraph(|_| true);
+        assert_eq!(
+            Some(12),
+            subgraph.ways.way(EdgeIndex::new(1)).map(|way| way.id)
+        );
+
+        // Contraction only appends shortcuts, the original edges keep their ways
+        let mut contracted = g.clone();
+        let overlay_graph = crate::node_contraction::NodeContractor::new(&mut contracted).run();
+        assert_eq!(g.ways, overlay_graph.road_graph().ways);
+    }
+
+    #[test]
+    fn keep_metric_of_import() {
+        let path = minimal_osm_path();
+        let profile =
+            osm_reader::profile::SpeedProfile::from_toml("[highway]\nmotorway = 130").unwrap();
+        let reader = PbfReader::new(&path)
+            .earth_model(EarthModel::Karney)
+            .profile(profile);
+        let g = Graph::from_pbf_reader(&reader).unwrap();
+        assert_eq!(Some(Metric::new(EarthModel::Karney, 130.0)), g.metric);
+
+        // The estimate never exceeds the weight of an edge
+        for edge in g.edges.iter() {
+            let (source, target) = (&g.nodes[edge.source.index()], &g.nodes[edge.target.index()]);
+            assert!(g.straight_line(source, target) > 0.0);
+            assert!(g.straight_line(source, target) <= edge.weight);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let graph_path = dir.path().join("minimal_metric.graph.bin");
+        g.encode(&graph_path).unwrap();
+        let g_imported = Graph::from_decode(&graph_path).unwrap();
+        assert_eq!(g.metric, g_imported.metric);
+        assert_eq!(g.metric, g.subgraph(|_| true).metric);
+        assert_eq!(None, Graph::new().metric);
+    }
+
+    #[test]
+    fn resolve_osm_ids() {
+        let path = minimal_osm_path();
+        let mut g = Graph::from_pbf(&path).unwrap();
+
+        for id in [8, 10] {
+            let node_idx = g.node_by_osm_id(id).unwrap();
+            assert_eq!(Some(id), g.osm_id(node_idx));
+        }
+        assert_eq!(None, g.node_by_osm_id(12));
+
+        let node_idx = g.add_node(Node::new(12, 0.0, 0.0));
+        assert_eq!(Some(node_idx), g.node_by_osm_id(12));
+        // Duplicate ids resolve to the first node
+        let first = g.node_by_osm_id(8);
+        g.add_node(Node::new(8, 0.0, 0.0));
+        assert_eq!(first, g.node_by_osm_id(8));
+
+        let dir = tempfile::tempdir().unwrap();
+        let graph_path = dir.path().join("osm_ids.graph.bin");
+        g.encode(&graph_path).unwrap();
+        let g_imported = Graph::from_decode(&graph_path).unwrap();
+        let overlay_graph = crate::node_contraction::NodeContractor::new(&mut g.clone()).run();
+        for id in [8, 10, 12] {
+            assert_eq!(g.node_by_osm_id(id), g_imported.node_by_osm_id(id));
+            assert_eq!(g.node_by_osm_id(id), overlay_graph.node_by_osm_id(id));
+        }
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let g = generate_complex_graph();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("complex_graph.graph.bin");
+
+        for compression in [Compression::None, Compression::Zstd] {
+            g.encode_with_compression(&path, compression).unwrap();
+            let g_imported = Graph::from_decode(&path).unwrap();
+
+            assert_eq!(g.edges_in, g_imported.edges_in);
+            assert_eq!(g.edges_out, g_imported.edges_out);
+            assert_eq!(g.nodes.len(), g_imported.nodes.len());
+            assert_eq!(g.num_shortcuts, g_imported.num_shortcuts);
+            for (e1, e2) in g.edges().zip(g_imported.edges()) {
+                assert_eq!(e1.source, e2.source);
+                assert_eq!(e1.target, e2.target);
+                assert_eq!(e1.weight, e2.weight);
+            }
+        }
+
+        // A graph file is no overlay graph file
+        assert!(crate::overlay_graph::OverlayGraph::from_decode(&path).is_err());
+    }
+
+    #[test]
+    fn changed_options_invalidate_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("minimal.osm");
+        std::fs::copy(minimal_osm_path(), &path).unwrap();
+        let cache = cache_path(&path, false);
+
+        let reader = PbfReader::new(&path);
+        let g = Graph::from_pbf_reader_cached(&reader, ComponentFilter::None).unwrap();
+        assert!(g.fingerprint.is_some());
+        assert_eq!(
+            g.fingerprint,
+            Graph::from_decode(&cache).unwrap().fingerprint
+        );
+
+        // Slower roads change the weights, so the cached graph must not be used
+        let profile =
+            osm_reader::profile::SpeedProfile::from_toml("[highway]\nsecondary = 10").unwrap();
+        let reader = PbfReader::new(&path).profile(profile);
+        let g_slow = Graph::from_pbf_reader_cached(&reader, ComponentFilter::None).unwrap();
+        assert_ne!(g.fingerprint, g_slow.fingerprint);
+        for (edge, edge_slow) in g.edges().zip(g_slow.edges()) {
+            assert!(edge_slow.weight > edge.weight);
+        }
+
+        // The cache was replaced and is used for the same options
+        let g_cached = Graph::from_pbf_reader_cached(&reader, ComponentFilter::None).unwrap();
+        assert_eq!(g_slow.fingerprint, g_cached.fingerprint);
+        assert_eq!(g_slow.edges[0].weight, g_cached.edges[0].weight);
+
+        // The component filter is part of the fingerprint as well
+        let g_tagged = Graph::from_pbf_reader_cached(&reader, ComponentFilter::Tag).unwrap();
+        assert_ne!(g_slow.fingerprint, g_tagged.fingerprint);
+        assert!(g_tagged.components.is_some());
+    }
+
+    #[test]
+    fn cache_path_next_to_pbf() {
+        let path = Path::new("test_data/vaterstetten_pp.osm.pbf");
+
+        assert_eq!(
+            cache_path(path, true),
+            Path::new("test_data/vaterstetten_pp.osm.pbf.graph.bin")
+        );
+        assert_eq!(
+            cache_path(path, false),
+            Path::new("test_data/vaterstetten_pp.osm.pbf.raw.graph.bin")
+        );
+        assert_eq!(
+            cache_path(Path::new("test_data/extract"), true),
+            Path::new("test_data/extract.graph.bin")
+        );
+    }
+
     #[test]
     fn add_duplicate_edges() {
         let mut g = Graph::new();
@@ -568,4 +1195,20 @@ mod tests {
         assert_eq!(g.edges.len(), 1);
         assert_eq!(g.edges[edge1.index()].weight, 1.0);
     }
+
+    #[test]
+    fn subgraph_in_bbox() {
+        let g = generate_complex_graph();
+        let bbox = BoundingBox::new(4.0, 9.0, 7.0, 15.0);
+
+        let sub = g.subgraph_in_bbox(&bbox);
+
+        // G, H, I and J with the edges G-H, G-I, H-I, H-J and I-J in both directions
+        let ids: Vec<_> = sub.nodes().map(|node| node.id).collect();
+        assert_eq!(vec![6, 7, 8, 9], ids);
+        assert_eq!(10, sub.edges.len());
+        assert!(sub
+            .edges()
+            .all(|edge| edge.source.index() < 4 && edge.target.index() < 4));
+    }
 }
@@ -0,0 +1,404 @@
+//! Turn-by-turn instructions for unpacked shortest paths.
+//!
+//! The path is split into steps at every change of the street and at every junction where the
+//! path turns. The direction of a turn is derived from the bearings of the edges before and
+//! after the junction, see [`Maneuver::from_angle`]. Roundabouts are merged into a single step
+//! which counts the exits passed.
+//!
+//! The streets are taken from the [`WayTable`](crate::ways::WayTable) of the graph, so graphs
+//! which were not imported from OSM only get instructions with turns. Simplified graphs
+//! (see [`Graph::from_pbf_with_simplification`]) have no geometry between junctions, so the
+//! bearings and distances are measured along straight lines between the junctions.
+//!
+//! # Examples
+//! ```no_run
+//! use std::path::Path;
+//! use ch_core::prelude::*;
+//! use ch_core::guidance;
+//!
+//! let g = Graph::from_pbf(Path::new("path/to/pbf/file.osm.pbf")).unwrap();
+//! let mut dijkstra = search::Dijkstra::new(&g);
+//! let path = dijkstra.search(node_index(3), node_index(20)).unwrap();
+//!
+//! for instruction in guidance::instructions(&g, &path).unwrap() {
+//!     println!("{}", instruction);
+//! }
+//! ```
+use std::fmt::Display;
+
+use rustc_hash::FxHashSet;
+
+use crate::{
+    constants::Weight,
+    graph::{Graph, NodeIndex},
+    search::shortest_path::{Segment, ShortestPath},
+    util::math::bearing,
+};
+
+/// What to do at the start of a step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Maneuver {
+    Depart,
+    Continue,
+    SlightLeft,
+    SlightRight,
+    Left,
+    Right,
+    SharpLeft,
+    SharpRight,
+    UTurn,
+    /// Leave the roundabout at the `exit`th exit
+    Roundabout {
+        exit: usize,
+    },
+    Arrive,
+}
+
+impl Maneuver {
+    /// Classifies the change of direction at a junction. `angle` is the difference between
+    /// the bearing after and before the junction in degrees, positive angles turn right.
+    pub fn from_angle(angle: f64) -> Self {
+        let angle = (angle + 180.0).rem_euclid(360.0) - 180.0;
+        let (left, magnitude) = (angle < 0.0, angle.abs());
+
+        match (magnitude, left) {
+            (m, _) if m <= 20.0 => Maneuver::Continue,
+            (m, true) if m <= 50.0 => Maneuver::SlightLeft,
+            (m, false) if m <= 50.0 => Maneuver::SlightRight,
+            (m, true) if m <= 130.0 => Maneuver::Left,
+            (m, false) if m <= 130.0 => Maneuver::Right,
+            (m, true) if m <= 170.0 => Maneuver::SharpLeft,
+            (m, false) if m <= 170.0 => Maneuver::SharpRight,
+            _ => Maneuver::UTurn,
+        }
+    }
+}
+
+impl Display for Maneuver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Maneuver::Depart => write!(f, "Depart"),
+            Maneuver::Continue => write!(f, "Continue"),
+            Maneuver::SlightLeft => write!(f, "Turn slightly left"),
+            Maneuver::SlightRight => write!(f, "Turn slightly right"),
+            Maneuver::Left => write!(f, "Turn left"),
+            Maneuver::Right => write!(f, "Turn right"),
+            Maneuver::SharpLeft => write!(f, "Turn sharply left"),
+            Maneuver::SharpRight => write!(f, "Turn sharply right"),
+            Maneuver::UTurn => write!(f, "Make a U-turn"),
+            Maneuver::Roundabout { exit } => write!(f, "At the roundabout take exit {}", exit),
+            Maneuver::Arrive => write!(f, "Arrive at the destination"),
+        }
+    }
+}
+
+/// A single step of the route
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub maneuver: Maneuver,
+    /// Node at which the maneuver takes place
+    pub node: NodeIndex,
+    /// Name or ref of the street the step follows
+    pub street: Option<String>,
+    /// Length of the step in metres
+    pub distance: f64,
+    /// Sum of the weights of the step, i.e. the travel time in seconds for imported graphs
+    pub duration: Weight,
+}
+
+impl Instruction {
+    fn new(maneuver: Maneuver, node: NodeIndex, street: Option<String>) -> Self {
+        Instruction {
+            maneuver,
+            node,
+            street,
+            distance: 0.0,
+            duration: 0.0,
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.maneuver)?;
+        if let Some(street) = &self.street {
+            let preposition = match self.maneuver {
+                Maneuver::Depart => "on",
+                _ => "onto",
+            };
+            write!(f, " {} {}", preposition, street)?;
+        }
+        if self.maneuver != Maneuver::Arrive {
+            write!(f, ", {:.0} m ({:.0} s)", self.distance, self.duration)?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates the instructions for an unpacked `path` in `g`. The first instruction is always a
+/// [`Maneuver::Depart`] and the last one a [`Maneuver::Arrive`].
+///
+/// Returns `None` if two consecutive nodes of the path are not connected by an edge, see
+/// [`ShortestPath::segments`].
+pub fn instructions(g: &Graph, path: &ShortestPath) -> Option<Vec<Instruction>> {
+    let segments = path.segments(g)?;
+    let first = *path.nodes.first()?;
+    let last = *path.nodes.last()?;
+
+    let mut instructions = vec![Instruction::new(
+        Maneuver::Depart,
+        first,
+        segments.first().and_then(street),
+    )];
+
+    for (i, segment) in segments.iter().enumerate() {
+        if i > 0 {
+            if let Some(instruction) = maneuver(g, &segments[i - 1], segment, &mut instructions) {
+                instructions.push(instruction);
+            }
+        }
+
+        let step = instructions.last_mut().unwrap();
+        // Graphs without metric still have coordinates
+        step.distance += g.metric.unwrap_or_default().distance(
+            &g.nodes[segment.source.index()],
+            &g.nodes[segment.target.index()],
+        );
+        step.duration += segment.weight;
+    }
+
+    instructions.push(Instruction::new(Maneuver::Arrive, last, None));
+    Some(instructions)
+}
+
+/// Returns the instruction for the junction between `prev` and `next`, if any. Updates the
+/// current roundabout instruction instead, if the path is inside a roundabout.
+fn maneuver(
+    g: &Graph,
+    prev: &Segment,
+    next: &Segment,
+    instructions: &mut [Instruction],
+) -> Option<Instruction> {
+    let node = next.source;
+
+    match (is_roundabout(prev), is_roundabout(next)) {
+        (false, true) => Some(Instruction::new(
+            Maneuver::Roundabout { exit: 0 },
+            node,
+            None,
+        )),
+        (true, inside) => {
+            let step = instructions.last_mut().unwrap();
+            if let Maneuver::Roundabout { exit } = &mut step.maneuver {
+                // Count the exits passed and the one taken
+                if !inside || has_exit(g, node) {
+                    *exit += 1;
+                }
+                if !inside {
+                    step.street = street(next);
+                }
+            }
+            None
+        }
+        (false, false) => {
+            let angle = bearing(&g.nodes[node.index()], &g.nodes[next.target.index()])
+                - bearing(&g.nodes[prev.source.index()], &g.nodes[node.index()]);
+            let maneuver = Maneuver::from_angle(angle);
+
+            let street_changed = street(prev) != street(next);
+            let turns = maneuver != Maneuver::Continue && is_junction(g, node);
+            (street_changed || turns).then(|| Instruction::new(maneuver, node, street(next)))
+        }
+    }
+}
+
+/// Name of the street of `segment`, or its ref if it has no name
+fn street(segment: &Segment) -> Option<String> {
+    segment
+        .way
+        .and_then(|way| way.name.clone().or_else(|| way.reference.clone()))
+}
+
+fn is_roundabout(segment: &Segment) -> bool {
+    segment.way.is_some_and(|way| way.roundabout)
+}
+
+/// Returns true if `node` is connected to more than two other nodes
+fn is_junction(g: &Graph, node: NodeIndex) -> bool {
+    let num_original = g.edges.len() - g.num_shortcuts;
+    let neighbors: FxHashSet<NodeIndex> = g
+        .neighbors_outgoing(node)
+        .filter(|(edge_idx, _)| edge_idx.index() < num_original)
+        .map(|(_, edge)| edge.target)
+        .chain(
+            g.neighbors_incoming(node)
+                .filter(|(edge_idx, _)| edge_idx.index() < num_original)
+                .map(|(_, edge)| edge.source),
+        )
+        .collect();
+
+    neighbors.len() > 2
+}
+
+/// Returns true if a road which is no part of the roundabout leaves at `node`
+fn has_exit(g: &Graph, node: NodeIndex) -> bool {
+    let num_original = g.edges.len() - g.num_shortcuts;
+    g.neighbors_outgoing(node).any(|(edge_idx, _)| {
+        edge_idx.index() < num_original && g.ways.way(edge_idx).is_none_or(|way| !way.roundabout)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        edge,
+        graph::{node_index, Node},
+        search::Dijkstra,
+        util::math::Metric,
+        ways::{RoadType, WayInfo},
+    };
+
+    use super::*;
+
+    /// Adds a way with the given `nodes` to `g`. The way is bidirectional unless it is a
+    /// roundabout.
+    fn add_way(g: &mut Graph, id: i64, name: &str, roundabout: bool, nodes: &[usize]) {
+        let way_idx = g.ways.add_way(WayInfo {
+            id,
+            name: Some(name.to_string()),
+            reference: None,
+            road_type: RoadType::Residential,
+            roundabout,
+        });
+        for pair in nodes.windows(2) {
+            let (a, b) = (node_index(pair[0]), node_index(pair[1]));
+            let weight =
+                Metric::default().distance(&g.nodes[a.index()], &g.nodes[b.index()]) / 10.0;
+            let edge_idx = g.add_edge(edge!(a => b, weight));
+            g.ways.set(edge_idx, way_idx);
+            if !roundabout {
+                let edge_idx = g.add_edge(edge!(b => a, weight));
+                g.ways.set(edge_idx, way_idx);
+            }
+        }
+    }
+
+    fn graph(coordinates: &[(f64, f64)]) -> Graph {
+        let mut g = Graph::new();
+        for (id, (lat, lon)) in coordinates.iter().enumerate() {
+            g.add_node(Node::new(id, *lat, *lon));
+        }
+        g
+    }
+
+    fn route(g: &Graph, source: usize, target: usize) -> Vec<Instruction> {
+        let mut dijkstra = Dijkstra::new(g);
+        let path = dijkstra
+            .search(node_index(source), node_index(target))
+            .unwrap();
+        instructions(g, &path).unwrap()
+    }
+
+    #[test]
+    fn classify_angles() {
+        assert_eq!(Maneuver::Continue, Maneuver::from_angle(10.0));
+        assert_eq!(Maneuver::Continue, Maneuver::from_angle(355.0));
+        assert_eq!(Maneuver::SlightRight, Maneuver::from_angle(30.0));
+        assert_eq!(Maneuver::Left, Maneuver::from_angle(-90.0));
+        assert_eq!(Maneuver::Left, Maneuver::from_angle(270.0));
+        assert_eq!(Maneuver::SharpRight, Maneuver::from_angle(150.0));
+        assert_eq!(Maneuver::UTurn, Maneuver::from_angle(-179.0));
+    }
+
+    #[test]
+    fn turns_at_junctions() {
+        //      F
+        //      |
+        //      C --- D
+        //      |     |
+        // A -- B     E
+        let mut g = graph(&[
+            (0.0, 0.0),
+            (0.0, 0.01),
+            (0.01, 0.01),
+            (0.01, 0.02),
+            (0.0, 0.02),
+            (0.02, 0.01),
+        ]);
+        add_way(&mut g, 1, "Hauptstraße", false, &[0, 1]);
+        add_way(&mut g, 2, "Bahnhofstraße", false, &[1, 2, 3]);
+        add_way(&mut g, 3, "Gartenweg", false, &[3, 4]);
+        // Makes C a junction
+        add_way(&mut g, 4, "Schulweg", false, &[2, 5]);
+
+        let instructions = route(&g, 0, 4);
+        let maneuvers: Vec<_> = instructions
+            .iter()
+            .map(|instruction| (instruction.maneuver, instruction.node.index()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Maneuver::Depart, 0),
+                (Maneuver::Left, 1),
+                (Maneuver::Right, 2),
+                (Maneuver::Right, 3),
+                (Maneuver::Arrive, 4)
+            ],
+            maneuvers
+        );
+
+        assert_eq!(Some("Bahnhofstraße".to_string()), instructions[1].street);
+        assert_eq!(Some("Gartenweg".to_string()), instructions[3].street);
+        // About 1.1 km per step
+        assert!((instructions[0].distance - 1112.0).abs() < 1.0);
+        assert!((instructions[0].duration - instructions[0].distance / 10.0).abs() < 1e-9);
+        assert_eq!(
+            "Turn left onto Bahnhofstraße, 1112 m (111 s)",
+            instructions[1].to_string()
+        );
+    }
+
+    #[test]
+    fn count_roundabout_exits() {
+        //       Y2
+        //       |
+        // X -- R1 -> R2
+        //      ^      |
+        //      R4 <- R3 -- Y3
+        let mut g = graph(&[
+            (0.0, 0.0),
+            (0.0, 0.01),
+            (0.0, 0.02),
+            (-0.01, 0.02),
+            (-0.01, 0.01),
+            (0.01, 0.02),
+            (-0.01, 0.03),
+        ]);
+        add_way(&mut g, 1, "Hauptstraße", false, &[0, 1]);
+        add_way(&mut g, 2, "Kreisverkehr", true, &[1, 2, 3, 4, 1]);
+        add_way(&mut g, 3, "Nordstraße", false, &[2, 5]);
+        add_way(&mut g, 4, "Oststraße", false, &[3, 6]);
+
+        let instructions = route(&g, 0, 6);
+        assert_eq!(3, instructions.len());
+        assert_eq!(Maneuver::Roundabout { exit: 2 }, instructions[1].maneuver);
+        assert_eq!(1, instructions[1].node.index());
+        assert_eq!(Some("Oststraße".to_string()), instructions[1].street);
+        assert_eq!(
+            "At the roundabout take exit 2 onto Oststraße",
+            instructions[1].to_string().split(',').next().unwrap()
+        );
+    }
+
+    #[test]
+    fn path_without_edges() {
+        let g = graph(&[(0.0, 0.0)]);
+        let path = ShortestPath::new(vec![node_index(0)], 0.0);
+
+        let instructions = instructions(&g, &path).unwrap();
+        assert_eq!(2, instructions.len());
+        assert_eq!(Maneuver::Depart, instructions[0].maneuver);
+        assert_eq!(Maneuver::Arrive, instructions[1].maneuver);
+        assert_eq!("Arrive at the destination", instructions[1].to_string());
+    }
+}
@@ -27,14 +27,21 @@
 //!
 //!```
 //! [`Graph`]: crate::graph::Graph
+pub mod components;
 pub mod constants;
 pub mod contraction_params;
 pub mod contraction_strategy;
+pub mod formats;
 pub mod graph;
+pub mod guidance;
+pub mod mmap_graph;
 pub mod node_contraction;
 pub mod overlay_graph;
 pub mod prelude;
 pub mod search;
+pub mod serialization;
 pub mod statistics;
 pub mod util;
+pub mod verification;
+pub mod ways;
 pub(crate) mod witness_search;
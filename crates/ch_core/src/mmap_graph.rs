@@ -0,0 +1,1248 @@
+//! Memory-mapped, zero-copy file format for the [`OverlayGraph`].
+//!
+//! [`OverlayGraph::from_decode`] deserializes the whole file into heap allocated vectors.
+//! The format in this module is laid out so that it can be memory-mapped and queried
+//! directly: every section is a flat, 8-byte aligned array whose layout matches the
+//! in-memory layout of the corresponding Rust type. Processes mapping the same file
+//! share the pages of the OS page cache instead of holding private copies.
+//!
+//! # Layout
+//! All values are stored in little endian byte order.
+//! ```text
+//! +-------------+
+//! | Header      | 112 bytes, see [`MmapHeader`]
+//! +-------------+
+//! | Nodes       | num_nodes * 24 bytes        ([`Node`])
+//! | Edges       | num_edges * 16 bytes        ([`Edge`])
+//! | Offsets G↑  | (num_nodes + 1) * 8 bytes
+//! | Edges G↑    | num_edges_fwd * 4 bytes     ([`EdgeIndex`])
+//! | Offsets G↓  | (num_nodes + 1) * 8 bytes
+//! | Edges G↓    | num_edges_bwd * 4 bytes     ([`EdgeIndex`])
+//! | Shortcuts   | num_edges * 8 bytes         ([`EdgeIndex`; 2], `u32::MAX` if no shortcut)
+//! | Node order  | num_ranks * 8 bytes
+//! | Core        | num_core * 4 bytes          (sorted [`NodeIndex`] of the core nodes)
+//! | Components  | num_components * 4 bytes    (strongly connected component of every node)
+//! | Weak        | num_components * 4 bytes    (weakly connected component of every node)
+//! | Ways        | ways_len bytes              (bincode encoded [`WayTable`])
+//! +-------------+
+//! ```
+//! Every section starts at a multiple of 8 bytes. The checksum in the header is the
+//! CRC32 of everything following the header.
+//!
+//! The ways are the only section which is decoded when the file is opened, they are not used
+//! by the searches.
+//!
+//! # Examples
+//! ```
+//! use ch_core::prelude::*;
+//! use ch_core::mmap_graph::{MmapCHSearch, MmapOverlayGraph};
+//!
+//! let mut g = generate_simple_graph();
+//! let mut contractor = NodeContractor::new(&mut g);
+//! let overlay_graph = contractor.run();
+//!
+//! let path = std::env::temp_dir().join("simple_graph.chmm");
+//! overlay_graph.write_mmap(&path).unwrap();
+//!
+//! let mapped = MmapOverlayGraph::open(&path).unwrap();
+//! let mut ch = MmapCHSearch::new(&mapped);
+//!
+//! let sp = ch.search(node_index(4), node_index(1)).unwrap();
+//! println!("Costs: {}", sp.weight);
+//! ```
+//! [`OverlayGraph`]: crate::overlay_graph::OverlayGraph
+//! [`OverlayGraph::from_decode`]: crate::overlay_graph::OverlayGraph::from_decode
+use std::{
+    collections::BinaryHeap,
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    mem::{align_of, size_of},
+    ops::Range,
+    path::PathBuf,
+};
+
+use anyhow::{bail, ensure, Context};
+use log::{debug, info};
+use memmap2::Mmap;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use osm_reader::geo::EarthModel;
+
+use crate::{
+    constants::Weight,
+    graph::{DefaultIdx, Edge, EdgeIndex, Node, NodeIndex},
+    overlay_graph::OverlayGraph,
+    search::{
+        dijkstra::Candidate, shortest_path::ShortestPath, SearchSpace, ShortestPathAlgorithm,
+    },
+    serialization,
+    statistics::SearchStats,
+    util::math::Metric,
+    ways::WayTable,
+};
+
+const MAGIC: [u8; 8] = *b"CHMMAP\0\0";
+/// Version of the memory-mapped file format written by [`OverlayGraph::write_mmap`].
+pub const FORMAT_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 112;
+const ALIGNMENT: usize = 8;
+const NO_SHORTCUT: u32 = u32::MAX;
+/// Earth model of graphs without metric
+const NO_METRIC: u32 = u32::MAX;
+
+// The zero-copy casts below rely on these layouts
+const _: () = assert!(size_of::<Node>() == 24);
+const _: () = assert!(size_of::<Edge>() == 16);
+const _: () = assert!(size_of::<EdgeIndex>() == 4);
+const _: () = assert!(align_of::<Edge>() <= ALIGNMENT && align_of::<Node>() <= ALIGNMENT);
+
+/// Header of a memory-mapped overlay graph file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MmapHeader {
+    /// Version of the file format
+    pub version: u32,
+    /// Size of node and edge indices in bytes
+    pub index_width: u32,
+    pub num_nodes: u64,
+    pub num_edges: u64,
+    /// Number of shortcut edges contained in `num_edges`
+    pub num_shortcuts: u64,
+    /// Number of entries in the upward graph G↑
+    pub num_edges_fwd: u64,
+    /// Number of entries in the downward graph G↓
+    pub num_edges_bwd: u64,
+    /// Number of entries in the node order. Either `0` or `num_nodes`
+    pub num_ranks: u64,
+    /// CRC32 of all bytes following the header
+    pub checksum: u64,
+    /// Number of nodes in the uncontracted core
+    pub num_core: u64,
+    /// Number of nodes tagged with their component. Either `0` or `num_nodes`
+    pub num_components: u64,
+    /// Length of the encoded way table in bytes
+    pub ways_len: u64,
+    /// Metric of the road graph, see [`Graph::metric`](crate::graph::Graph::metric)
+    pub metric: Option<Metric>,
+}
+
+impl MmapHeader {
+    fn to_bytes(self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0..8].copy_from_slice(&MAGIC);
+        bytes[8..12].copy_from_slice(&self.version.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.index_width.to_le_bytes());
+
+        let fields = [
+            self.num_nodes,
+            self.num_edges,
+            self.num_shortcuts,
+            self.num_edges_fwd,
+            self.num_edges_bwd,
+            self.num_ranks,
+            self.checksum,
+        ];
+        for (i, field) in fields.iter().enumerate() {
+            bytes[16 + i * 8..24 + i * 8].copy_from_slice(&field.to_le_bytes());
+        }
+
+        bytes[72..80].copy_from_slice(&self.num_core.to_le_bytes());
+        bytes[80..88].copy_from_slice(&self.num_components.to_le_bytes());
+        bytes[88..96].copy_from_slice(&self.ways_len.to_le_bytes());
+        let earth_model: u32 = match self.metric.map(|metric| metric.earth_model) {
+            Some(EarthModel::Haversine) => 0,
+            Some(EarthModel::Vincenty) => 1,
+            Some(EarthModel::Karney) => 2,
+            None => NO_METRIC,
+        };
+        let max_speed = self.metric.map_or(0.0, |metric| metric.max_speed);
+        bytes[96..100].copy_from_slice(&earth_model.to_le_bytes());
+        bytes[104..112].copy_from_slice(&max_speed.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        ensure!(
+            bytes.len() >= HEADER_SIZE,
+            "File is too small to contain a header ({} bytes)",
+            bytes.len()
+        );
+        ensure!(
+            bytes[0..8] == MAGIC,
+            "Invalid magic bytes. File is not a memory-mapped overlay graph"
+        );
+
+        let u32_at = |pos: usize| u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap());
+        let u64_at = |pos: usize| u64::from_le_bytes(bytes[pos..pos + 8].try_into().unwrap());
+
+        let version = u32_at(8);
+        ensure!(
+            version == FORMAT_VERSION,
+            "Unsupported format version {} (expected {})",
+            version,
+            FORMAT_VERSION
+        );
+
+        let earth_model = match u32_at(96) {
+            0 => Some(EarthModel::Haversine),
+            1 => Some(EarthModel::Vincenty),
+            2 => Some(EarthModel::Karney),
+            NO_METRIC => None,
+            other => bail!("Unknown earth model {}", other),
+        };
+        let max_speed = f64::from_le_bytes(bytes[104..112].try_into().unwrap());
+
+        Ok(MmapHeader {
+            version,
+            index_width: u32_at(12),
+            num_nodes: u64_at(16),
+            num_edges: u64_at(24),
+            num_shortcuts: u64_at(32),
+            num_edges_fwd: u64_at(40),
+            num_edges_bwd: u64_at(48),
+            num_ranks: u64_at(56),
+            checksum: u64_at(64),
+            num_core: u64_at(72),
+            num_components: u64_at(80),
+            ways_len: u64_at(88),
+            metric: earth_model.map(|earth_model| Metric::new(earth_model, max_speed)),
+        })
+    }
+}
+
+/// Byte ranges of all sections inside the file
+#[derive(Debug, Clone)]
+struct Layout {
+    nodes: Range<usize>,
+    edges: Range<usize>,
+    offsets_fwd: Range<usize>,
+    edges_fwd: Range<usize>,
+    offsets_bwd: Range<usize>,
+    edges_bwd: Range<usize>,
+    shortcuts: Range<usize>,
+    node_order: Range<usize>,
+    core: Range<usize>,
+    components: Range<usize>,
+    weak: Range<usize>,
+    ways: Range<usize>,
+    total: usize,
+}
+
+impl Layout {
+    fn new(header: &MmapHeader) -> anyhow::Result<Self> {
+        let mut pos = HEADER_SIZE;
+        let mut section = |len: u64, item_size: usize| -> anyhow::Result<Range<usize>> {
+            let end = usize::try_from(len)
+                .ok()
+                .and_then(|len| len.checked_mul(item_size))
+                .and_then(|bytes| bytes.checked_add(pos))
+                .filter(|end| end.checked_add(ALIGNMENT).is_some())
+                .context("Section size exceeds address space")?;
+            let range = pos..end;
+            pos = align(range.end);
+            Ok(range)
+        };
+        let num_offsets = header
+            .num_nodes
+            .checked_add(1)
+            .context("Number of nodes exceeds address space")?;
+
+        let nodes = section(header.num_nodes, size_of::<Node>())?;
+        let edges = section(header.num_edges, size_of::<Edge>())?;
+        let offsets_fwd = section(num_offsets, size_of::<u64>())?;
+        let edges_fwd = section(header.num_edges_fwd, size_of::<EdgeIndex>())?;
+        let offsets_bwd = section(num_offsets, size_of::<u64>())?;
+        let edges_bwd = section(header.num_edges_bwd, size_of::<EdgeIndex>())?;
+        let shortcuts = section(header.num_edges, size_of::<[EdgeIndex; 2]>())?;
+        let node_order = section(header.num_ranks, size_of::<u64>())?;
+        let core = section(header.num_core, size_of::<u32>())?;
+        let components = section(header.num_components, size_of::<u32>())?;
+        let weak = section(header.num_components, size_of::<u32>())?;
+        let ways = section(header.ways_len, 1)?;
+
+        Ok(Layout {
+            nodes,
+            edges,
+            offsets_fwd,
+            edges_fwd,
+            offsets_bwd,
+            edges_bwd,
+            shortcuts,
+            node_order,
+            core,
+            components,
+            weak,
+            ways,
+            total: pos,
+        })
+    }
+}
+
+fn align(pos: usize) -> usize {
+    (pos + ALIGNMENT - 1) & !(ALIGNMENT - 1)
+}
+
+/// Writer which keeps track of the position and the checksum of all written bytes.
+struct SectionWriter<W: Write> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+    pos: usize,
+}
+
+impl<W: Write> SectionWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.hasher.update(bytes);
+        self.pos += bytes.len();
+        self.inner.write_all(bytes)
+    }
+
+    fn pad(&mut self) -> std::io::Result<()> {
+        let padding = align(self.pos) - self.pos;
+        self.write(&[0; ALIGNMENT][..padding])
+    }
+
+    fn write_adjacency(&mut self, adjacency: &[Vec<EdgeIndex>]) -> std::io::Result<()> {
+        let mut offset = 0u64;
+        self.write(&offset.to_le_bytes())?;
+        for edges in adjacency {
+            offset += edges.len() as u64;
+            self.write(&offset.to_le_bytes())?;
+        }
+        self.pad()?;
+
+        for edge_idx in adjacency.iter().flatten() {
+            self.write(&(edge_idx.index() as u32).to_le_bytes())?;
+        }
+        self.pad()
+    }
+}
+
+impl OverlayGraph {
+    /// Writes the graph in the memory-mapped file format. See [`crate::mmap_graph`].
+    ///
+    /// Returns the number of bytes written.
+    pub fn write_mmap(&self, path: impl Into<PathBuf>) -> anyhow::Result<usize> {
+        let g = self.road_graph();
+        let core: Vec<NodeIndex> = self.core_nodes().collect();
+        let ways = if g.ways.is_empty() {
+            Vec::new()
+        } else {
+            serialization::encode_to_vec(&g.ways)?
+        };
+        let mut file = File::create(path.into())?;
+
+        let header = MmapHeader {
+            version: FORMAT_VERSION,
+            index_width: size_of::<DefaultIdx>() as u32,
+            num_nodes: g.nodes.len() as u64,
+            num_edges: g.edges.len() as u64,
+            num_shortcuts: g.num_shortcuts as u64,
+            num_edges_fwd: self.edges_fwd.iter().map(Vec::len).sum::<usize>() as u64,
+            num_edges_bwd: self.edges_bwd.iter().map(Vec::len).sum::<usize>() as u64,
+            num_ranks: self.node_order.len() as u64,
+            checksum: 0,
+            num_core: core.len() as u64,
+            num_components: g.components.as_ref().map_or(0, |_| g.nodes.len() as u64),
+            ways_len: ways.len() as u64,
+            metric: g.metric,
+        };
+
+        // Write placeholder header. It gets replaced once the checksum is known.
+        file.write_all(&header.to_bytes())?;
+
+        let mut wtr = SectionWriter {
+            inner: BufWriter::new(&mut file),
+            hasher: crc32fast::Hasher::new(),
+            pos: HEADER_SIZE,
+        };
+
+        for node in g.nodes() {
+            wtr.write(&(node.id as u64).to_le_bytes())?;
+            wtr.write(&node.lat.to_le_bytes())?;
+            wtr.write(&node.lon.to_le_bytes())?;
+        }
+        wtr.pad()?;
+
+        for edge in g.edges() {
+            wtr.write(&(edge.source.index() as u32).to_le_bytes())?;
+            wtr.write(&(edge.target.index() as u32).to_le_bytes())?;
+            wtr.write(&edge.weight.to_le_bytes())?;
+        }
+        wtr.pad()?;
+
+        wtr.write_adjacency(&self.edges_fwd)?;
+        wtr.write_adjacency(&self.edges_bwd)?;
+
+        for edge_idx in 0..g.edges.len() {
+            let [incoming, outgoing] = match self.shortcuts.get(&EdgeIndex::new(edge_idx)) {
+                Some([incoming, outgoing]) => [incoming.index() as u32, outgoing.index() as u32],
+                None => [NO_SHORTCUT, NO_SHORTCUT],
+            };
+            wtr.write(&incoming.to_le_bytes())?;
+            wtr.write(&outgoing.to_le_bytes())?;
+        }
+        wtr.pad()?;
+
+        for rank in self.node_order.iter() {
+            wtr.write(&(*rank as u64).to_le_bytes())?;
+        }
+        wtr.pad()?;
+
+        for node in core.iter() {
+            wtr.write(&(node.index() as u32).to_le_bytes())?;
+        }
+        wtr.pad()?;
+
+        if let Some(components) = &g.components {
+            for node in 0..g.nodes.len() {
+                let component = components.component(NodeIndex::new(node));
+                wtr.write(&(component as u32).to_le_bytes())?;
+            }
+            wtr.pad()?;
+            for node in 0..g.nodes.len() {
+                let weak = components.weak_component(NodeIndex::new(node));
+                wtr.write(&(weak as u32).to_le_bytes())?;
+            }
+            wtr.pad()?;
+        }
+
+        wtr.write(&ways)?;
+        wtr.pad()?;
+
+        let bytes_written = wtr.pos;
+        let checksum = wtr.hasher.clone().finalize();
+        wtr.inner.flush()?;
+        drop(wtr);
+
+        let header = MmapHeader {
+            checksum: checksum as u64,
+            ..header
+        };
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header.to_bytes())?;
+
+        debug!("Wrote memory-mapped graph: {:?}", header);
+
+        Ok(bytes_written)
+    }
+}
+
+/// An [`OverlayGraph`] backed by a memory-mapped file. All accessors read directly from
+/// the mapped file without decoding.
+///
+/// [`OverlayGraph`]: crate::overlay_graph::OverlayGraph
+pub struct MmapOverlayGraph {
+    mmap: Mmap,
+    header: MmapHeader,
+    layout: Layout,
+    ways: WayTable,
+}
+
+impl MmapOverlayGraph {
+    /// Maps the file at `path` and validates the header, the checksum and that all node and
+    /// edge indices in the file are in range.
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let graph = Self::map(path.into(), true)?;
+        graph.validate_indices()?;
+
+        Ok(graph)
+    }
+
+    /// Maps the file at `path` and validates only the header and the offsets of the
+    /// adjacency lists.
+    ///
+    /// Skips the checksum calculation and the validation of the indices, which have to read
+    /// the whole file. Use this if the file is known to be intact and should be opened without
+    /// touching all pages. Accessors panic on out of range indices of corrupted files and the
+    /// way table of a corrupted file may request an arbitrary amount of memory when decoded.
+    pub fn open_unchecked(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        Self::map(path.into(), false)
+    }
+
+    /// Maps the file at `path`. The checksum is verified before the way table is decoded if
+    /// `verify_checksum` is set.
+    fn map(path: PathBuf, verify_checksum: bool) -> anyhow::Result<Self> {
+        if cfg!(target_endian = "big") {
+            bail!("Memory-mapped graphs are only supported on little endian targets");
+        }
+
+        let file = File::open(&path).context("Failed to read file")?;
+
+        // SAFETY: The file must not be modified while it is mapped. This is the same
+        // contract every user of memory-mapped files has to uphold.
+        let mmap = unsafe { Mmap::map(&file) }.context("Failed to map file")?;
+
+        let header = MmapHeader::from_bytes(&mmap)?;
+        ensure!(
+            header.index_width as usize == size_of::<DefaultIdx>(),
+            "Unsupported index width of {} bytes (expected {})",
+            header.index_width,
+            size_of::<DefaultIdx>()
+        );
+        ensure!(
+            header.num_ranks == 0 || header.num_ranks == header.num_nodes,
+            "Node order contains {} entries but graph has {} nodes",
+            header.num_ranks,
+            header.num_nodes
+        );
+        ensure!(
+            header.num_components == 0 || header.num_components == header.num_nodes,
+            "Components contain {} entries but graph has {} nodes",
+            header.num_components,
+            header.num_nodes
+        );
+        ensure!(
+            header.num_core <= header.num_nodes,
+            "Core contains {} nodes but graph has only {} nodes",
+            header.num_core,
+            header.num_nodes
+        );
+
+        let layout = Layout::new(&header)?;
+        ensure!(
+            mmap.len() >= layout.total,
+            "File is truncated ({} bytes, expected {})",
+            mmap.len(),
+            layout.total
+        );
+        ensure!(
+            mmap.as_ptr().align_offset(ALIGNMENT) == 0,
+            "Mapped memory is not aligned"
+        );
+
+        info!(
+            "Mapped {}: #Nodes: {}, #Edges: {}",
+            path.display(),
+            header.num_nodes,
+            header.num_edges
+        );
+
+        if verify_checksum {
+            let checksum = crc32fast::hash(&mmap[HEADER_SIZE..layout.total]) as u64;
+            ensure!(
+                checksum == header.checksum,
+                "Checksum mismatch (expected {:#x}, found {:#x}). File is corrupted",
+                header.checksum,
+                checksum
+            );
+        }
+
+        let ways = if layout.ways.is_empty() {
+            WayTable::default()
+        } else {
+            serialization::decode_from_slice(&mmap[layout.ways.clone()])?
+        };
+
+        let graph = MmapOverlayGraph {
+            mmap,
+            header,
+            layout,
+            ways,
+        };
+        graph.validate_offsets()?;
+
+        Ok(graph)
+    }
+
+    /// Checks that the offsets of both adjacency lists start at `0`, are monotonic and end at
+    /// the number of entries, so every node refers to a valid range of edges.
+    fn validate_offsets(&self) -> anyhow::Result<()> {
+        let adjacencies = [
+            ("G↑", &self.layout.offsets_fwd, self.header.num_edges_fwd),
+            ("G↓", &self.layout.offsets_bwd, self.header.num_edges_bwd),
+        ];
+        for (name, offsets, len) in adjacencies {
+            let offsets = self.section::<u64>(offsets);
+            ensure!(
+                offsets.first() == Some(&0),
+                "Offsets of {} do not start at 0",
+                name
+            );
+            ensure!(
+                offsets.windows(2).all(|pair| pair[0] <= pair[1]),
+                "Offsets of {} are not monotonic",
+                name
+            );
+            ensure!(
+                offsets.last() == Some(&len),
+                "Offsets of {} end at {:?}, expected {}",
+                name,
+                offsets.last(),
+                len
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that all node and edge indices are in range and that shortcuts only replace
+    /// edges with lower indices, so unpacking terminates.
+    fn validate_indices(&self) -> anyhow::Result<()> {
+        let num_nodes = self.num_nodes();
+        let num_edges = self.num_edges();
+
+        for (edge_idx, edge) in self.edges().iter().enumerate() {
+            ensure!(
+                edge.source.index() < num_nodes && edge.target.index() < num_nodes,
+                "Edge {} connects unknown nodes {:?} and {:?}",
+                edge_idx,
+                edge.source,
+                edge.target
+            );
+        }
+
+        for (name, edges) in [
+            ("G↑", &self.layout.edges_fwd),
+            ("G↓", &self.layout.edges_bwd),
+        ] {
+            if let Some(edge_idx) = self
+                .section::<EdgeIndex>(edges)
+                .iter()
+                .find(|edge_idx| edge_idx.index() >= num_edges)
+            {
+                bail!("{} contains unknown edge {:?}", name, edge_idx);
+            }
+        }
+
+        let shortcuts = self.section::<[EdgeIndex; 2]>(&self.layout.shortcuts);
+        for (edge_idx, [incoming, outgoing]) in shortcuts.iter().enumerate() {
+            let no_shortcut = NO_SHORTCUT as usize;
+            ensure!(
+                (incoming.index() == no_shortcut && outgoing.index() == no_shortcut)
+                    || (incoming.index() < edge_idx && outgoing.index() < edge_idx),
+                "Shortcut {} replaces invalid edges {:?} and {:?}",
+                edge_idx,
+                incoming,
+                outgoing
+            );
+        }
+
+        let core = self.section::<u32>(&self.layout.core);
+        ensure!(
+            core.windows(2).all(|pair| pair[0] < pair[1])
+                && core.last().is_none_or(|node| (*node as usize) < num_nodes),
+            "Core contains unknown or unsorted nodes"
+        );
+
+        Ok(())
+    }
+
+    /// Returns the header of the mapped file
+    pub fn header(&self) -> &MmapHeader {
+        &self.header
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.header.num_nodes as usize
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.header.num_edges as usize
+    }
+
+    /// Reinterprets the bytes in `range` as a slice of `T`.
+    fn section<T>(&self, range: &Range<usize>) -> &[T] {
+        let bytes = &self.mmap[range.clone()];
+        // SAFETY: `open_unchecked` checked that the file contains all sections and that the
+        // mapping is aligned. Every section starts at a multiple of `ALIGNMENT` and is only
+        // reinterpreted as types with `repr(C)`/`repr(transparent)` layout whose alignment
+        // does not exceed `ALIGNMENT` and for which every bit pattern is valid.
+        unsafe {
+            std::slice::from_raw_parts(bytes.as_ptr() as *const T, bytes.len() / size_of::<T>())
+        }
+    }
+
+    /// Returns all nodes of the graph
+    pub fn nodes(&self) -> &[Node] {
+        self.section(&self.layout.nodes)
+    }
+
+    /// Returns node with the given index if it exists
+    pub fn node(&self, node_idx: NodeIndex) -> Option<&Node> {
+        self.nodes().get(node_idx.index())
+    }
+
+    /// Returns all edges of the graph including shortcuts
+    pub fn edges(&self) -> &[Edge] {
+        self.section(&self.layout.edges)
+    }
+
+    pub fn edge(&self, edge_idx: EdgeIndex) -> &Edge {
+        &self.edges()[edge_idx.index()]
+    }
+
+    /// Metric of the road graph the file was written from
+    pub fn metric(&self) -> Option<Metric> {
+        self.header.metric
+    }
+
+    /// Ways of the original edges, see [`crate::ways`]
+    pub fn ways(&self) -> &WayTable {
+        &self.ways
+    }
+
+    /// Returns true if the file contains an uncontracted core
+    pub fn has_core(&self) -> bool {
+        self.header.num_core > 0
+    }
+
+    /// Returns true if `node` belongs to the uncontracted core
+    pub fn is_core(&self, node: NodeIndex) -> bool {
+        self.section::<u32>(&self.layout.core)
+            .binary_search(&(node.index() as u32))
+            .is_ok()
+    }
+
+    /// Returns an iterator over all core nodes in ascending order
+    pub fn core_nodes(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.section::<u32>(&self.layout.core)
+            .iter()
+            .map(|node| NodeIndex::new(*node as usize))
+    }
+
+    /// Returns false if `target` is known to be unreachable from `source`. Always true if the
+    /// components were not tagged, see [`Graph::may_reach`](crate::graph::Graph::may_reach).
+    pub fn may_reach(&self, source: NodeIndex, target: NodeIndex) -> bool {
+        if self.header.num_components == 0 {
+            return true;
+        }
+
+        let component = self.section::<u32>(&self.layout.components);
+        let weak = self.section::<u32>(&self.layout.weak);
+        let (s, t) = (source.index(), target.index());
+        weak[s] == weak[t] && component[s] >= component[t]
+    }
+
+    /// Returns the rank of `node_idx` in the contraction order if the order was stored
+    pub fn rank(&self, node_idx: NodeIndex) -> Option<usize> {
+        self.section::<u64>(&self.layout.node_order)
+            .get(node_idx.index())
+            .map(|rank| *rank as usize)
+    }
+
+    fn adjacency(
+        &self,
+        offsets: &Range<usize>,
+        edges: &Range<usize>,
+        node: NodeIndex,
+    ) -> impl Iterator<Item = (EdgeIndex, &Edge)> {
+        let offsets = self.section::<u64>(offsets);
+        let (from, to) = (
+            offsets[node.index()] as usize,
+            offsets[node.index() + 1] as usize,
+        );
+
+        self.section::<EdgeIndex>(edges)[from..to]
+            .iter()
+            .map(|edge_idx| (*edge_idx, self.edge(*edge_idx)))
+    }
+
+    /// Returns an iterator over all edges of `node` in the upward graph G↑
+    pub fn edges_fwd(&self, node: NodeIndex) -> impl Iterator<Item = (EdgeIndex, &Edge)> {
+        self.adjacency(&self.layout.offsets_fwd, &self.layout.edges_fwd, node)
+    }
+
+    /// Returns an iterator over all edges of `node` in the downward graph G↓
+    pub fn edges_bwd(&self, node: NodeIndex) -> impl Iterator<Item = (EdgeIndex, &Edge)> {
+        self.adjacency(&self.layout.offsets_bwd, &self.layout.edges_bwd, node)
+    }
+
+    /// Returns the two edges which are replaced by the shortcut `edge_idx`
+    pub fn shortcut(&self, edge_idx: EdgeIndex) -> Option<[EdgeIndex; 2]> {
+        let [incoming, outgoing] =
+            self.section::<[EdgeIndex; 2]>(&self.layout.shortcuts)[edge_idx.index()];
+
+        if incoming.index() == NO_SHORTCUT as usize {
+            None
+        } else {
+            Some([incoming, outgoing])
+        }
+    }
+
+    /// Recursively unpacks shortcut edges.
+    pub(crate) fn unpack_edge(&self, edge_idx: EdgeIndex) -> Vec<EdgeIndex> {
+        let mut unpacked = Vec::new();
+
+        match self.shortcut(edge_idx) {
+            Some([incoming, outgoing]) => {
+                unpacked.append(&mut self.unpack_edge(incoming));
+                unpacked.append(&mut self.unpack_edge(outgoing));
+            }
+            None => unpacked.push(edge_idx),
+        }
+        unpacked
+    }
+}
+
+type NodeData = FxHashMap<NodeIndex, (Weight, Option<EdgeIndex>)>;
+
+/// Bidirectional CH query on a [`MmapOverlayGraph`]. Works like [`CHSearch`] (including
+/// stall-on-demand) but reads the hierarchy directly from the mapped file.
+///
+/// Like [`CHSearch`], paths through an uncontracted core are not found.
+///
+/// [`CHSearch`]: crate::search::CHSearch
+pub struct MmapCHSearch<'a, Idx = DefaultIdx> {
+    pub stats: SearchStats,
+    g: &'a MmapOverlayGraph,
+
+    pub settled_fwd: FxHashSet<NodeIndex<Idx>>,
+    pub settled_bwd: FxHashSet<NodeIndex<Idx>>,
+
+    data_fwd: NodeData,
+    data_bwd: NodeData,
+
+    intersect_node: Option<NodeIndex<Idx>>,
+    best_weight: Weight,
+
+    pub nodes_stalled: usize,
+
+    search_space: Option<SearchSpace>,
+}
+
+impl<'a> MmapCHSearch<'a> {
+    pub fn new(graph: &'a MmapOverlayGraph) -> Self {
+        MmapCHSearch {
+            g: graph,
+            stats: SearchStats::default(),
+            settled_fwd: FxHashSet::default(),
+            settled_bwd: FxHashSet::default(),
+            data_fwd: FxHashMap::default(),
+            data_bwd: FxHashMap::default(),
+            intersect_node: None,
+            best_weight: Weight::MAX,
+            nodes_stalled: 0,
+            search_space: None,
+        }
+    }
+
+    fn init(&mut self) {
+        self.settled_fwd.clear();
+        self.settled_bwd.clear();
+        self.data_fwd.clear();
+        self.data_bwd.clear();
+        self.intersect_node = None;
+        self.best_weight = Weight::MAX;
+        self.stats.init();
+        self.nodes_stalled = 0;
+        if let Some(space) = &mut self.search_space {
+            space.clear();
+        }
+    }
+
+    /// Finds the shortest path from `source` to `target`.
+    pub fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
+        info!(
+            "BEGIN MMAP BIDIRECTIONAL SEARCH from {:?} to {:?}",
+            source, target
+        );
+        self.init();
+
+        if !self.g.may_reach(source, target) {
+            debug!("{:?} can't reach {:?}", source, target);
+            self.stats.finish();
+            return None;
+        }
+
+        let mut queue_fwd = BinaryHeap::new();
+        let mut queue_bwd = BinaryHeap::new();
+
+        queue_fwd.push(Candidate::new(source, 0.0));
+        queue_bwd.push(Candidate::new(target, 0.0));
+
+        self.data_fwd.insert(source, (0.0, None));
+        self.data_bwd.insert(target, (0.0, None));
+
+        while !queue_fwd.is_empty() || !queue_bwd.is_empty() {
+            self.search_step(&mut queue_fwd, true);
+            self.search_step(&mut queue_bwd, false);
+        }
+
+        self.stats.finish();
+        if let Some(space) = &mut self.search_space {
+            space.meeting_node = self.intersect_node;
+        }
+
+        self.reconstruct_shortest_path(source)
+    }
+
+    /// Settles the next node of the forward (`fwd == true`) or backward search.
+    fn search_step(&mut self, queue: &mut BinaryHeap<Candidate>, fwd: bool) {
+        while let Some(curr) = queue.pop() {
+            let (settled, settled_other) = if fwd {
+                (&self.settled_fwd, &self.settled_bwd)
+            } else {
+                (&self.settled_bwd, &self.settled_fwd)
+            };
+
+            if settled.contains(&curr.node_idx) {
+                continue;
+            }
+
+            if curr.weight > self.best_weight {
+                break;
+            }
+
+            if self.is_stallable(&curr, fwd) {
+                self.nodes_stalled += 1;
+                continue;
+            }
+
+            let reaches_other = settled_other.contains(&curr.node_idx);
+
+            let g = self.g;
+            let layout = &g.layout;
+            let (edges, data) = if fwd {
+                (
+                    g.adjacency(&layout.offsets_fwd, &layout.edges_fwd, curr.node_idx),
+                    &mut self.data_fwd,
+                )
+            } else {
+                (
+                    g.adjacency(&layout.offsets_bwd, &layout.edges_bwd, curr.node_idx),
+                    &mut self.data_bwd,
+                )
+            };
+
+            for (edge_idx, edge) in edges {
+                if let Some(space) = &mut self.search_space {
+                    space.relax(edge.source, edge.target, fwd);
+                }
+                let next = if fwd { edge.target } else { edge.source };
+                let new_weight = curr.weight + edge.weight;
+                if new_weight < data.get(&next).map_or(Weight::MAX, |(w, _)| *w) {
+                    data.insert(next, (new_weight, Some(edge_idx)));
+                    queue.push(Candidate::new(next, new_weight));
+                }
+            }
+            self.stats.nodes_settled += 1;
+            if let Some(space) = &mut self.search_space {
+                space.settle(curr.node_idx, fwd);
+            }
+
+            if fwd {
+                self.settled_fwd.insert(curr.node_idx);
+            } else {
+                self.settled_bwd.insert(curr.node_idx);
+            }
+
+            if reaches_other {
+                let other = if fwd { &self.data_bwd } else { &self.data_fwd };
+                let weight = curr.weight + other[&curr.node_idx].0;
+                if weight < self.best_weight {
+                    self.best_weight = weight;
+                    self.intersect_node = Some(curr.node_idx);
+                }
+            }
+            break;
+        }
+    }
+
+    // Stall on demand: A node is not settled if it can be reached with a lower weight
+    // through an edge of the opposite graph.
+    fn is_stallable(&self, cand: &Candidate, fwd: bool) -> bool {
+        if fwd {
+            self.g.edges_bwd(cand.node_idx).any(|(_, edge)| {
+                matches!(self.data_fwd.get(&edge.source), Some((dist, _)) if *dist + edge.weight < cand.weight)
+            })
+        } else {
+            self.g.edges_fwd(cand.node_idx).any(|(_, edge)| {
+                matches!(self.data_bwd.get(&edge.target), Some((dist, _)) if *dist + edge.weight < cand.weight)
+            })
+        }
+    }
+
+    fn reconstruct_shortest_path(&self, source: NodeIndex) -> Option<ShortestPath> {
+        let Some(v) = self.intersect_node else {
+            info!("No path found");
+            return None;
+        };
+
+        let mut path = vec![];
+        let mut previous_node = v;
+        while let Some((_, Some(prev_edge))) = self.data_fwd.get(&previous_node) {
+            for edge_idx in self.g.unpack_edge(*prev_edge).iter().rev() {
+                path.push(self.g.edge(*edge_idx).target);
+            }
+            previous_node = self.g.edge(*prev_edge).source;
+        }
+        path.push(source);
+        path.reverse();
+
+        let mut next_node = v;
+        while let Some((_, Some(next_edge))) = self.data_bwd.get(&next_node) {
+            for edge_idx in self.g.unpack_edge(*next_edge).iter() {
+                path.push(self.g.edge(*edge_idx).target);
+            }
+            next_node = self.g.edge(*next_edge).target;
+        }
+
+        info!("{}, weight: {}", self.stats, self.best_weight);
+
+        Some(ShortestPath::new(path, self.best_weight))
+    }
+}
+
+impl ShortestPathAlgorithm for MmapCHSearch<'_> {
+    fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
+        MmapCHSearch::search(self, source, target)
+    }
+
+    fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    fn settled_fwd(&self) -> &FxHashSet<NodeIndex> {
+        &self.settled_fwd
+    }
+
+    fn settled_bwd(&self) -> Option<&FxHashSet<NodeIndex>> {
+        Some(&self.settled_bwd)
+    }
+
+    fn meeting_node(&self) -> Option<NodeIndex> {
+        self.intersect_node
+    }
+
+    fn record_search_space(&mut self, record: bool) {
+        self.search_space = record.then(SearchSpace::default);
+    }
+
+    fn search_space(&self) -> Option<&SearchSpace> {
+        self.search_space.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use approx::assert_abs_diff_eq;
+    use osm_reader::{RoadType, WayInfo};
+
+    use super::*;
+    use crate::{
+        components::ComponentFilter,
+        contraction_strategy::{ContractionStrategy, CoreSize, UpdateStrategy},
+        graph::node_index,
+        node_contraction::NodeContractor,
+        search::CHSearch,
+        util::test_graphs::{generate_complex_graph, generate_simple_graph},
+    };
+
+    fn simple_overlay_graph() -> OverlayGraph {
+        let mut g = generate_simple_graph();
+        let node_order = vec![
+            node_index(0),
+            node_index(4),
+            node_index(3),
+            node_index(2),
+            node_index(1),
+        ];
+        let mut contractor = NodeContractor::new(&mut g);
+        contractor.run_with_order(&node_order)
+    }
+
+    /// Replaces the checksum in the header of `bytes` after the body was modified
+    fn update_checksum(bytes: &mut [u8]) {
+        let checksum = crc32fast::hash(&bytes[HEADER_SIZE..]) as u64;
+        bytes[64..72].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    fn open_modified(path: &Path, modify: impl FnOnce(&mut Vec<u8>)) -> anyhow::Result<()> {
+        let mut bytes = std::fs::read(path).unwrap();
+        modify(&mut bytes);
+        let modified = path.with_extension("modified.chmm");
+        std::fs::write(&modified, &bytes).unwrap();
+        MmapOverlayGraph::open(&modified).map(|_| ())
+    }
+
+    #[test]
+    fn write_and_open_simple_graph() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("simple_graph.chmm");
+        let overlay_graph = simple_overlay_graph();
+        overlay_graph.write_mmap(&path).unwrap();
+
+        let mapped = MmapOverlayGraph::open(&path).unwrap();
+        let g = overlay_graph.road_graph();
+
+        assert_eq!(mapped.num_nodes(), g.nodes.len());
+        assert_eq!(mapped.num_edges(), g.edges.len());
+        assert_eq!(mapped.header().num_shortcuts as usize, g.num_shortcuts);
+
+        for (edge, mapped_edge) in g.edges().zip(mapped.edges()) {
+            assert_eq!(edge.source, mapped_edge.source);
+            assert_eq!(edge.target, mapped_edge.target);
+            assert_eq!(edge.weight, mapped_edge.weight);
+        }
+
+        for node in 0..g.nodes.len() {
+            let node = node_index(node);
+            let fwd: Vec<EdgeIndex> = mapped.edges_fwd(node).map(|(e, _)| e).collect();
+            let bwd: Vec<EdgeIndex> = mapped.edges_bwd(node).map(|(e, _)| e).collect();
+            assert_eq!(overlay_graph.edges_fwd[node.index()], fwd);
+            assert_eq!(overlay_graph.edges_bwd[node.index()], bwd);
+            assert_eq!(
+                Some(overlay_graph.node_order[node.index()]),
+                mapped.rank(node)
+            );
+        }
+
+        for (edge_idx, replaces) in overlay_graph.shortcuts.iter() {
+            assert_eq!(Some(*replaces), mapped.shortcut(*edge_idx));
+        }
+
+        assert!(!mapped.has_core());
+        assert!(mapped.ways().is_empty());
+        assert_eq!(None, mapped.metric());
+    }
+
+    #[test]
+    fn search_matches_ch_search() {
+        let mut g = generate_complex_graph();
+        let num_nodes = g.nodes.len();
+
+        let mut contractor = NodeContractor::new(&mut g);
+        let overlay_graph = contractor.run();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("complex_graph.chmm");
+        overlay_graph.write_mmap(&path).unwrap();
+
+        let mapped = MmapOverlayGraph::open(&path).unwrap();
+        let mut ch = CHSearch::new(&overlay_graph);
+        let mut mmap_ch = MmapCHSearch::new(&mapped);
+
+        for s in 0..num_nodes {
+            for t in 0..num_nodes {
+                let sp = ch.search(node_index(s), node_index(t)).unwrap();
+                let sp_mmap = mmap_ch.search(node_index(s), node_index(t)).unwrap();
+
+                assert_abs_diff_eq!(sp.weight, sp_mmap.weight, epsilon = 1e-4);
+                assert_eq!(sp.nodes.first(), sp_mmap.nodes.first());
+                assert_eq!(sp.nodes.last(), sp_mmap.nodes.last());
+            }
+        }
+    }
+
+    #[test]
+    fn keep_core_components_ways_and_metric() {
+        let mut g = generate_complex_graph();
+        g.filter_components(ComponentFilter::Tag);
+        g.metric = Some(Metric::new(EarthModel::Karney, 130.0));
+        let way_idx = g.ways.add_way(WayInfo {
+            id: 42,
+            name: Some("Hauptstraße".to_string()),
+            reference: None,
+            road_type: RoadType::Primary,
+            roundabout: false,
+        });
+        g.ways.set(EdgeIndex::new(1), way_idx);
+
+        let strategy = UpdateStrategy::new().set_core_size(CoreSize::Nodes(4));
+        let mut contractor = NodeContractor::new(&mut g);
+        let overlay_graph = contractor.run_with_strategy(ContractionStrategy::LazyUpdate(strategy));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("core_graph.chmm");
+        overlay_graph.write_mmap(&path).unwrap();
+        let mapped = MmapOverlayGraph::open(&path).unwrap();
+
+        let g = overlay_graph.road_graph();
+        assert_eq!(
+            overlay_graph.core_nodes().collect::<Vec<_>>(),
+            mapped.core_nodes().collect::<Vec<_>>()
+        );
+        for node in 0..g.nodes.len() {
+            let node = node_index(node);
+            assert_eq!(overlay_graph.is_core(node), mapped.is_core(node));
+            for other in 0..g.nodes.len() {
+                let other = node_index(other);
+                assert_eq!(g.may_reach(node, other), mapped.may_reach(node, other));
+            }
+        }
+        assert_eq!(&g.ways, mapped.ways());
+        assert_eq!(g.metric, mapped.metric());
+    }
+
+    #[test]
+    fn detect_corrupted_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("corrupted_graph.chmm");
+        let overlay_graph = simple_overlay_graph();
+        overlay_graph.write_mmap(&path).unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+
+        assert!(MmapOverlayGraph::open(&path).is_err());
+
+        bytes[0] = b'X';
+        std::fs::write(&path, &bytes).unwrap();
+
+        assert!(MmapOverlayGraph::open_unchecked(&path).is_err());
+
+        // Truncated file
+        overlay_graph.write_mmap(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 16]).unwrap();
+        assert!(MmapOverlayGraph::open_unchecked(&path).is_err());
+    }
+
+    #[test]
+    fn reject_invalid_offsets_and_indices() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("invalid_graph.chmm");
+        let overlay_graph = simple_overlay_graph();
+        overlay_graph.write_mmap(&path).unwrap();
+
+        let mapped = MmapOverlayGraph::open(&path).unwrap();
+        let layout = mapped.layout.clone();
+        let num_edges = mapped.num_edges() as u32;
+        drop(mapped);
+
+        // Offsets behind the end of the adjacency list, even with a valid checksum
+        let offsets = layout.offsets_fwd.clone();
+        let result = open_modified(&path, |bytes| {
+            bytes[offsets.start + 8..offsets.start + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+            update_checksum(bytes);
+        });
+        assert!(result.is_err());
+
+        // Adjacency entry with an unknown edge
+        let edges_fwd = layout.edges_fwd.clone();
+        let result = open_modified(&path, |bytes| {
+            bytes[edges_fwd.start..edges_fwd.start + 4].copy_from_slice(&num_edges.to_le_bytes());
+            update_checksum(bytes);
+        });
+        assert!(result.is_err());
+
+        // Edge to an unknown node
+        let edges = layout.edges.clone();
+        let result = open_modified(&path, |bytes| {
+            bytes[edges.start + 4..edges.start + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+            update_checksum(bytes);
+        });
+        assert!(result.is_err());
+
+        // Shortcut replacing itself
+        let shortcuts = layout.shortcuts.clone();
+        let result = open_modified(&path, |bytes| {
+            bytes[shortcuts.start..shortcuts.start + 8].copy_from_slice(&[0; 8]);
+            update_checksum(bytes);
+        });
+        assert!(result.is_err());
+
+        // Section sizes overflowing the address space
+        for field in [16, 24] {
+            let result = open_modified(&path, |bytes| {
+                bytes[field..field + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+            });
+            assert!(result.is_err());
+        }
+        let result = open_modified(&path, |bytes| {
+            bytes[88..96].copy_from_slice(&(u64::MAX - 2).to_le_bytes());
+        });
+        assert!(result.is_err());
+    }
+}
@@ -149,6 +149,14 @@ impl<'a> NodeContractor<'a> {
         };
         self.stats.stop_timer_node_ordering();
 
+        // Nodes which are left uncontracted
+        let core_size = match strategy {
+            ContractionStrategy::LazyUpdate(strat) => strat
+                .core_size()
+                .map_or(0, |core_size| core_size.num_core_nodes(self.num_nodes)),
+            _ => 0,
+        };
+
         let mut step_size = STEP_SIZE;
         let mut next_goal = step_size;
 
@@ -156,12 +164,12 @@ impl<'a> NodeContractor<'a> {
         let mut did_fixed_update = false;
 
         info!("Progress: {:.2}%", 0.0 * 100.0);
-        let pb = ProgressBar::new(queue.len() as u64);
+        let pb = ProgressBar::new((queue.len() - core_size) as u64);
         pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {human_pos}/{human_len} Nodes ({eta})")
         .unwrap()
         .with_key("eta", |state: &ProgressState, w: &mut dyn Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
         .progress_chars("#>-"));
-        while !queue.is_empty() {
+        while queue.len() > core_size {
             if let ContractionStrategy::LazyUpdate(strat) = strategy {
                 // Do recalculation if
                 // - too many lazy updates were performed consecutively
@@ -281,15 +289,28 @@ impl<'a> NodeContractor<'a> {
         info!("{:?}", self.stats);
         println!("{}", self.stats);
 
+        // The remaining nodes form the core and are ranked above all contracted nodes
+        let mut core = Vec::with_capacity(queue.len());
+        while let Some((node, _)) = queue.pop() {
+            self.node_ranks[node.index()] = self.num_nodes - queue.len();
+            core.push(node);
+        }
+        if !core.is_empty() {
+            info!("Left {} nodes uncontracted in the core", core.len());
+        }
+
         self.g.edges.shrink_to_fit();
         self.shortcuts.shrink_to_fit();
-        OverlayGraph::new(
+        let mut overlay_graph = OverlayGraph::new(
             edges_fwd,
             edges_bwd,
             self.g.to_owned(),
             self.shortcuts.clone(),
             self.node_ranks.clone(),
-        )
+        );
+        overlay_graph.metadata.params = Some(self.params);
+        overlay_graph.set_core(&core);
+        overlay_graph
     }
 
     pub fn run(&mut self) -> OverlayGraph {
@@ -1,17 +1,39 @@
 //! The overlay graph is a representation of the graph after running the node contraction process.
-use std::{fmt::Display, path::PathBuf};
-
-use anyhow::Context;
-
-use csv::Writer;
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context};
+use csv::{StringRecord, Writer};
+use log::info;
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 
-use crate::graph::{DefaultIdx, Edge, EdgeIndex, Graph, Node, NodeIndex};
+use crate::{
+    constants::OSMId,
+    graph::{CompactGraph, DefaultIdx, Edge, EdgeIndex, Graph, Node, NodeIndex},
+    serialization::{self, BuildMetadata, Compression, FormatError, OVERLAY_GRAPH_MAGIC},
+    verification::{self, ValidationReport},
+};
+
+const CSV_NODES: &str = "nodes.csv";
+const CSV_EDGES: &str = "edges.csv";
+const CSV_EDGES_FWD: &str = "edges_fwd.csv";
+const CSV_EDGES_BWD: &str = "edges_bwd.csv";
 
 /// Representation of the graph after running the node contraction process. The
 /// graph contains two adjancency lists, one for the upward graph G↑ and one for
 /// the downward graph G↓.
+///
+/// If the contraction was stopped early (see
+/// [`CoreSize`](crate::contraction_strategy::CoreSize)), the remaining nodes form the core.
+/// Core nodes have no upward or downward edges, the edges between them are available with
+/// [`OverlayGraph::core_edges_out`] and [`OverlayGraph::core_edges_in`].
 #[derive(Serialize, Deserialize)]
 pub struct OverlayGraph<Idx = DefaultIdx> {
     // Represents the upward graph G↑
@@ -23,6 +45,14 @@ pub struct OverlayGraph<Idx = DefaultIdx> {
 
     pub node_order: Vec<usize>,
 
+    /// Information about how the graph was built. Stored in the file header.
+    #[serde(skip)]
+    pub metadata: BuildMetadata,
+
+    /// Marks the uncontracted nodes. Empty if all nodes are contracted.
+    #[serde(skip)]
+    core: Vec<bool>,
+
     g: Graph<Idx>,
 }
 
@@ -37,31 +67,122 @@ impl OverlayGraph {
         OverlayGraph {
             edges_fwd,
             edges_bwd,
+            metadata: BuildMetadata {
+                fingerprint: graph.fingerprint,
+                ..BuildMetadata::new(graph.source.clone(), None)
+            },
             g: graph,
             shortcuts,
             node_order: node_ranks,
+            core: Vec::new(),
         }
     }
 
+    /// Marks `core` as the uncontracted nodes
+    pub(crate) fn set_core(&mut self, core: &[NodeIndex]) {
+        self.core.clear();
+        if !core.is_empty() {
+            self.core.resize(self.g.nodes.len(), false);
+            for node in core {
+                self.core[node.index()] = true;
+            }
+        }
+    }
+
+    /// Returns true if `node` was left uncontracted
+    pub fn is_core(&self, node: NodeIndex) -> bool {
+        self.core.get(node.index()).copied().unwrap_or(false)
+    }
+
+    /// Returns true if the contraction was stopped early and some nodes are uncontracted
+    pub fn has_core(&self) -> bool {
+        !self.core.is_empty()
+    }
+
+    /// Iterator over all uncontracted nodes
+    pub fn core_nodes(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.core
+            .iter()
+            .enumerate()
+            .filter(|(_, is_core)| **is_core)
+            .map(|(node, _)| NodeIndex::new(node))
+    }
+
+    /// Outgoing edges (including shortcuts) of the core node `node` to other core nodes
+    pub fn core_edges_out(&self, node: NodeIndex) -> impl Iterator<Item = (EdgeIndex, &Edge)> {
+        self.g.edges_out[node.index()]
+            .iter()
+            .map(|edge_idx| (*edge_idx, &self.g.edges[edge_idx.index()]))
+            .filter(|(_, edge)| self.is_core(edge.target))
+    }
+
+    /// Incoming edges (including shortcuts) of the core node `node` from other core nodes
+    pub fn core_edges_in(&self, node: NodeIndex) -> impl Iterator<Item = (EdgeIndex, &Edge)> {
+        self.g.edges_in[node.index()]
+            .iter()
+            .map(|edge_idx| (*edge_idx, &self.g.edges[edge_idx.index()]))
+            .filter(|(_, edge)| self.is_core(edge.source))
+    }
+
     pub fn nodes(&self) -> impl Iterator<Item = &Node> {
         self.g.nodes()
     }
 
-    pub fn encode(&self, path: impl Into<PathBuf>) -> anyhow::Result<usize> {
-        let mut file = std::fs::File::create(path.into())?;
-        let bytes_written =
-            bincode::serde::encode_into_std_write(self, &mut file, bincode::config::standard())?;
+    /// Returns the index of the node with the OSM id `id`, see [`Graph::node_by_osm_id`]
+    pub fn node_by_osm_id(&self, id: OSMId) -> Option<NodeIndex> {
+        self.g.node_by_osm_id(id)
+    }
+
+    /// Returns the OSM id of the node with the given index if it exists
+    pub fn osm_id(&self, node_idx: NodeIndex) -> Option<OSMId> {
+        self.g.osm_id(node_idx)
+    }
+
+    /// Writes the graph in the versioned binary format without compression.
+    /// See [`crate::serialization`].
+    ///
+    /// Returns the number of bytes written.
+    pub fn encode(&self, path: impl Into<PathBuf>) -> Result<usize, FormatError> {
+        self.encode_with_compression(path, Compression::None)
+    }
 
-        Ok(bytes_written)
+    /// Writes the graph in the versioned binary format and compresses it with `compression`.
+    ///
+    /// Returns the number of bytes written.
+    pub fn encode_with_compression(
+        &self,
+        path: impl Into<PathBuf>,
+        compression: Compression,
+    ) -> Result<usize, FormatError> {
+        serialization::write_file(
+            &path.into(),
+            OVERLAY_GRAPH_MAGIC,
+            &self.metadata,
+            compression,
+            &CompactOverlayGraph::from(self),
+        )
     }
 
-    pub fn from_decode(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
-        let file = std::fs::File::open(path.into()).context("Failed to read file")?;
-        let mut reader = std::io::BufReader::new(file);
+    /// Reads a graph written by [`OverlayGraph::encode`]. The compression is detected from
+    /// the header. Files written by older versions are still supported.
+    pub fn from_decode(path: impl Into<PathBuf>) -> Result<Self, FormatError> {
+        let mut body = serialization::read_file(&path.into(), OVERLAY_GRAPH_MAGIC)?;
+
+        let mut overlay_graph: OverlayGraph = match body.header().version {
+            // Files without header stored the plain struct
+            0 => {
+                let mut overlay_graph: OverlayGraph = body.decode()?;
+                overlay_graph.g.rebuild_osm_ids();
+                overlay_graph
+            }
+            _ => body.decode::<CompactOverlayGraph>()?.into(),
+        };
+        let header = body.finish()?;
 
-        let overlay_graph =
-            bincode::serde::decode_from_std_read(&mut reader, bincode::config::standard())
-                .context("Failed to decode file")?;
+        info!("Loaded overlay graph ({})", header.metadata);
+        overlay_graph.g.source = header.metadata.source.clone();
+        overlay_graph.g.fingerprint = header.metadata.fingerprint;
+        overlay_graph.metadata = header.metadata;
 
         Ok(overlay_graph)
     }
@@ -101,102 +222,453 @@ impl OverlayGraph {
         unpacked
     }
 
+    /// Checks the ranks of the upward and downward edges, the unpacking of all shortcuts and
+    /// that no edge index is dangling. See [`verification::validate`].
+    pub fn validate(&self) -> ValidationReport {
+        verification::validate(self)
+    }
+
     pub fn print_info(&self) {
         println!(
             "SearchGraph:\t#Nodes: {}, #Edges: {}",
             self.edges_fwd.len(),
             self.edges_fwd.iter().flatten().count()
         );
+        if self.has_core() {
+            println!("Core:\t\t#Nodes: {}", self.core_nodes().count());
+        }
     }
 
+    /// Writes the graph as `nodes.csv`, `edges.csv`, `edges_fwd.csv` and `edges_bwd.csv` to
+    /// the current directory. See [`OverlayGraph::write_csv_into`] for the columns.
     pub fn export_csv(&self) -> anyhow::Result<()> {
-        self.g.export_csv()?;
-
-        let mut wtr = Writer::from_path("edges_fwd.csv")?;
-        wtr.write_record(["source", "target_edge"])?;
-
-        // Export edges_fwd
-        for (idx_from, edges) in self.edges_fwd.iter().enumerate() {
-            for idx_to in edges {
-                wtr.write_record(&[idx_from.to_string(), idx_to.index().to_string()])?;
-            }
-        }
-        wtr.flush()?;
+        self.export_csv_to(".")
+    }
 
-        let mut wtr = Writer::from_path("edges_bwd.csv")?;
-        wtr.write_record(["source", "target_edge"])?;
+    /// Writes the graph as `nodes.csv`, `edges.csv`, `edges_fwd.csv` and `edges_bwd.csv` to
+    /// `dir`. The directory is created if it does not exist.
+    pub fn export_csv_to(&self, dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        self.write_csv_into(
+            File::create(dir.join(CSV_NODES))?,
+            File::create(dir.join(CSV_EDGES))?,
+            File::create(dir.join(CSV_EDGES_FWD))?,
+            File::create(dir.join(CSV_EDGES_BWD))?,
+        )
+    }
 
-        // Export edges_fwd
-        for (idx_from, edges) in self.edges_bwd.iter().enumerate() {
-            for idx_to in edges {
-                wtr.write_record(&[idx_from.to_string(), idx_to.index().to_string()])?;
-            }
+    /// Writes the graph as CSV into the given writers:
+    /// - `nodes`: `id,lat,lon,rank,core,level`. The row number is the node index. `rank` and
+    ///   `level` (see [`OverlayGraph::levels`]) are empty if the graph has no node order, `core`
+    ///   is `true` for the uncontracted nodes of the core.
+    /// - `edges`: `id,source,target,weight,shortcut_in,shortcut_out`. `source` and `target`
+    ///   are node indices. For shortcuts `shortcut_in` and `shortcut_out` contain the two
+    ///   edges the shortcut replaces, otherwise they are empty.
+    /// - `edges_fwd`/`edges_bwd`: `source,target_edge` for the upward and downward graph.
+    pub fn write_csv_into<W: Write>(
+        &self,
+        nodes: W,
+        edges: W,
+        edges_fwd: W,
+        edges_bwd: W,
+    ) -> anyhow::Result<()> {
+        let mut wtr = Writer::from_writer(nodes);
+        wtr.write_record(["id", "lat", "lon", "rank", "core", "level"])?;
+        let levels = self.levels();
+        for (node_idx, node) in self.g.nodes().enumerate() {
+            let rank = self
+                .node_order
+                .get(node_idx)
+                .map(|rank| rank.to_string())
+                .unwrap_or_default();
+            let level = levels
+                .get(node_idx)
+                .map(|level| level.to_string())
+                .unwrap_or_default();
+            wtr.write_record(&[
+                node.id.to_string(),
+                node.lat.to_string(),
+                node.lon.to_string(),
+                rank,
+                self.is_core(NodeIndex::new(node_idx)).to_string(),
+                level,
+            ])?;
         }
         wtr.flush()?;
 
-        let mut wtr = csv::Writer::from_path("shortcuts.csv")?;
-        wtr.write_record(["id", "in", "out"])?;
-
-        for (edge_idx, replaces) in self.shortcuts.iter() {
+        let mut wtr = Writer::from_writer(edges);
+        wtr.write_record([
+            "id",
+            "source",
+            "target",
+            "weight",
+            "shortcut_in",
+            "shortcut_out",
+        ])?;
+        for (edge_idx, edge) in self.g.edges().enumerate() {
+            let [shortcut_in, shortcut_out] = match self.shortcuts.get(&EdgeIndex::new(edge_idx)) {
+                Some([incoming, outgoing]) => {
+                    [incoming.index().to_string(), outgoing.index().to_string()]
+                }
+                None => Default::default(),
+            };
             wtr.write_record(&[
-                edge_idx.index().to_string(),
-                replaces[0].index().to_string(),
-                replaces[1].index().to_string(),
+                edge_idx.to_string(),
+                edge.source.index().to_string(),
+                edge.target.index().to_string(),
+                edge.weight.to_string(),
+                shortcut_in,
+                shortcut_out,
             ])?;
         }
         wtr.flush()?;
 
+        write_adjacency_csv(edges_fwd, &self.edges_fwd)?;
+        write_adjacency_csv(edges_bwd, &self.edges_bwd)?;
+
         Ok(())
     }
 
+    /// Reads a graph exported with [`OverlayGraph::export_csv_to`] from `dir`.
+    pub fn from_csv_dir(dir: impl AsRef<Path>) -> anyhow::Result<OverlayGraph> {
+        let dir = dir.as_ref();
+        let open = |name: &str| {
+            File::open(dir.join(name)).with_context(|| format!("Failed to open {}", name))
+        };
+
+        OverlayGraph::from_csv_readers(
+            open(CSV_NODES)?,
+            open(CSV_EDGES)?,
+            open(CSV_EDGES_FWD)?,
+            open(CSV_EDGES_BWD)?,
+        )
+    }
+
+    /// Reads the upward and downward edges and the shortcuts of `g` from the files written by
+    /// older versions of [`OverlayGraph::export_csv`]. `g` has to contain the shortcuts
+    /// already. The node order is derived with [`OverlayGraph::derive_node_order`].
+    ///
+    /// Exports of the current version are read with [`OverlayGraph::from_csv_dir`].
     pub fn from_csv<P: Into<PathBuf>>(
         g: Graph,
         csv_shortcuts: P,
         csv_fwd: P,
         csv_bwd: P,
     ) -> anyhow::Result<OverlayGraph> {
-        let mut edges_fwd = vec![Vec::new(); g.nodes.len()];
-        let mut edges_bwd = vec![Vec::new(); g.nodes.len()];
+        let open = |path: P| {
+            let path = path.into();
+            File::open(&path).with_context(|| format!("Failed to open {}", path.display()))
+        };
+
+        let edges_fwd = read_adjacency_csv(open(csv_fwd)?, g.nodes.len(), g.edges.len())?;
+        let edges_bwd = read_adjacency_csv(open(csv_bwd)?, g.nodes.len(), g.edges.len())?;
 
-        let mut rdr = csv::Reader::from_path(csv_fwd.into())?;
+        let mut shortcuts = FxHashMap::default();
+        let mut rdr = csv::Reader::from_reader(open(csv_shortcuts)?);
         for result in rdr.records() {
             let record = result?;
-            let source = record[0].parse::<usize>()?;
-            let target = record[1].parse::<usize>()?;
-
-            edges_fwd[source].push(EdgeIndex::new(target));
+            let edge_idx = parse_field(&record, 0)?;
+            let replaces = [parse_field(&record, 1)?, parse_field(&record, 2)?];
+            if edge_idx >= g.edges.len() || replaces.iter().any(|child| *child >= edge_idx) {
+                bail!("Invalid shortcut {} -> {:?}", edge_idx, replaces);
+            }
+            shortcuts.insert(EdgeIndex::new(edge_idx), replaces.map(EdgeIndex::new));
         }
 
-        let mut rdr = csv::Reader::from_path(csv_bwd.into())?;
+        let mut overlay_graph = OverlayGraph::new(edges_fwd, edges_bwd, g, shortcuts, Vec::new());
+        overlay_graph.node_order = overlay_graph.derive_node_order()?;
+        Ok(overlay_graph)
+    }
+
+    /// Reads a graph written with [`OverlayGraph::write_csv_into`] from the given readers.
+    ///
+    /// If the nodes have no ranks, the order is derived from their levels or, if those are
+    /// missing too, with [`OverlayGraph::derive_node_order`]. Invalid weights, shortcuts which don't replace
+    /// two preceding edges and indices of unknown nodes or edges are reported as errors.
+    pub fn from_csv_readers<R: Read>(
+        nodes: R,
+        edges: R,
+        edges_fwd: R,
+        edges_bwd: R,
+    ) -> anyhow::Result<OverlayGraph> {
+        let mut g = Graph::new();
+        let mut node_order = Vec::new();
+        let mut core = Vec::new();
+        let mut levels = Vec::new();
+
+        let mut rdr = csv::Reader::from_reader(nodes);
         for result in rdr.records() {
             let record = result?;
-            let source = record[0].parse::<usize>()?;
-            let target = record[1].parse::<usize>()?;
-
-            edges_bwd[source].push(EdgeIndex::new(target));
+            g.add_node(Node::new(
+                parse_field(&record, 0)?,
+                parse_field(&record, 1)?,
+                parse_field(&record, 2)?,
+            ));
+            // Files written by older versions have no rank column
+            if let Some(rank) = record.get(3).filter(|rank| !rank.is_empty()) {
+                node_order.push(rank.parse::<usize>()?);
+            }
+            // Older files have no core column either
+            if record.get(4).is_some() && parse_field::<bool>(&record, 4)? {
+                core.push(NodeIndex::new(g.nodes.len() - 1));
+            }
+            if let Some(level) = record.get(5).filter(|level| !level.is_empty()) {
+                levels.push(level.parse::<usize>()?);
+            }
+        }
+        if !node_order.is_empty() && node_order.len() != g.nodes.len() {
+            bail!(
+                "Only {} of {} nodes have a rank",
+                node_order.len(),
+                g.nodes.len()
+            );
+        }
+        let mut ranked = vec![false; node_order.len()];
+        for (node, rank) in node_order.iter().enumerate() {
+            // Ranks start at 1, see `NodeContractor`
+            if *rank == 0 || *rank > ranked.len() || std::mem::replace(&mut ranked[rank - 1], true)
+            {
+                bail!("Node {} has invalid or duplicate rank {}", node, rank);
+            }
+        }
+        if !levels.is_empty() && levels.len() != g.nodes.len() {
+            bail!(
+                "Only {} of {} nodes have a level",
+                levels.len(),
+                g.nodes.len()
+            );
         }
 
-        let mut rdr = csv::Reader::from_path(csv_shortcuts.into())?;
         let mut shortcuts = FxHashMap::default();
-        for result in rdr.records() {
+        let mut rdr = csv::Reader::from_reader(edges);
+        for (row, result) in rdr.records().enumerate() {
             let record = result?;
-            let id = record[0].parse::<usize>()?;
-            let in_ = record[1].parse::<usize>()?;
-            let out = record[2].parse::<usize>()?;
+            let edge_idx: usize = parse_field(&record, 0)?;
+            if edge_idx != row {
+                bail!(
+                    "Edges must be sorted by id (expected {}, found {})",
+                    row,
+                    edge_idx
+                );
+            }
 
-            shortcuts.insert(
-                EdgeIndex::new(id),
-                [EdgeIndex::new(in_), EdgeIndex::new(out)],
+            let edge = Edge::new(
+                NodeIndex::new(parse_field(&record, 1)?),
+                NodeIndex::new(parse_field(&record, 2)?),
+                parse_field(&record, 3)?,
             );
+            if edge.source.index() >= g.nodes.len() || edge.target.index() >= g.nodes.len() {
+                bail!("Edge {} references unknown node", edge_idx);
+            }
+            if !edge.weight.is_finite() || edge.weight <= 0.0 {
+                bail!("Edge {} has invalid weight {}", edge_idx, edge.weight);
+            }
+
+            if record.get(4).unwrap_or_default().is_empty() {
+                // Edges are written without duplicates, so they must not be merged again
+                g.add_edge_internal(edge, false);
+            } else {
+                // The replaced edges exist before the shortcut is added
+                let replaces: [usize; 2] = [parse_field(&record, 4)?, parse_field(&record, 5)?];
+                if replaces.iter().any(|child| *child >= edge_idx) {
+                    bail!(
+                        "Shortcut {} replaces unknown edges {:?}",
+                        edge_idx,
+                        replaces
+                    );
+                }
+                let replaces = replaces.map(EdgeIndex::new);
+                let edge_idx = g.add_shortcut(edge);
+                shortcuts.insert(edge_idx, replaces);
+            }
         }
 
-        Ok(OverlayGraph::new(
-            edges_fwd,
-            edges_bwd,
-            g,
+        let edges_fwd = read_adjacency_csv(edges_fwd, g.nodes.len(), g.edges.len())?;
+        let edges_bwd = read_adjacency_csv(edges_bwd, g.nodes.len(), g.edges.len())?;
+
+        let mut overlay_graph = OverlayGraph::new(edges_fwd, edges_bwd, g, shortcuts, node_order);
+        overlay_graph.set_core(&core);
+        if overlay_graph.node_order.is_empty() && !levels.is_empty() {
+            info!("No ranks found, deriving the node order from the levels");
+            // Every edge leads to a higher level, so ordering by level keeps the edges upward
+            let mut nodes: Vec<_> = (0..levels.len()).collect();
+            nodes.sort_by_key(|node| levels[*node]);
+            overlay_graph.node_order = vec![0; levels.len()];
+            for (rank, node) in nodes.into_iter().enumerate() {
+                overlay_graph.node_order[node] = rank + 1;
+            }
+        } else if overlay_graph.node_order.is_empty() {
+            info!("No ranks found, deriving the node order from the edges");
+            overlay_graph.node_order = overlay_graph.derive_node_order()?;
+        }
+
+        Ok(overlay_graph)
+    }
+
+    /// Derives ranks consistent with the upward and downward edges: every upward edge leads to
+    /// and every downward edge comes from a higher ranked node. Ranks start at 1 like the ones
+    /// assigned during the contraction. The order of unrelated nodes is arbitrary but
+    /// deterministic.
+    ///
+    /// Fails if the edges contain a cycle and therefore no consistent order exists.
+    pub fn derive_node_order(&self) -> anyhow::Result<Vec<usize>> {
+        let num_nodes = self.g.nodes.len();
+
+        // Topological sort with an edge from every node to each higher ranked neighbor
+        let mut num_lower = vec![0usize; num_nodes];
+        for node in 0..num_nodes {
+            for neighbor in self.higher_neighbors(node) {
+                num_lower[neighbor.index()] += 1;
+            }
+        }
+
+        let mut queue: VecDeque<_> = (0..num_nodes).filter(|n| num_lower[*n] == 0).collect();
+        let mut node_order = vec![0; num_nodes];
+        let mut rank = 0;
+        while let Some(node) = queue.pop_front() {
+            rank += 1;
+            node_order[node] = rank;
+            for neighbor in self.higher_neighbors(node) {
+                num_lower[neighbor.index()] -= 1;
+                if num_lower[neighbor.index()] == 0 {
+                    queue.push_back(neighbor.index());
+                }
+            }
+        }
+
+        if rank != num_nodes {
+            bail!(
+                "Upward and downward edges contain a cycle, {} nodes can't be ranked",
+                num_nodes - rank
+            );
+        }
+        Ok(node_order)
+    }
+
+    /// Level of every node in the hierarchy: 0 for nodes without lower ranked neighbors,
+    /// otherwise one more than the highest level of those neighbors. The highest level is the
+    /// depth of the hierarchy, which the ranks alone don't show. Empty if the graph has no
+    /// node order.
+    pub fn levels(&self) -> Vec<usize> {
+        let mut nodes: Vec<_> = (0..self.node_order.len()).collect();
+        nodes.sort_by_key(|node| self.node_order[*node]);
+
+        let mut levels = vec![0; nodes.len()];
+        for node in nodes {
+            for neighbor in self.higher_neighbors(node) {
+                levels[neighbor.index()] = levels[neighbor.index()].max(levels[node] + 1);
+            }
+        }
+        levels
+    }
+
+    /// Targets of the upward and sources of the downward edges of `node`
+    fn higher_neighbors(&self, node: usize) -> impl Iterator<Item = NodeIndex> + '_ {
+        let fwd = self.edges_fwd[node]
+            .iter()
+            .map(|edge_idx| self.g.edges[edge_idx.index()].target);
+        let bwd = self.edges_bwd[node]
+            .iter()
+            .map(|edge_idx| self.g.edges[edge_idx.index()].source);
+        fwd.chain(bwd)
+    }
+}
+
+fn write_adjacency_csv<W: Write>(wtr: W, adjacency: &[Vec<EdgeIndex>]) -> anyhow::Result<()> {
+    let mut wtr = Writer::from_writer(wtr);
+    wtr.write_record(["source", "target_edge"])?;
+
+    for (idx_from, edges) in adjacency.iter().enumerate() {
+        for idx_to in edges {
+            wtr.write_record(&[idx_from.to_string(), idx_to.index().to_string()])?;
+        }
+    }
+    wtr.flush()?;
+
+    Ok(())
+}
+
+/// Parses the field at `idx` of `record`. Missing fields are reported as errors.
+fn parse_field<T>(record: &StringRecord, idx: usize) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let field = record
+        .get(idx)
+        .with_context(|| format!("Missing column {} in line {:?}", idx, record.position()))?;
+    field
+        .parse()
+        .with_context(|| format!("Invalid value {:?} in column {}", field, idx))
+}
+
+fn read_adjacency_csv<R: Read>(
+    rdr: R,
+    num_nodes: usize,
+    num_edges: usize,
+) -> anyhow::Result<Vec<Vec<EdgeIndex>>> {
+    let mut adjacency = vec![Vec::new(); num_nodes];
+
+    let mut rdr = csv::Reader::from_reader(rdr);
+    for result in rdr.records() {
+        let record = result?;
+        let source: usize = parse_field(&record, 0)?;
+        let target: usize = parse_field(&record, 1)?;
+        if source >= num_nodes || target >= num_edges {
+            bail!("Invalid adjacency entry {} -> {}", source, target);
+        }
+
+        adjacency[source].push(EdgeIndex::new(target));
+    }
+
+    Ok(adjacency)
+}
+
+/// Space efficient representation of an [`OverlayGraph`] used for serialization. The
+/// adjacency lists are delta encoded.
+#[derive(Serialize, Deserialize)]
+struct CompactOverlayGraph {
+    edges_fwd: Vec<Vec<i64>>,
+    edges_bwd: Vec<Vec<i64>>,
+    shortcuts: Vec<(EdgeIndex, [EdgeIndex; 2])>,
+    node_order: Vec<usize>,
+    core: Vec<NodeIndex>,
+    g: CompactGraph,
+}
+
+impl From<&OverlayGraph> for CompactOverlayGraph {
+    fn from(overlay_graph: &OverlayGraph) -> Self {
+        let mut shortcuts: Vec<_> = overlay_graph
+            .shortcuts
+            .iter()
+            .map(|(edge_idx, replaces)| (*edge_idx, *replaces))
+            .collect();
+        shortcuts.sort_unstable_by_key(|(edge_idx, _)| *edge_idx);
+
+        CompactOverlayGraph {
+            edges_fwd: serialization::delta_encode(&overlay_graph.edges_fwd),
+            edges_bwd: serialization::delta_encode(&overlay_graph.edges_bwd),
             shortcuts,
-            Default::default(),
-        ))
+            node_order: overlay_graph.node_order.clone(),
+            core: overlay_graph.core_nodes().collect(),
+            g: CompactGraph::from(&overlay_graph.g),
+        }
+    }
+}
+
+impl From<CompactOverlayGraph> for OverlayGraph {
+    fn from(overlay_graph: CompactOverlayGraph) -> Self {
+        let mut decoded = OverlayGraph::new(
+            serialization::delta_decode(overlay_graph.edges_fwd),
+            serialization::delta_decode(overlay_graph.edges_bwd),
+            overlay_graph.g.into(),
+            overlay_graph.shortcuts.into_iter().collect(),
+            overlay_graph.node_order,
+        );
+        decoded.set_core(&overlay_graph.core);
+        decoded
     }
 }
 
@@ -227,11 +699,18 @@ impl Display for OverlayGraph {
 
 #[cfg(test)]
 mod tests {
+    use crate::contraction_strategy::{ContractionStrategy, CoreSize, UpdateStrategy};
     use crate::{
-        edge, graph::*, overlay_graph::OverlayGraph, util::test_graphs::overlay_graph_vaterstetten,
+        edge,
+        graph::*,
+        overlay_graph::OverlayGraph,
+        serialization::Compression,
+        util::test_graphs::{generate_complex_graph, overlay_graph_vaterstetten},
     };
     use crate::{node_contraction::NodeContractor, util::test_graphs::generate_simple_graph};
 
+    use super::*;
+
     #[test]
     fn test_unpacking_edges() {
         //           B
@@ -329,16 +808,276 @@ mod tests {
 
         let overlay_graph = contractor.run_with_order(&node_order);
 
-        overlay_graph.export_csv().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        overlay_graph.export_csv_to(dir.path()).unwrap();
 
-        let h = overlay_graph.road_graph().clone();
-
-        let overlay_graph_imported =
-            OverlayGraph::from_csv(h, "shortcuts.csv", "edges_fwd.csv", "edges_bwd.csv").unwrap();
+        let overlay_graph_imported = OverlayGraph::from_csv_dir(dir.path()).unwrap();
 
         assert_eq!(overlay_graph.edges_bwd, overlay_graph_imported.edges_bwd);
         assert_eq!(overlay_graph.edges_fwd, overlay_graph_imported.edges_fwd);
         assert_eq!(overlay_graph.shortcuts, overlay_graph_imported.shortcuts);
+        assert_eq!(overlay_graph.node_order, overlay_graph_imported.node_order);
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut g = generate_complex_graph();
+        let mut contractor = NodeContractor::new(&mut g);
+        let overlay_graph = contractor.run();
+
+        let mut sinks: [Vec<u8>; 4] = Default::default();
+        let [nodes, edges, edges_fwd, edges_bwd] = &mut sinks;
+        overlay_graph
+            .write_csv_into(nodes, edges, edges_fwd, edges_bwd)
+            .unwrap();
+
+        let [nodes, edges, edges_fwd, edges_bwd] = &sinks;
+        let overlay_graph_imported = OverlayGraph::from_csv_readers(
+            nodes.as_slice(),
+            edges.as_slice(),
+            edges_fwd.as_slice(),
+            edges_bwd.as_slice(),
+        )
+        .unwrap();
+
+        assert_eq!(overlay_graph.edges_fwd, overlay_graph_imported.edges_fwd);
+        assert_eq!(overlay_graph.edges_bwd, overlay_graph_imported.edges_bwd);
+        assert_eq!(overlay_graph.shortcuts, overlay_graph_imported.shortcuts);
+        assert_eq!(overlay_graph.node_order, overlay_graph_imported.node_order);
+        assert_eq!(overlay_graph.g.edges_in, overlay_graph_imported.g.edges_in);
+        assert_eq!(
+            overlay_graph.g.edges_out,
+            overlay_graph_imported.g.edges_out
+        );
+        assert_eq!(
+            overlay_graph.g.num_shortcuts,
+            overlay_graph_imported.g.num_shortcuts
+        );
+        for (e1, e2) in overlay_graph
+            .g
+            .edges()
+            .zip(overlay_graph_imported.g.edges())
+        {
+            assert_eq!(e1.source, e2.source);
+            assert_eq!(e1.target, e2.target);
+            assert_eq!(e1.weight, e2.weight);
+        }
+        for (n1, n2) in overlay_graph.nodes().zip(overlay_graph_imported.nodes()) {
+            assert_eq!(n1.id, n2.id);
+            assert_eq!(n1.lat, n2.lat);
+            assert_eq!(n1.lon, n2.lon);
+        }
+
+        // Exporting the imported graph again yields the same files
+        let mut sinks_again: [Vec<u8>; 4] = Default::default();
+        let [nodes, edges, edges_fwd, edges_bwd] = &mut sinks_again;
+        overlay_graph_imported
+            .write_csv_into(nodes, edges, edges_fwd, edges_bwd)
+            .unwrap();
+        assert_eq!(sinks, sinks_again);
+    }
+
+    #[test]
+    fn import_legacy_csv() {
+        let mut g = generate_complex_graph();
+        let overlay_graph = NodeContractor::new(&mut g).run();
+
+        // Older exports stored the shortcuts in a separate file and the graph with Graph::export_csv
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        write_adjacency_csv(
+            File::create(path("edges_fwd.csv")).unwrap(),
+            &overlay_graph.edges_fwd,
+        )
+        .unwrap();
+        write_adjacency_csv(
+            File::create(path("edges_bwd.csv")).unwrap(),
+            &overlay_graph.edges_bwd,
+        )
+        .unwrap();
+        let mut wtr = Writer::from_path(path("shortcuts.csv")).unwrap();
+        wtr.write_record(["id", "in", "out"]).unwrap();
+        for (edge_idx, [incoming, outgoing]) in overlay_graph.shortcuts.iter() {
+            wtr.serialize((edge_idx.index(), incoming.index(), outgoing.index()))
+                .unwrap();
+        }
+        wtr.flush().unwrap();
+
+        let imported = OverlayGraph::from_csv(
+            overlay_graph.g.clone(),
+            path("shortcuts.csv"),
+            path("edges_fwd.csv"),
+            path("edges_bwd.csv"),
+        )
+        .unwrap();
+        assert_eq!(overlay_graph.edges_fwd, imported.edges_fwd);
+        assert_eq!(overlay_graph.shortcuts, imported.shortcuts);
+        assert!(imported.validate().is_ok());
+    }
+
+    #[test]
+    fn reject_invalid_csv() {
+        let mut g = generate_simple_graph();
+        let overlay_graph = NodeContractor::new(&mut g).run();
+
+        let mut sinks: [Vec<u8>; 4] = Default::default();
+        let [nodes, edges, edges_fwd, edges_bwd] = &mut sinks;
+        overlay_graph
+            .write_csv_into(nodes, edges, edges_fwd, edges_bwd)
+            .unwrap();
+        let edges = String::from_utf8(sinks[1].clone()).unwrap();
+        // Columns: id, source, target, weight, replaced edges
+        let first_edge: Vec<_> = edges.lines().nth(1).unwrap().split(',').collect();
+        let source_target = first_edge[1..3].join(",");
+        let last_row = edges.lines().count() - 2;
+
+        let import = |edges: &str| {
+            OverlayGraph::from_csv_readers(
+                sinks[0].as_slice(),
+                edges.as_bytes(),
+                sinks[2].as_slice(),
+                sinks[3].as_slice(),
+            )
+        };
+        assert!(import(&edges).is_ok());
+
+        let with_first_edge = |edge: &str| {
+            let mut lines: Vec<_> = edges.lines().map(|line| line.to_string()).collect();
+            lines[1] = edge.to_string();
+            lines.join("\n")
+        };
+        for weight in ["0", "-1", "NaN", "inf", "fast"] {
+            let edges = with_first_edge(&format!("0,{},{},,", source_target, weight));
+            assert!(import(&edges).is_err(), "weight {}", weight);
+        }
+        // Shortcut of edges which don't exist yet
+        let edges = with_first_edge(&format!("0,{},1.0,0,{}", source_target, last_row));
+        assert!(import(&edges).is_err());
+        // Missing columns
+        assert!(import("id,source,target\n0,1,2\n").is_err());
+
+        // Ranks start at 1 and can't exceed the number of nodes
+        let nodes = String::from_utf8(sinks[0].clone()).unwrap();
+        let num_nodes = nodes.lines().count() - 1;
+        for rank in [0, num_nodes + 1] {
+            let nodes: Vec<_> = nodes
+                .lines()
+                .enumerate()
+                .map(|(row, line)| {
+                    let mut columns: Vec<_> = line.split(',').map(str::to_string).collect();
+                    if row == 1 {
+                        columns[3] = rank.to_string();
+                    }
+                    columns.join(",")
+                })
+                .collect();
+            let result = OverlayGraph::from_csv_readers(
+                nodes.join("\n").as_bytes(),
+                sinks[1].as_slice(),
+                sinks[2].as_slice(),
+                sinks[3].as_slice(),
+            );
+            assert!(result.is_err(), "rank {}", rank);
+        }
+    }
+
+    #[test]
+    fn csv_without_ranks() {
+        let mut g = generate_complex_graph();
+        let order: Vec<_> = [9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 10]
+            .into_iter()
+            .map(node_index)
+            .collect();
+        let mut overlay_graph = NodeContractor::new(&mut g).run_with_order(&order);
+        overlay_graph.node_order.clear();
+
+        let mut sinks: [Vec<u8>; 4] = Default::default();
+        let [nodes, edges, edges_fwd, edges_bwd] = &mut sinks;
+        overlay_graph
+            .write_csv_into(nodes, edges, edges_fwd, edges_bwd)
+            .unwrap();
+
+        // Older files have no rank, core or level column
+        let nodes_without_column: String = String::from_utf8(sinks[0].clone())
+            .unwrap()
+            .lines()
+            .map(|line| {
+                format!(
+                    "{}\n",
+                    line.splitn(4, ',').take(3).collect::<Vec<_>>().join(",")
+                )
+            })
+            .collect();
+
+        for nodes in [sinks[0].as_slice(), nodes_without_column.as_bytes()] {
+            let imported = OverlayGraph::from_csv_readers(
+                nodes,
+                sinks[1].as_slice(),
+                sinks[2].as_slice(),
+                sinks[3].as_slice(),
+            )
+            .unwrap();
+
+            assert_eq!(11, imported.node_order.len());
+            let report = imported.validate();
+            assert!(report.is_ok(), "{}", report);
+        }
+    }
+
+    #[test]
+    fn csv_node_order_from_levels() {
+        let mut g = generate_complex_graph();
+        let overlay_graph = NodeContractor::new(&mut g).run();
+
+        let levels = overlay_graph.levels();
+        for node in 0..levels.len() {
+            for neighbor in overlay_graph.higher_neighbors(node) {
+                assert!(levels[node] < levels[neighbor.index()]);
+            }
+        }
+
+        let mut sinks: [Vec<u8>; 4] = Default::default();
+        let [nodes, edges, edges_fwd, edges_bwd] = &mut sinks;
+        overlay_graph
+            .write_csv_into(nodes, edges, edges_fwd, edges_bwd)
+            .unwrap();
+
+        // Keep the levels but drop the ranks
+        let nodes_without_ranks: String = String::from_utf8(sinks[0].clone())
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let mut columns: Vec<_> = line.split(',').collect();
+                columns[3] = "";
+                format!("{}\n", columns.join(","))
+            })
+            .collect();
+
+        let imported = OverlayGraph::from_csv_readers(
+            nodes_without_ranks.as_bytes(),
+            sinks[1].as_slice(),
+            sinks[2].as_slice(),
+            sinks[3].as_slice(),
+        )
+        .unwrap();
+
+        assert_eq!(levels, imported.levels());
+        let report = imported.validate();
+        assert!(report.is_ok(), "{}", report);
+    }
+
+    #[test]
+    fn derive_node_order_detects_cycles() {
+        let mut g = generate_simple_graph();
+        let mut overlay_graph = NodeContractor::new(&mut g).run();
+        overlay_graph.node_order = overlay_graph.derive_node_order().unwrap();
+        assert!(overlay_graph.validate().is_ok());
+
+        // Use an upward edge as downward edge as well, so both endpoints rank above each other
+        let edge_idx = *overlay_graph.edges_fwd.iter().flatten().next().unwrap();
+        let target = overlay_graph.edge(edge_idx).target;
+        overlay_graph.edges_bwd[target.index()].push(edge_idx);
+        assert!(overlay_graph.derive_node_order().is_err());
     }
 
     #[test]
@@ -356,12 +1095,15 @@ mod tests {
 
         let overlay_graph = contractor.run_with_order(&node_order);
 
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("simple_graph.bin");
+
         // Encode
-        let _ = overlay_graph.encode("simple_graph.bin");
+        let _ = overlay_graph.encode(&path);
 
         // Decode
         let overlay_graph_imported =
-            OverlayGraph::from_decode("simple_graph.bin").expect("Failed to decode file");
+            OverlayGraph::from_decode(&path).expect("Failed to decode file");
 
         assert_eq!(overlay_graph.edges_bwd, overlay_graph_imported.edges_bwd);
         assert_eq!(overlay_graph.edges_fwd, overlay_graph_imported.edges_fwd);
@@ -371,15 +1113,135 @@ mod tests {
             overlay_graph_imported.g.edges_out
         );
         assert_eq!(overlay_graph.shortcuts, overlay_graph_imported.shortcuts);
+        assert_eq!(overlay_graph.metadata, overlay_graph_imported.metadata);
+        assert!(overlay_graph_imported.metadata.params.is_some());
+    }
+
+    #[test]
+    fn decode_legacy_file() {
+        let mut g = generate_simple_graph();
+        let mut contractor = NodeContractor::new(&mut g);
+        let overlay_graph = contractor.run();
+
+        // Files written before the header was introduced contain only raw bincode
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("simple_graph_legacy.bin");
+        let mut file = std::fs::File::create(&path).unwrap();
+        bincode::serde::encode_into_std_write(
+            &overlay_graph,
+            &mut file,
+            bincode::config::standard(),
+        )
+        .unwrap();
+
+        let overlay_graph_imported = OverlayGraph::from_decode(&path).unwrap();
+
+        assert_eq!(overlay_graph.edges_fwd, overlay_graph_imported.edges_fwd);
+        assert_eq!(overlay_graph.shortcuts, overlay_graph_imported.shortcuts);
+        assert_eq!(overlay_graph_imported.metadata, Default::default());
+        for node in overlay_graph.g.nodes() {
+            assert_eq!(
+                overlay_graph.g.node_by_osm_id(node.id),
+                overlay_graph_imported.g.node_by_osm_id(node.id)
+            );
+            assert!(overlay_graph_imported.g.node_by_osm_id(node.id).is_some());
+        }
+    }
+
+    #[test]
+    fn encode_and_decode_compressed() {
+        let mut g = generate_complex_graph();
+        let mut contractor = NodeContractor::new(&mut g);
+        let overlay_graph = contractor.run();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("complex_graph_compressed.bin");
+
+        for compression in [Compression::Zstd, Compression::Lz4] {
+            overlay_graph
+                .encode_with_compression(&path, compression)
+                .unwrap();
+
+            let overlay_graph_imported = OverlayGraph::from_decode(&path).unwrap();
+
+            assert_eq!(overlay_graph.edges_fwd, overlay_graph_imported.edges_fwd);
+            assert_eq!(overlay_graph.edges_bwd, overlay_graph_imported.edges_bwd);
+            assert_eq!(overlay_graph.g.edges_in, overlay_graph_imported.g.edges_in);
+            assert_eq!(
+                overlay_graph.g.edges_out,
+                overlay_graph_imported.g.edges_out
+            );
+            assert_eq!(overlay_graph.shortcuts, overlay_graph_imported.shortcuts);
+            assert_eq!(overlay_graph.node_order, overlay_graph_imported.node_order);
+        }
+    }
+
+    #[test]
+    fn encode_and_decode_core() {
+        let mut g = generate_complex_graph();
+        let strategy = UpdateStrategy::new().set_core_size(CoreSize::Nodes(4));
+        let mut contractor = NodeContractor::new(&mut g);
+        let overlay_graph = contractor.run_with_strategy(ContractionStrategy::LazyUpdate(strategy));
+        assert_eq!(4, overlay_graph.core_nodes().count());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("complex_graph_core.bin");
+        overlay_graph.encode(&path).unwrap();
+        let overlay_graph_imported = OverlayGraph::from_decode(&path).unwrap();
+
+        assert_eq!(overlay_graph.core, overlay_graph_imported.core);
+        assert_eq!(overlay_graph.edges_fwd, overlay_graph_imported.edges_fwd);
+        assert_eq!(overlay_graph.node_order, overlay_graph_imported.node_order);
+    }
+
+    #[test]
+    fn csv_round_trip_core() {
+        let mut g = generate_complex_graph();
+        let strategy = UpdateStrategy::new().set_core_size(CoreSize::Nodes(4));
+        let mut contractor = NodeContractor::new(&mut g);
+        let overlay_graph = contractor.run_with_strategy(ContractionStrategy::LazyUpdate(strategy));
+
+        let dir = tempfile::tempdir().unwrap();
+        overlay_graph.export_csv_to(dir.path()).unwrap();
+        let overlay_graph_imported = OverlayGraph::from_csv_dir(dir.path()).unwrap();
+
+        assert_eq!(4, overlay_graph_imported.core_nodes().count());
+        assert_eq!(overlay_graph.core, overlay_graph_imported.core);
+        assert_eq!(overlay_graph.node_order, overlay_graph_imported.node_order);
+    }
+
+    #[test]
+    fn decode_unknown_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("not_a_graph.bin");
+        std::fs::write(&path, b"CHOVRLAY").unwrap();
+
+        assert!(OverlayGraph::from_decode(&path).is_err());
     }
 
     #[test]
     fn encode_and_decode_vaterstetten() {
         let overlay_graph = overlay_graph_vaterstetten();
 
-        let _ = overlay_graph.encode("vaterstetten.bin");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vaterstetten.bin");
+
+        let mut file = std::fs::File::create(dir.path().join("vaterstetten_legacy.bin")).unwrap();
+        let legacy_size = bincode::serde::encode_into_std_write(
+            &overlay_graph,
+            &mut file,
+            bincode::config::standard(),
+        )
+        .unwrap();
+
+        let compressed_size = overlay_graph
+            .encode_with_compression(dir.path().join("vaterstetten_zstd.bin"), Compression::Zstd)
+            .unwrap();
+        let size = overlay_graph.encode(&path).unwrap();
+
+        assert!(size < legacy_size);
+        assert!(compressed_size < size);
 
-        let overlay_graph_imported = OverlayGraph::from_decode("vaterstetten.bin").unwrap();
+        let overlay_graph_imported = OverlayGraph::from_decode(&path).unwrap();
 
         assert_eq!(overlay_graph.edges_bwd, overlay_graph_imported.edges_bwd);
         assert_eq!(overlay_graph.edges_fwd, overlay_graph_imported.edges_fwd);
@@ -10,7 +10,7 @@ use crate::{
     statistics::SearchStats,
 };
 
-use super::shortest_path::ShortestPath;
+use super::{shortest_path::ShortestPath, SearchSpace, ShortestPathAlgorithm};
 
 #[derive(Debug)]
 struct Candidate<Idx = DefaultIdx> {
@@ -54,6 +54,7 @@ impl Ord for Candidate {
 pub struct AStar<'a, Idx = DefaultIdx> {
     pub stats: SearchStats,
     pub nodes_settled: FxHashSet<NodeIndex<Idx>>,
+    search_space: Option<SearchSpace>,
     g: &'a Graph<Idx>,
 }
 
@@ -63,6 +64,7 @@ impl<'a> AStar<'a> {
             g,
             stats: SearchStats::default(),
             nodes_settled: FxHashSet::default(),
+            search_space: None,
         }
     }
 
@@ -71,25 +73,52 @@ impl<'a> AStar<'a> {
         source: NodeIndex,
         target: NodeIndex,
         heuristic: impl Fn(&Node, &Node) -> Weight,
+    ) -> Option<ShortestPath> {
+        let g = self.g;
+        let target_node = g.node(target).unwrap();
+        self.search_with_potential(source, target, |node| {
+            heuristic(g.node(node).unwrap(), target_node)
+        })
+    }
+
+    /// Like [`AStar::search`], but the heuristic is a potential, which estimates the distance
+    /// from a node to `target`, e.g. [`Landmarks::potential`]. Nodes with an infinite potential
+    /// can't reach `target` and are skipped.
+    ///
+    /// [`Landmarks::potential`]: crate::search::landmarks::Landmarks::potential
+    pub fn search_with_potential(
+        &mut self,
+        source: NodeIndex,
+        target: NodeIndex,
+        potential: impl Fn(NodeIndex) -> Weight,
     ) -> Option<ShortestPath> {
         info!("BEGIN ASTAR SEARCH from {:?} to {:?}", source, target);
         self.stats.init();
+        self.nodes_settled.clear();
+        if let Some(space) = &mut self.search_space {
+            space.clear();
+        }
         if source == target {
             self.stats.nodes_settled += 1;
+            if let Some(space) = &mut self.search_space {
+                space.settle(source, true);
+            }
             self.stats.finish();
             return Some(ShortestPath::new(vec![source], 0.0));
         }
 
+        if !self.g.may_reach(source, target) {
+            debug!("{:?} can't reach {:?}", source, target);
+            self.stats.finish();
+            return None;
+        }
+
         let mut node_data: FxHashMap<NodeIndex, (Weight, Option<NodeIndex>)> = FxHashMap::default();
         node_data.insert(source, (0.0, None));
 
         let mut queue = BinaryHeap::new();
 
-        queue.push(Candidate::new(
-            source,
-            0.0,
-            heuristic(self.g.node(source).unwrap(), self.g.node(target).unwrap()),
-        ));
+        queue.push(Candidate::new(source, 0.0, potential(source)));
 
         while let Some(Candidate {
             tentative_weight: _,
@@ -97,7 +126,14 @@ impl<'a> AStar<'a> {
             node,
         }) = queue.pop()
         {
+            // Skip outdated entries of nodes which were reached on a shorter path later
+            if real_weight > node_data[&node].0 {
+                continue;
+            }
             self.stats.nodes_settled += 1;
+            if let Some(space) = &mut self.search_space {
+                space.settle(node, true);
+            }
 
             if node == target {
                 break;
@@ -106,6 +142,9 @@ impl<'a> AStar<'a> {
             for (_, edge) in self.g.neighbors_outgoing(node).filter(|(edge_idx, _)| {
                 edge_idx.index() < self.g.edges.len() - self.g.num_shortcuts
             }) {
+                if let Some(space) = &mut self.search_space {
+                    space.relax(node, edge.target, true);
+                }
                 let real_weight = real_weight + edge.weight;
 
                 if real_weight
@@ -114,11 +153,10 @@ impl<'a> AStar<'a> {
                         .unwrap_or(&(std::f64::INFINITY, None))
                         .0
                 {
-                    let tentative_weight = real_weight
-                        + heuristic(
-                            self.g.node(edge.target).unwrap(),
-                            self.g.node(target).unwrap(),
-                        );
+                    let tentative_weight = real_weight + potential(edge.target);
+                    if tentative_weight.is_infinite() {
+                        continue;
+                    }
 
                     node_data.insert(edge.target, (real_weight, Some(node)));
                     queue.push(Candidate::new(edge.target, real_weight, tentative_weight));
@@ -153,13 +191,37 @@ impl<'a> AStar<'a> {
     }
 }
 
+impl ShortestPathAlgorithm for AStar<'_> {
+    fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
+        let g = self.g;
+        AStar::search(self, source, target, |src, dst| g.straight_line(src, dst))
+    }
+
+    fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    fn settled_fwd(&self) -> &FxHashSet<NodeIndex> {
+        &self.nodes_settled
+    }
+
+    fn record_search_space(&mut self, record: bool) {
+        self.search_space = record.then(SearchSpace::default);
+    }
+
+    fn search_space(&self) -> Option<&SearchSpace> {
+        self.search_space.as_ref()
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::search::assert_path;
     use crate::{
         graph::{node_index, Edge},
-        search::assert_no_path,
+        search::{assert_no_path, Dijkstra},
+        util::test_graphs::generate_complex_graph,
     };
-    use crate::{search::assert_path, util::math::straight_line};
 
     use super::*;
 
@@ -204,20 +266,39 @@ mod tests {
         assert_path(
             vec![6, 3],
             20.0,
-            astar.search(6.into(), 3.into(), straight_line),
+            astar.search(6.into(), 3.into(), |src, dst| g.straight_line(src, dst)),
         );
         assert_path(
             vec![4],
             0.0,
-            astar.search(4.into(), 4.into(), straight_line),
+            astar.search(4.into(), 4.into(), |src, dst| g.straight_line(src, dst)),
         );
         assert_path(
             vec![1, 2, 3, 4],
             22.0,
-            astar.search(1.into(), 4.into(), straight_line),
+            astar.search(1.into(), 4.into(), |src, dst| g.straight_line(src, dst)),
         );
     }
 
+    #[test]
+    fn same_as_dijkstra_without_metric() {
+        // The weights are no travel times, the straight line estimate must not be used
+        let g = generate_complex_graph();
+        assert!(g.metric.is_none());
+        let mut d = Dijkstra::new(&g);
+        let mut astar = AStar::new(&g);
+
+        for s in 0..g.nodes.len() {
+            for t in 0..g.nodes.len() {
+                let (s, t) = (node_index(s), node_index(t));
+                assert_eq!(
+                    d.search(s, t).map(|sp| sp.weight),
+                    ShortestPathAlgorithm::search(&mut astar, s, t).map(|sp| sp.weight)
+                );
+            }
+        }
+    }
+
     #[test]
     fn disconnected_graph() {
         // 0 -> 1 -> 2
@@ -0,0 +1,458 @@
+//! Implementation of the bidirectional A* search algorithm.
+//!
+//! Both searches use the average potentials `p_f(v) = (π_t(v) - π_s(v)) / 2` and
+//! `p_b(v) = -p_f(v)`, where `π_t` estimates the distance from `v` to the target and `π_s`
+//! the distance from the source to `v`. Both are consistent, so every node is settled at most
+//! once per direction and the search can stop as soon as the sum of the smallest keys reaches
+//! the best path found so far.
+//!
+//! The estimates are either the straight line distance of the graph's [`Metric`] or the lower
+//! bounds of precomputed [`Landmarks`].
+//!
+//! [`Metric`]: crate::util::math::Metric
+use std::collections::BinaryHeap;
+
+use log::{debug, info};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::constants::Weight;
+use crate::graph::*;
+use crate::search::dijkstra::Candidate;
+use crate::search::landmarks::Landmarks;
+use crate::search::shortest_path::ShortestPath;
+use crate::search::{SearchSpace, ShortestPathAlgorithm};
+use crate::statistics::SearchStats;
+
+type NodeData = FxHashMap<NodeIndex, (Weight, Option<NodeIndex>)>;
+
+pub struct BidirAStar<'a, Idx = DefaultIdx> {
+    pub stats: SearchStats,
+    pub settled_fwd: FxHashSet<NodeIndex<Idx>>,
+    pub settled_bwd: FxHashSet<NodeIndex<Idx>>,
+    pub data_fwd: NodeData,
+    pub data_bwd: NodeData,
+    pub best_weight: Weight,
+    pub intersect_node: Option<NodeIndex<Idx>>,
+    search_space: Option<SearchSpace>,
+    g: &'a Graph<Idx>,
+    estimate: Estimate<'a>,
+}
+
+/// Source of the distance estimates the potentials are built from
+#[derive(Clone, Copy)]
+enum Estimate<'a> {
+    StraightLine,
+    Landmarks(&'a Landmarks),
+}
+
+impl<'a> BidirAStar<'a> {
+    /// Bidirectional A* guided by the straight line distance between nodes.
+    pub fn new(graph: &'a Graph) -> Self {
+        Self::with_estimate(graph, Estimate::StraightLine)
+    }
+
+    /// Bidirectional A* guided by the lower bounds of `landmarks` (ALT).
+    pub fn with_landmarks(graph: &'a Graph, landmarks: &'a Landmarks) -> Self {
+        Self::with_estimate(graph, Estimate::Landmarks(landmarks))
+    }
+
+    fn with_estimate(graph: &'a Graph, estimate: Estimate<'a>) -> Self {
+        BidirAStar {
+            g: graph,
+            estimate,
+            settled_fwd: FxHashSet::default(),
+            settled_bwd: FxHashSet::default(),
+            data_fwd: FxHashMap::default(),
+            data_bwd: FxHashMap::default(),
+            stats: SearchStats::default(),
+            best_weight: Weight::INFINITY,
+            intersect_node: None,
+            search_space: None,
+        }
+    }
+
+    pub fn init(&mut self) {
+        self.settled_fwd.clear();
+        self.settled_bwd.clear();
+        self.data_fwd.clear();
+        self.data_bwd.clear();
+        self.best_weight = Weight::INFINITY;
+        self.intersect_node = None;
+        if let Some(space) = &mut self.search_space {
+            space.clear();
+        }
+        self.stats.init();
+    }
+
+    fn get_weight_fwd(&self, node: NodeIndex) -> Weight {
+        self.data_fwd.get(&node).map_or(Weight::INFINITY, |d| d.0)
+    }
+
+    fn get_weight_bwd(&self, node: NodeIndex) -> Weight {
+        self.data_bwd.get(&node).map_or(Weight::INFINITY, |d| d.0)
+    }
+
+    /// Forward potential `p_f` of `node`. Infinite if `node` can't be on a path from `source`
+    /// to `target`.
+    fn potential_fwd(&self, node: NodeIndex, source: NodeIndex, target: NodeIndex) -> Weight {
+        let to_target = self.estimate(node, target);
+        let from_source = self.estimate(source, node);
+        if to_target.is_infinite() || from_source.is_infinite() {
+            return Weight::INFINITY;
+        }
+        (to_target - from_source) / 2.0
+    }
+
+    fn estimate(&self, from: NodeIndex, to: NodeIndex) -> Weight {
+        match self.estimate {
+            Estimate::StraightLine => self
+                .g
+                .straight_line(self.g.node(from).unwrap(), self.g.node(to).unwrap()),
+            Estimate::Landmarks(landmarks) => landmarks.lower_bound(from, to),
+        }
+    }
+
+    pub fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
+        self.init();
+
+        info!(
+            "BEGIN bidir. ASTAR SEARCH from {:?} to {:?}",
+            source, target
+        );
+
+        if source == target {
+            self.stats.nodes_settled += 1;
+            self.intersect_node = Some(source);
+            if let Some(space) = &mut self.search_space {
+                space.settle(source, true);
+                space.meeting_node = Some(source);
+            }
+            self.stats.finish();
+            return Some(ShortestPath::new(vec![source], 0.0));
+        }
+
+        if !self.g.may_reach(source, target) {
+            debug!("{:?} can't reach {:?}", source, target);
+            self.stats.finish();
+            return None;
+        }
+
+        let num_edges = self.g.edges.len() - self.g.num_shortcuts;
+
+        self.data_fwd.insert(source, (0.0, None));
+        self.data_bwd.insert(target, (0.0, None));
+
+        // The keys are `d_f(v) + p_f(v)` and `d_b(v) - p_f(v)`
+        let mut queue_fwd = BinaryHeap::new();
+        let mut queue_bwd = BinaryHeap::new();
+        queue_fwd.push(Candidate::new(
+            source,
+            self.potential_fwd(source, source, target),
+        ));
+        queue_bwd.push(Candidate::new(
+            target,
+            -self.potential_fwd(target, source, target),
+        ));
+
+        loop {
+            let min_fwd = queue_fwd.peek().map_or(Weight::INFINITY, |c| c.weight);
+            let min_bwd = queue_bwd.peek().map_or(Weight::INFINITY, |c| c.weight);
+
+            // With average potentials the sum of both keys is a lower bound for every path
+            // through unsettled nodes
+            if min_fwd.is_infinite()
+                || min_bwd.is_infinite()
+                || min_fwd + min_bwd >= self.best_weight
+            {
+                break;
+            }
+
+            if min_fwd <= min_bwd {
+                let node = queue_fwd.pop().unwrap().node_idx;
+                if !self.settled_fwd.insert(node) {
+                    continue;
+                }
+                self.stats.nodes_settled += 1;
+                if let Some(space) = &mut self.search_space {
+                    space.settle(node, true);
+                }
+                let weight = self.get_weight_fwd(node);
+
+                for (_, edge) in self
+                    .g
+                    .neighbors_outgoing(node)
+                    .filter(|(edge_idx, _)| edge_idx.index() < num_edges)
+                {
+                    if let Some(space) = &mut self.search_space {
+                        space.relax(node, edge.target, true);
+                    }
+                    let new_distance = weight + edge.weight;
+                    if new_distance >= self.get_weight_fwd(edge.target) {
+                        continue;
+                    }
+                    let potential = self.potential_fwd(edge.target, source, target);
+                    if potential.is_infinite() {
+                        continue;
+                    }
+
+                    self.data_fwd
+                        .insert(edge.target, (new_distance, Some(node)));
+                    queue_fwd.push(Candidate::new(edge.target, new_distance + potential));
+
+                    let total = new_distance + self.get_weight_bwd(edge.target);
+                    if total < self.best_weight {
+                        debug!("FWD: new best_weight: {}", total);
+                        self.best_weight = total;
+                        self.intersect_node = Some(edge.target);
+                    }
+                }
+            } else {
+                let node = queue_bwd.pop().unwrap().node_idx;
+                if !self.settled_bwd.insert(node) {
+                    continue;
+                }
+                self.stats.nodes_settled += 1;
+                if let Some(space) = &mut self.search_space {
+                    space.settle(node, false);
+                }
+                let weight = self.get_weight_bwd(node);
+
+                for (_, edge) in self
+                    .g
+                    .neighbors_incoming(node)
+                    .filter(|(edge_idx, _)| edge_idx.index() < num_edges)
+                {
+                    if let Some(space) = &mut self.search_space {
+                        space.relax(edge.source, node, false);
+                    }
+                    let new_distance = weight + edge.weight;
+                    if new_distance >= self.get_weight_bwd(edge.source) {
+                        continue;
+                    }
+                    let potential = self.potential_fwd(edge.source, source, target);
+                    if potential.is_infinite() {
+                        continue;
+                    }
+
+                    self.data_bwd
+                        .insert(edge.source, (new_distance, Some(node)));
+                    queue_bwd.push(Candidate::new(edge.source, new_distance - potential));
+
+                    let total = new_distance + self.get_weight_fwd(edge.source);
+                    if total < self.best_weight {
+                        debug!("BWD: new best_weight: {}", total);
+                        self.best_weight = total;
+                        self.intersect_node = Some(edge.source);
+                    }
+                }
+            }
+        }
+
+        self.stats.finish();
+        if let Some(space) = &mut self.search_space {
+            space.meeting_node = self.intersect_node;
+        }
+
+        if let Some(intersect_node) = self.intersect_node {
+            info!("{}, weight: {}", self.stats, self.best_weight);
+            Some(self.reconstruct_shortest_path(intersect_node))
+        } else {
+            info!("No path found");
+            None
+        }
+    }
+
+    /// Joins both halves at `intersect_node`. The weight is summed up from the source like
+    /// in [`Dijkstra`](crate::search::Dijkstra), so both return exactly the same result.
+    fn reconstruct_shortest_path(&self, intersect_node: NodeIndex) -> ShortestPath {
+        let mut path = vec![intersect_node];
+        let mut weight = self.get_weight_fwd(intersect_node);
+
+        let mut node = intersect_node;
+        while let Some(Some(prev_node)) = self.data_fwd.get(&node).map(|d| d.1) {
+            path.push(prev_node);
+            node = prev_node;
+        }
+        path.reverse();
+
+        let mut node = intersect_node;
+        while let Some(Some(next_node)) = self.data_bwd.get(&node).map(|d| d.1) {
+            weight += self.edge_weight(node, next_node);
+            path.push(next_node);
+            node = next_node;
+        }
+
+        debug!("Path: {:?}", &path);
+        ShortestPath::new(path, weight)
+    }
+
+    /// Weight of the lightest original edge from `source` to `target`
+    fn edge_weight(&self, source: NodeIndex, target: NodeIndex) -> Weight {
+        let num_edges = self.g.edges.len() - self.g.num_shortcuts;
+        self.g
+            .neighbors_outgoing(source)
+            .filter(|(edge_idx, edge)| edge_idx.index() < num_edges && edge.target == target)
+            .map(|(_, edge)| edge.weight)
+            .fold(Weight::INFINITY, Weight::min)
+    }
+}
+
+impl ShortestPathAlgorithm for BidirAStar<'_> {
+    fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
+        BidirAStar::search(self, source, target)
+    }
+
+    fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    fn settled_fwd(&self) -> &FxHashSet<NodeIndex> {
+        &self.settled_fwd
+    }
+
+    fn settled_bwd(&self) -> Option<&FxHashSet<NodeIndex>> {
+        Some(&self.settled_bwd)
+    }
+
+    fn meeting_node(&self) -> Option<NodeIndex> {
+        self.intersect_node
+    }
+
+    fn record_search_space(&mut self, record: bool) {
+        self.search_space = record.then(SearchSpace::default);
+    }
+
+    fn search_space(&self) -> Option<&SearchSpace> {
+        self.search_space.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::landmarks::LandmarkSelection;
+    use crate::search::{assert_no_path, assert_path, Dijkstra};
+    use crate::util::test_graphs::{generate_complex_graph, graph_vaterstetten};
+
+    use super::*;
+
+    fn simple_graph() -> Graph {
+        //      7 -> 8 -> 9
+        //      |         |
+        // 0 -> 5 -> 6 -  |
+        // |         |  \ |
+        // 1 -> 2 -> 3 -> 4
+        let mut g = Graph::new();
+
+        for i in 0..10 {
+            g.add_node(Node::new(i, 0.0, 0.0));
+        }
+
+        g.add_edge(Edge::new(node_index(0), node_index(1), 1.0));
+        g.add_edge(Edge::new(node_index(1), node_index(2), 1.0));
+        g.add_edge(Edge::new(node_index(2), node_index(3), 1.0));
+        g.add_edge(Edge::new(node_index(3), node_index(4), 20.0));
+        g.add_edge(Edge::new(node_index(0), node_index(5), 5.0));
+        g.add_edge(Edge::new(node_index(5), node_index(6), 1.0));
+        g.add_edge(Edge::new(node_index(6), node_index(4), 20.0));
+        g.add_edge(Edge::new(node_index(6), node_index(3), 20.0));
+        g.add_edge(Edge::new(node_index(5), node_index(7), 5.0));
+        g.add_edge(Edge::new(node_index(7), node_index(8), 1.0));
+        g.add_edge(Edge::new(node_index(8), node_index(9), 1.0));
+        g.add_edge(Edge::new(node_index(9), node_index(4), 1.0));
+
+        g
+    }
+
+    fn assert_simple_paths(b: &mut BidirAStar) {
+        assert_no_path(b.search(4.into(), 0.into())); // Cannot be reached
+        assert_path(vec![0, 5, 7, 8, 9, 4], 13.0, b.search(0.into(), 4.into()));
+        assert_path(vec![6, 3], 20.0, b.search(6.into(), 3.into()));
+        assert_path(vec![4], 0.0, b.search(4.into(), 4.into()));
+        assert_path(vec![1, 2, 3, 4], 22.0, b.search(1.into(), 4.into()));
+    }
+
+    #[test]
+    fn simple_path() {
+        let g = simple_graph();
+        assert_simple_paths(&mut BidirAStar::new(&g));
+    }
+
+    #[test]
+    fn simple_path_with_landmarks() {
+        let g = simple_graph();
+        let landmarks = Landmarks::new(&g, 3, LandmarkSelection::Avoid);
+        assert_simple_paths(&mut BidirAStar::with_landmarks(&g, &landmarks));
+    }
+
+    #[test]
+    fn stats_count_both_directions() {
+        let g = simple_graph();
+        let mut b = BidirAStar::new(&g);
+
+        b.search(0.into(), 4.into());
+        assert_eq!(
+            b.settled_fwd.len() + b.settled_bwd.len(),
+            b.stats.nodes_settled
+        );
+        assert!(b.stats.duration.is_some());
+    }
+
+    fn assert_same_as_dijkstra(g: &Graph, b: &mut BidirAStar, s: usize, t: usize) {
+        let (s, t) = (node_index(s), node_index(t));
+        let mut d = Dijkstra::new(g);
+
+        match (d.search(s, t), b.search(s, t)) {
+            (Some(expected), Some(sp)) => {
+                assert_eq!(expected.weight, sp.weight);
+                // Paths may only differ if there are several shortest paths
+                let weight = sp.nodes.windows(2).fold(0.0, |weight, nodes| {
+                    weight + b.edge_weight(nodes[0], nodes[1])
+                });
+                assert_eq!(expected.weight, weight);
+                assert_eq!((Some(&s), Some(&t)), (sp.nodes.first(), sp.nodes.last()));
+            }
+            (expected, sp) => assert_eq!(expected, sp),
+        }
+    }
+
+    #[test]
+    fn same_as_dijkstra_on_complex_graph() {
+        let g = generate_complex_graph();
+
+        // Without metric the straight line estimate is zero
+        let mut b = BidirAStar::new(&g);
+        for s in 0..g.nodes.len() {
+            for t in 0..g.nodes.len() {
+                assert_same_as_dijkstra(&g, &mut b, s, t);
+            }
+        }
+
+        for selection in [LandmarkSelection::Farthest, LandmarkSelection::Avoid] {
+            let landmarks = Landmarks::new(&g, 3, selection);
+            let mut b = BidirAStar::with_landmarks(&g, &landmarks);
+
+            for s in 0..g.nodes.len() {
+                for t in 0..g.nodes.len() {
+                    assert_same_as_dijkstra(&g, &mut b, s, t);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_as_dijkstra_on_vaterstetten() {
+        let g = graph_vaterstetten();
+        let num_nodes = g.nodes.len();
+        let landmarks = Landmarks::new(&g, 8, LandmarkSelection::Avoid);
+
+        let mut runner = proptest::test_runner::TestRunner::default();
+
+        runner
+            .run(&(0..num_nodes, 0..num_nodes), |(s, t)| {
+                assert_same_as_dijkstra(&g, &mut BidirAStar::new(&g), s, t);
+                assert_same_as_dijkstra(&g, &mut BidirAStar::with_landmarks(&g, &landmarks), s, t);
+                Ok(())
+            })
+            .unwrap();
+    }
+}
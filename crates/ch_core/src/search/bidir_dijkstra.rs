@@ -4,6 +4,7 @@ use std::collections::BinaryHeap;
 use crate::constants::Weight;
 use crate::graph::*;
 use crate::search::shortest_path::ShortestPath;
+use crate::search::{SearchSpace, ShortestPathAlgorithm};
 use crate::statistics::SearchStats;
 use log::{debug, info};
 use rustc_hash::{FxHashMap, FxHashSet};
@@ -51,6 +52,7 @@ pub struct BidirDijkstra<'a, Idx = DefaultIdx> {
     pub data_bwd: NodeData,
     pub best_weight: Weight,
     pub intersect_node: Option<NodeIndex<Idx>>,
+    search_space: Option<SearchSpace>,
     g: &'a Graph<Idx>,
 }
 
@@ -67,6 +69,7 @@ impl<'a> BidirDijkstra<'a> {
             stats: SearchStats::default(),
             best_weight: Weight::MAX,
             intersect_node: None,
+            search_space: None,
         }
     }
 
@@ -77,6 +80,9 @@ impl<'a> BidirDijkstra<'a> {
         self.data_bwd.clear();
         self.best_weight = Weight::MAX;
         self.intersect_node = None;
+        if let Some(space) = &mut self.search_space {
+            space.clear();
+        }
         self.stats.init();
     }
 
@@ -140,10 +146,21 @@ impl<'a> BidirDijkstra<'a> {
 
         if source == target {
             self.stats.nodes_settled += 1;
+            self.intersect_node = Some(source);
+            if let Some(space) = &mut self.search_space {
+                space.settle(source, true);
+                space.meeting_node = Some(source);
+            }
             self.stats.finish();
             return Some(ShortestPath::new(vec![source], 0.0));
         }
 
+        if !self.g.may_reach(source, target) {
+            debug!("{:?} can't reach {:?}", source, target);
+            self.stats.finish();
+            return None;
+        }
+
         self.data_fwd.insert(source, (0.0, None));
         self.data_bwd.insert(target, (0.0, None));
 
@@ -159,6 +176,10 @@ impl<'a> BidirDijkstra<'a> {
 
             self.settled_fwd.insert(u.node_idx);
             self.settled_bwd.insert(v.node_idx);
+            if let Some(space) = &mut self.search_space {
+                space.settle(u.node_idx, true);
+                space.settle(v.node_idx, false);
+            }
 
             // Forward search
             for (_, edge) in self
@@ -168,6 +189,9 @@ impl<'a> BidirDijkstra<'a> {
                     edge_idx.index() < self.g.edges.len() - self.g.num_shortcuts
                 })
             {
+                if let Some(space) = &mut self.search_space {
+                    space.relax(u.node_idx, edge.target, true);
+                }
                 let new_distance = u.weight + edge.weight;
                 if !self.settled_fwd.contains(&edge.target)
                     && new_distance < self.get_weight_fwd(edge.target)
@@ -195,6 +219,9 @@ impl<'a> BidirDijkstra<'a> {
                     edge_idx.index() < self.g.edges.len() - self.g.num_shortcuts
                 })
             {
+                if let Some(space) = &mut self.search_space {
+                    space.relax(edge.source, v.node_idx, false);
+                }
                 let new_distance = v.weight + edge.weight;
                 if !self.settled_bwd.contains(&edge.source)
                     && new_distance < self.get_weight_bwd(edge.source)
@@ -223,6 +250,9 @@ impl<'a> BidirDijkstra<'a> {
         }
 
         self.stats.finish();
+        if let Some(space) = &mut self.search_space {
+            space.meeting_node = self.intersect_node;
+        }
 
         info!("Intersect node: {:?}", self.intersect_node);
         info!("Weight: {}", self.best_weight);
@@ -237,6 +267,36 @@ impl<'a> BidirDijkstra<'a> {
     }
 }
 
+impl ShortestPathAlgorithm for BidirDijkstra<'_> {
+    fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
+        BidirDijkstra::search(self, source, target)
+    }
+
+    fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    fn settled_fwd(&self) -> &FxHashSet<NodeIndex> {
+        &self.settled_fwd
+    }
+
+    fn settled_bwd(&self) -> Option<&FxHashSet<NodeIndex>> {
+        Some(&self.settled_bwd)
+    }
+
+    fn meeting_node(&self) -> Option<NodeIndex> {
+        self.intersect_node
+    }
+
+    fn record_search_space(&mut self, record: bool) {
+        self.search_space = record.then(SearchSpace::default);
+    }
+
+    fn search_space(&self) -> Option<&SearchSpace> {
+        self.search_space.as_ref()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::search::{assert_no_path, assert_path};
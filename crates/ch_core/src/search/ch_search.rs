@@ -33,7 +33,7 @@ use crate::{
     statistics::SearchStats,
 };
 
-use super::{dijkstra::Candidate, shortest_path::ShortestPath};
+use super::{dijkstra::Candidate, shortest_path::ShortestPath, SearchSpace, ShortestPathAlgorithm};
 
 pub type NodeData = FxHashMap<NodeIndex, (Weight, Option<EdgeIndex>)>;
 
@@ -51,6 +51,8 @@ pub struct CHSearch<'a, Idx = DefaultIdx> {
     best_weight: Weight,
 
     pub nodes_stalled: usize,
+
+    search_space: Option<SearchSpace>,
 }
 
 impl<'a> CHSearch<'a> {
@@ -65,6 +67,7 @@ impl<'a> CHSearch<'a> {
             data_bwd: FxHashMap::default(),
             intersect_node: None,
             best_weight: Weight::MAX,
+            search_space: None,
         }
     }
 
@@ -77,6 +80,9 @@ impl<'a> CHSearch<'a> {
         self.best_weight = Weight::MAX;
         self.stats.init();
         self.nodes_stalled = 0;
+        if let Some(space) = &mut self.search_space {
+            space.clear();
+        }
     }
 
     pub fn search_without_stalling(
@@ -89,6 +95,9 @@ impl<'a> CHSearch<'a> {
 
     /// Finds the shortest path from `source` to `target`.
     /// The search is performed using a modified bidirectional version of Dijkstras algorithm
+    ///
+    /// Paths through an uncontracted core are not found, use [`CoreALT`](super::CoreALT) for
+    /// partially contracted graphs.
     pub fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
         self.search_internal(source, target, true)
     }
@@ -107,6 +116,12 @@ impl<'a> CHSearch<'a> {
         );
         self.init();
 
+        if !self.g.road_graph().may_reach(source, target) {
+            debug!("{:?} can't reach {:?}", source, target);
+            self.stats.finish();
+            return None;
+        }
+
         let mut queue_fwd = BinaryHeap::new();
         let mut queue_bwd = BinaryHeap::new();
 
@@ -129,6 +144,9 @@ impl<'a> CHSearch<'a> {
         );
 
         self.stats.finish();
+        if let Some(space) = &mut self.search_space {
+            space.meeting_node = self.intersect_node;
+        }
 
         self.reconstruct_shortest_path(self.intersect_node, source)
     }
@@ -155,6 +173,9 @@ impl<'a> CHSearch<'a> {
             }
 
             for (edge_idx, edge) in self.g.edges_bwd(curr.node_idx) {
+                if let Some(space) = &mut self.search_space {
+                    space.relax(edge.source, curr.node_idx, false);
+                }
                 let new_distance = curr.weight + edge.weight;
                 if new_distance < self.get_weight_bwd(edge.source) {
                     self.data_bwd
@@ -164,6 +185,9 @@ impl<'a> CHSearch<'a> {
             }
             self.stats.nodes_settled += 1;
             self.settled_bwd.insert(curr.node_idx);
+            if let Some(space) = &mut self.search_space {
+                space.settle(curr.node_idx, false);
+            }
 
             if self.settled_fwd.contains(&curr.node_idx)
                 && curr.weight + self.get_weight_fwd(curr.node_idx) < self.best_weight
@@ -197,6 +221,9 @@ impl<'a> CHSearch<'a> {
             }
 
             for (edge_idx, edge) in self.g.edges_fwd(curr.node_idx) {
+                if let Some(space) = &mut self.search_space {
+                    space.relax(curr.node_idx, edge.target, true);
+                }
                 let new_weight = curr.weight + edge.weight;
                 if new_weight < self.get_weight_fwd(edge.target) {
                     self.data_fwd
@@ -206,6 +233,9 @@ impl<'a> CHSearch<'a> {
             }
             self.stats.nodes_settled += 1;
             self.settled_fwd.insert(curr.node_idx);
+            if let Some(space) = &mut self.search_space {
+                space.settle(curr.node_idx, true);
+            }
 
             if self.settled_bwd.contains(&curr.node_idx)
                 && curr.weight + self.get_weight_bwd(curr.node_idx) < self.best_weight
@@ -535,6 +565,36 @@ impl<'a> CHSearch<'a> {
     }
 }
 
+impl ShortestPathAlgorithm for CHSearch<'_> {
+    fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
+        CHSearch::search(self, source, target)
+    }
+
+    fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    fn settled_fwd(&self) -> &FxHashSet<NodeIndex> {
+        &self.settled_fwd
+    }
+
+    fn settled_bwd(&self) -> Option<&FxHashSet<NodeIndex>> {
+        Some(&self.settled_bwd)
+    }
+
+    fn meeting_node(&self) -> Option<NodeIndex> {
+        self.intersect_node
+    }
+
+    fn record_search_space(&mut self, record: bool) {
+        self.search_space = record.then(SearchSpace::default);
+    }
+
+    fn search_space(&self) -> Option<&SearchSpace> {
+        self.search_space.as_ref()
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -544,10 +604,7 @@ mod tests {
         graph::node_index,
         node_contraction::NodeContractor,
         search::{assert_path, astar::AStar},
-        util::{
-            math::straight_line,
-            test_graphs::{generate_complex_graph, generate_simple_graph, graph_vaterstetten},
-        },
+        util::test_graphs::{generate_complex_graph, generate_simple_graph, graph_vaterstetten},
     };
 
     use super::*;
@@ -644,7 +701,7 @@ mod tests {
         info!("{:?}", sp);
 
         let mut astar = AStar::new(&g);
-        let sp = astar.search(a, b, straight_line);
+        let sp = astar.search(a, b, |src, dst| g.straight_line(src, dst));
         info!("{:?}", sp);
 
         let mut contractor = NodeContractor::new(&mut g);
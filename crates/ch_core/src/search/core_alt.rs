@@ -0,0 +1,468 @@
+//! Core-ALT search on a partially contracted [`OverlayGraph`].
+//!
+//! The query runs in two phases:
+//! 1. Upward CH searches from the source and to the target. Both stop at core nodes, which are
+//!    never contracted and therefore have no upward edges.
+//! 2. A bidirectional ALT search inside the core, started from all core nodes reached in the
+//!    first phase. The landmarks are computed on the road graph; since the contraction keeps
+//!    the distances between core nodes, their lower bounds hold inside the core as well.
+//!
+//! # Examples
+//! ```
+//! use ch_core::prelude::*;
+//! use ch_core::search::core_alt::CoreALT;
+//! use ch_core::search::landmarks::{LandmarkSelection, Landmarks};
+//!
+//! let mut g = generate_simple_graph();
+//!
+//! // Contract all but two nodes
+//! let strategy = UpdateStrategy::new().set_core_size(CoreSize::Nodes(2));
+//! let mut contractor = NodeContractor::new(&mut g);
+//! let overlay_graph = contractor.run_with_strategy(ContractionStrategy::LazyUpdate(strategy));
+//!
+//! let landmarks = Landmarks::new(overlay_graph.road_graph(), 2, LandmarkSelection::Avoid);
+//! let mut core_alt = CoreALT::new(&overlay_graph, &landmarks);
+//! let sp = core_alt.search(node_index(4), node_index(1)).unwrap();
+//! assert_eq!(sp.weight, 3.0);
+//! ```
+//! [`OverlayGraph`]: crate::overlay_graph::OverlayGraph
+use std::collections::BinaryHeap;
+
+use log::{debug, info};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    constants::Weight,
+    graph::{DefaultIdx, NodeIndex},
+    overlay_graph::OverlayGraph,
+    statistics::SearchStats,
+};
+
+use super::{
+    ch_search::NodeData, dijkstra::Candidate, landmarks::Landmarks, shortest_path::ShortestPath,
+    SearchSpace, ShortestPathAlgorithm,
+};
+
+pub struct CoreALT<'a, Idx = DefaultIdx> {
+    pub stats: SearchStats,
+    g: &'a OverlayGraph<Idx>,
+    landmarks: &'a Landmarks,
+
+    pub settled_fwd: FxHashSet<NodeIndex<Idx>>,
+    pub settled_bwd: FxHashSet<NodeIndex<Idx>>,
+
+    pub data_fwd: NodeData,
+    pub data_bwd: NodeData,
+
+    intersect_node: Option<NodeIndex<Idx>>,
+    best_weight: Weight,
+
+    search_space: Option<SearchSpace>,
+}
+
+impl<'a> CoreALT<'a> {
+    pub fn new(graph: &'a OverlayGraph, landmarks: &'a Landmarks) -> Self {
+        CoreALT {
+            g: graph,
+            landmarks,
+            stats: SearchStats::default(),
+            settled_fwd: FxHashSet::default(),
+            settled_bwd: FxHashSet::default(),
+            data_fwd: FxHashMap::default(),
+            data_bwd: FxHashMap::default(),
+            intersect_node: None,
+            best_weight: Weight::INFINITY,
+            search_space: None,
+        }
+    }
+
+    fn init(&mut self) {
+        self.settled_fwd.clear();
+        self.settled_bwd.clear();
+        self.data_fwd.clear();
+        self.data_bwd.clear();
+        self.intersect_node = None;
+        self.best_weight = Weight::INFINITY;
+        self.stats.init();
+        if let Some(space) = &mut self.search_space {
+            space.clear();
+        }
+    }
+
+    /// Finds the shortest path from `source` to `target`.
+    pub fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
+        info!("BEGIN CORE-ALT SEARCH from {:?} to {:?}", source, target);
+        self.init();
+
+        if !self.g.road_graph().may_reach(source, target) {
+            debug!("{:?} can't reach {:?}", source, target);
+            self.stats.finish();
+            return None;
+        }
+
+        // Phase 1: Upward searches until the core is reached
+        self.search_upward_fwd(source);
+        self.search_upward_bwd(target);
+
+        for (node, (weight_fwd, _)) in &self.data_fwd {
+            let weight = weight_fwd + self.get_weight_bwd(*node);
+            if weight < self.best_weight {
+                self.best_weight = weight;
+                self.intersect_node = Some(*node);
+            }
+        }
+        debug!("Best weight outside of the core: {}", self.best_weight);
+
+        // Phase 2: Bidirectional ALT search inside the core
+        if self.g.has_core() {
+            self.search_core(source, target);
+        }
+
+        self.stats.finish();
+        if let Some(space) = &mut self.search_space {
+            space.meeting_node = self.intersect_node;
+        }
+
+        self.reconstruct_shortest_path(source)
+    }
+
+    fn search_upward_fwd(&mut self, source: NodeIndex) {
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate::new(source, 0.0));
+        self.data_fwd.insert(source, (0.0, None));
+
+        while let Some(Candidate { node_idx, weight }) = queue.pop() {
+            // Core nodes are settled in the second phase
+            if self.g.is_core(node_idx) || !self.settled_fwd.insert(node_idx) {
+                continue;
+            }
+            self.stats.nodes_settled += 1;
+            if let Some(space) = &mut self.search_space {
+                space.settle(node_idx, true);
+            }
+
+            for (edge_idx, edge) in self.g.edges_fwd(node_idx) {
+                if let Some(space) = &mut self.search_space {
+                    space.relax(node_idx, edge.target, true);
+                }
+                let new_weight = weight + edge.weight;
+                if new_weight < self.get_weight_fwd(edge.target) {
+                    self.data_fwd
+                        .insert(edge.target, (new_weight, Some(edge_idx)));
+                    queue.push(Candidate::new(edge.target, new_weight));
+                }
+            }
+        }
+    }
+
+    fn search_upward_bwd(&mut self, target: NodeIndex) {
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate::new(target, 0.0));
+        self.data_bwd.insert(target, (0.0, None));
+
+        while let Some(Candidate { node_idx, weight }) = queue.pop() {
+            if self.g.is_core(node_idx) || !self.settled_bwd.insert(node_idx) {
+                continue;
+            }
+            self.stats.nodes_settled += 1;
+            if let Some(space) = &mut self.search_space {
+                space.settle(node_idx, false);
+            }
+
+            for (edge_idx, edge) in self.g.edges_bwd(node_idx) {
+                if let Some(space) = &mut self.search_space {
+                    space.relax(edge.source, node_idx, false);
+                }
+                let new_weight = weight + edge.weight;
+                if new_weight < self.get_weight_bwd(edge.source) {
+                    self.data_bwd
+                        .insert(edge.source, (new_weight, Some(edge_idx)));
+                    queue.push(Candidate::new(edge.source, new_weight));
+                }
+            }
+        }
+    }
+
+    /// Forward potential `p_f`, see [`BidirAStar`](crate::search::BidirAStar). Infinite if
+    /// `node` can't be on a path from `source` to `target`.
+    fn potential_fwd(&self, node: NodeIndex, source: NodeIndex, target: NodeIndex) -> Weight {
+        let to_target = self.landmarks.lower_bound(node, target);
+        let from_source = self.landmarks.lower_bound(source, node);
+        if to_target.is_infinite() || from_source.is_infinite() {
+            return Weight::INFINITY;
+        }
+        (to_target - from_source) / 2.0
+    }
+
+    fn search_core(&mut self, source: NodeIndex, target: NodeIndex) {
+        // The core nodes reached by the upward searches are the starting points
+        let mut queue_fwd = BinaryHeap::new();
+        for (node, (weight, _)) in self.data_fwd.iter().filter(|(n, _)| self.g.is_core(**n)) {
+            let potential = self.potential_fwd(*node, source, target);
+            if potential.is_finite() {
+                queue_fwd.push(Candidate::new(*node, weight + potential));
+            }
+        }
+        let mut queue_bwd = BinaryHeap::new();
+        for (node, (weight, _)) in self.data_bwd.iter().filter(|(n, _)| self.g.is_core(**n)) {
+            let potential = self.potential_fwd(*node, source, target);
+            if potential.is_finite() {
+                queue_bwd.push(Candidate::new(*node, weight - potential));
+            }
+        }
+
+        loop {
+            let min_fwd = queue_fwd.peek().map_or(Weight::INFINITY, |c| c.weight);
+            let min_bwd = queue_bwd.peek().map_or(Weight::INFINITY, |c| c.weight);
+
+            if min_fwd.is_infinite()
+                || min_bwd.is_infinite()
+                || min_fwd + min_bwd >= self.best_weight
+            {
+                break;
+            }
+
+            if min_fwd <= min_bwd {
+                let node = queue_fwd.pop().unwrap().node_idx;
+                if !self.settled_fwd.insert(node) {
+                    continue;
+                }
+                self.stats.nodes_settled += 1;
+                if let Some(space) = &mut self.search_space {
+                    space.settle(node, true);
+                }
+                let weight = self.get_weight_fwd(node);
+
+                for (edge_idx, edge) in self.g.core_edges_out(node) {
+                    if let Some(space) = &mut self.search_space {
+                        space.relax(node, edge.target, true);
+                    }
+                    let new_weight = weight + edge.weight;
+                    if new_weight >= self.get_weight_fwd(edge.target) {
+                        continue;
+                    }
+                    let potential = self.potential_fwd(edge.target, source, target);
+                    if potential.is_infinite() {
+                        continue;
+                    }
+
+                    self.data_fwd
+                        .insert(edge.target, (new_weight, Some(edge_idx)));
+                    queue_fwd.push(Candidate::new(edge.target, new_weight + potential));
+
+                    let total = new_weight + self.get_weight_bwd(edge.target);
+                    if total < self.best_weight {
+                        self.best_weight = total;
+                        self.intersect_node = Some(edge.target);
+                    }
+                }
+            } else {
+                let node = queue_bwd.pop().unwrap().node_idx;
+                if !self.settled_bwd.insert(node) {
+                    continue;
+                }
+                self.stats.nodes_settled += 1;
+                if let Some(space) = &mut self.search_space {
+                    space.settle(node, false);
+                }
+                let weight = self.get_weight_bwd(node);
+
+                for (edge_idx, edge) in self.g.core_edges_in(node) {
+                    if let Some(space) = &mut self.search_space {
+                        space.relax(edge.source, node, false);
+                    }
+                    let new_weight = weight + edge.weight;
+                    if new_weight >= self.get_weight_bwd(edge.source) {
+                        continue;
+                    }
+                    let potential = self.potential_fwd(edge.source, source, target);
+                    if potential.is_infinite() {
+                        continue;
+                    }
+
+                    self.data_bwd
+                        .insert(edge.source, (new_weight, Some(edge_idx)));
+                    queue_bwd.push(Candidate::new(edge.source, new_weight - potential));
+
+                    let total = new_weight + self.get_weight_fwd(edge.source);
+                    if total < self.best_weight {
+                        self.best_weight = total;
+                        self.intersect_node = Some(edge.source);
+                    }
+                }
+            }
+        }
+    }
+
+    fn get_weight_fwd(&self, node: NodeIndex) -> Weight {
+        self.data_fwd.get(&node).map_or(Weight::INFINITY, |d| d.0)
+    }
+
+    fn get_weight_bwd(&self, node: NodeIndex) -> Weight {
+        self.data_bwd.get(&node).map_or(Weight::INFINITY, |d| d.0)
+    }
+
+    /// Backtracks both searches from the intersect node and unpacks all shortcuts
+    fn reconstruct_shortest_path(&self, source: NodeIndex) -> Option<ShortestPath> {
+        let Some(intersect_node) = self.intersect_node else {
+            info!("No path found");
+            return None;
+        };
+
+        let mut path = vec![];
+        let mut node = intersect_node;
+        while let Some(Some(prev_edge)) = self.data_fwd.get(&node).map(|d| d.1) {
+            for edge_idx in self.g.unpack_edge(prev_edge).iter().rev() {
+                path.push(self.g.edge(*edge_idx).target);
+            }
+            node = self.g.edge(prev_edge).source;
+        }
+        path.push(source);
+        path.reverse();
+
+        let mut node = intersect_node;
+        while let Some(Some(next_edge)) = self.data_bwd.get(&node).map(|d| d.1) {
+            for edge_idx in self.g.unpack_edge(next_edge) {
+                path.push(self.g.edge(edge_idx).target);
+            }
+            node = self.g.edge(next_edge).target;
+        }
+
+        debug!("Path: {:?}", &path);
+        info!("{}, weight: {}", self.stats, self.best_weight);
+
+        Some(ShortestPath::new(path, self.best_weight))
+    }
+}
+
+impl ShortestPathAlgorithm for CoreALT<'_> {
+    fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
+        CoreALT::search(self, source, target)
+    }
+
+    fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    fn settled_fwd(&self) -> &FxHashSet<NodeIndex> {
+        &self.settled_fwd
+    }
+
+    fn settled_bwd(&self) -> Option<&FxHashSet<NodeIndex>> {
+        Some(&self.settled_bwd)
+    }
+
+    fn meeting_node(&self) -> Option<NodeIndex> {
+        self.intersect_node
+    }
+
+    fn record_search_space(&mut self, record: bool) {
+        self.search_space = record.then(SearchSpace::default);
+    }
+
+    fn search_space(&self) -> Option<&SearchSpace> {
+        self.search_space.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{
+        contraction_strategy::{ContractionStrategy, CoreSize, UpdateStrategy},
+        graph::node_index,
+        node_contraction::NodeContractor,
+        search::{landmarks::LandmarkSelection, Dijkstra},
+        util::test_graphs::{generate_complex_graph, graph_vaterstetten},
+    };
+
+    use super::*;
+
+    fn assert_same_as_dijkstra(overlay_graph: &OverlayGraph, landmarks: &Landmarks) {
+        let g = overlay_graph.road_graph();
+        let mut d = Dijkstra::new(g);
+        let mut core_alt = CoreALT::new(overlay_graph, landmarks);
+
+        for s in 0..g.nodes.len() {
+            for t in 0..g.nodes.len() {
+                let (s, t) = (node_index(s), node_index(t));
+                match (d.search(s, t), core_alt.search(s, t)) {
+                    (Some(expected), Some(sp)) => {
+                        assert_abs_diff_eq!(expected.weight, sp.weight, epsilon = 1e-4);
+                        assert_eq!(Some(&s), sp.nodes.first());
+                        assert_eq!(Some(&t), sp.nodes.last());
+                    }
+                    (expected, sp) => assert_eq!(expected, sp),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn search_on_complex_graph() {
+        for core_size in [
+            CoreSize::Nodes(0),
+            CoreSize::Nodes(3),
+            CoreSize::ContractedFraction(0.5),
+            CoreSize::ContractedFraction(0.0),
+        ] {
+            let mut g = generate_complex_graph();
+            let strategy = UpdateStrategy::new().set_core_size(core_size);
+            let mut contractor = NodeContractor::new(&mut g);
+            let overlay_graph =
+                contractor.run_with_strategy(ContractionStrategy::LazyUpdate(strategy));
+
+            let landmarks = Landmarks::new(overlay_graph.road_graph(), 2, LandmarkSelection::Avoid);
+            assert_same_as_dijkstra(&overlay_graph, &landmarks);
+        }
+    }
+
+    #[test]
+    fn core_has_expected_size() {
+        let mut g = generate_complex_graph();
+        let strategy = UpdateStrategy::new().set_core_size(CoreSize::ContractedFraction(0.8));
+        let mut contractor = NodeContractor::new(&mut g);
+        let overlay_graph = contractor.run_with_strategy(ContractionStrategy::LazyUpdate(strategy));
+
+        // 11 nodes, 9 of them contracted
+        assert_eq!(2, overlay_graph.core_nodes().count());
+        for node in overlay_graph.core_nodes() {
+            assert!(overlay_graph.edges_fwd[node.index()].is_empty());
+            assert!(overlay_graph.edges_bwd[node.index()].is_empty());
+            // Core nodes are ranked above all contracted nodes
+            assert!(overlay_graph.node_order[node.index()] > 9);
+        }
+    }
+
+    #[test]
+    fn search_on_vaterstetten() {
+        let mut g = graph_vaterstetten();
+        let num_nodes = g.nodes.len();
+        let strategy = UpdateStrategy::new().set_core_size(CoreSize::ContractedFraction(0.9));
+        let mut contractor = NodeContractor::new(&mut g);
+        let overlay_graph = contractor.run_with_strategy(ContractionStrategy::LazyUpdate(strategy));
+        let landmarks = Landmarks::new(overlay_graph.road_graph(), 8, LandmarkSelection::Avoid);
+
+        let mut runner = proptest::test_runner::TestRunner::default();
+
+        runner
+            .run(&(0..num_nodes, 0..num_nodes), |(s, t)| {
+                let (s, t) = (node_index(s), node_index(t));
+                let expected = Dijkstra::new(overlay_graph.road_graph())
+                    .search(s, t)
+                    .map(|sp| sp.weight);
+                let weight = CoreALT::new(&overlay_graph, &landmarks)
+                    .search(s, t)
+                    .map(|sp| sp.weight);
+                match (expected, weight) {
+                    (Some(expected), Some(weight)) => {
+                        assert_abs_diff_eq!(expected, weight, epsilon = 1e-4)
+                    }
+                    _ => assert_eq!(expected, weight),
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::BinaryHeap;
 use crate::constants::Weight;
 use crate::graph::*;
 use crate::search::shortest_path::ShortestPath;
+use crate::search::{SearchSpace, ShortestPathAlgorithm};
 use crate::statistics::SearchStats;
 use log::{debug, info};
 use rustc_hash::{FxHashMap, FxHashSet};
@@ -43,9 +44,88 @@ impl Ord for Candidate {
     }
 }
 
+/// Shortest path tree rooted at `source`, computed by the one-to-many searches of [`Dijkstra`].
+///
+/// Only settled nodes are part of the tree, so all distances are final. Trees of
+/// [`Dijkstra::all_to_one`] follow the incoming edges, their distances are the distances *to*
+/// the root.
+#[derive(Debug, Clone)]
+pub struct ShortestPathTree {
+    source: NodeIndex,
+    reverse: bool,
+    order: Vec<NodeIndex>,
+    nodes: FxHashMap<NodeIndex, (Weight, Option<NodeIndex>)>,
+}
+
+impl ShortestPathTree {
+    fn new(source: NodeIndex, reverse: bool) -> Self {
+        ShortestPathTree {
+            source,
+            reverse,
+            order: Vec::new(),
+            nodes: FxHashMap::default(),
+        }
+    }
+
+    /// Root of the tree
+    pub fn source(&self) -> NodeIndex {
+        self.source
+    }
+
+    /// Nodes of the tree in the order they were settled
+    pub fn settled(&self) -> &[NodeIndex] {
+        &self.order
+    }
+
+    /// Number of nodes in the tree
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn contains(&self, node: NodeIndex) -> bool {
+        self.nodes.contains_key(&node)
+    }
+
+    /// Distance from the root to `node`, or from `node` to the root for trees of
+    /// [`Dijkstra::all_to_one`]. `None` if `node` is not part of the tree.
+    pub fn distance(&self, node: NodeIndex) -> Option<Weight> {
+        self.nodes.get(&node).map(|(weight, _)| *weight)
+    }
+
+    /// Parent of `node` in the tree, i.e. the next node towards the root. `None` for the root
+    /// and nodes not part of the tree.
+    pub fn predecessor(&self, node: NodeIndex) -> Option<NodeIndex> {
+        self.nodes.get(&node)?.1
+    }
+
+    /// Node with Dijkstra rank `rank`, i.e. the `rank`-th node settled. Ranks start at `1`
+    /// with the source.
+    pub fn node_with_rank(&self, rank: usize) -> Option<NodeIndex> {
+        self.order.get(rank.checked_sub(1)?).copied()
+    }
+
+    /// Shortest path from the root to `node`, or from `node` to the root for trees of
+    /// [`Dijkstra::all_to_one`]
+    pub fn path_to(&self, node: NodeIndex) -> Option<ShortestPath> {
+        if node == self.source {
+            return Some(ShortestPath::new(vec![node], 0.0));
+        }
+        let mut sp = super::reconstruct_path(node, self.source, &self.nodes)?;
+        if self.reverse {
+            sp.nodes.reverse();
+        }
+        Some(sp)
+    }
+}
+
 pub struct Dijkstra<'a, Idx = DefaultIdx> {
     pub stats: SearchStats,
     pub nodes_settled: FxHashSet<NodeIndex<Idx>>,
+    search_space: Option<SearchSpace>,
     g: &'a Graph<Idx>,
 }
 
@@ -54,20 +134,34 @@ impl<'a> Dijkstra<'a> {
         Dijkstra {
             g: graph,
             nodes_settled: FxHashSet::default(),
+            search_space: None,
             stats: SearchStats::default(),
         }
     }
 
     pub fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
         self.stats.init();
+        self.nodes_settled.clear();
+        if let Some(space) = &mut self.search_space {
+            space.clear();
+        }
         info!("BEGIN DIJKSTRA SEARCH from {:?} to {:?}", source, target);
 
         if source == target {
             self.stats.nodes_settled += 1;
+            if let Some(space) = &mut self.search_space {
+                space.settle(source, true);
+            }
             self.stats.finish();
             return Some(ShortestPath::new(vec![source], 0.0));
         }
 
+        if !self.g.may_reach(source, target) {
+            debug!("{:?} can't reach {:?}", source, target);
+            self.stats.finish();
+            return None;
+        }
+
         let mut node_data: FxHashMap<NodeIndex, (Weight, Option<NodeIndex>)> = FxHashMap::default();
         node_data.insert(source, (0.0, None));
 
@@ -80,7 +174,14 @@ impl<'a> Dijkstra<'a> {
             node_idx: node,
         }) = queue.pop()
         {
+            // Skip outdated entries of nodes which were reached on a shorter path later
+            if weight > node_data[&node].0 {
+                continue;
+            }
             self.stats.nodes_settled += 1;
+            if let Some(space) = &mut self.search_space {
+                space.settle(node, true);
+            }
 
             if node == target {
                 break;
@@ -90,6 +191,9 @@ impl<'a> Dijkstra<'a> {
                 edge_idx.index() < self.g.edges.len() - self.g.num_shortcuts
                 // true
             }) {
+                if let Some(space) = &mut self.search_space {
+                    space.relax(node, edge.target, true);
+                }
                 let new_distance = weight + edge.weight;
                 if new_distance
                     < node_data
@@ -119,6 +223,138 @@ impl<'a> Dijkstra<'a> {
             None
         }
     }
+
+    /// Settles all nodes reachable from `source`.
+    pub fn one_to_all(&mut self, source: NodeIndex) -> ShortestPathTree {
+        self.grow_tree(source, Weight::INFINITY, false, |_| false)
+    }
+
+    /// Settles all nodes which can reach `target` by following the incoming edges. The search
+    /// is recorded as backward search.
+    pub fn all_to_one(&mut self, target: NodeIndex) -> ShortestPathTree {
+        self.grow_tree(target, Weight::INFINITY, true, |_| false)
+    }
+
+    /// Settles nodes until all `targets` are settled or no further nodes are reachable.
+    pub fn one_to_set(&mut self, source: NodeIndex, targets: &[NodeIndex]) -> ShortestPathTree {
+        let mut remaining: FxHashSet<NodeIndex> = targets.iter().copied().collect();
+        self.grow_tree(source, Weight::INFINITY, false, |node| {
+            remaining.remove(&node);
+            remaining.is_empty()
+        })
+    }
+
+    /// Settles all nodes with a distance of at most `radius` from `source`.
+    pub fn within_radius(&mut self, source: NodeIndex, radius: Weight) -> ShortestPathTree {
+        self.grow_tree(source, radius, false, |_| false)
+    }
+
+    /// Grows a shortest path tree from `source` until `is_done` returns `true` for a settled
+    /// node or the next node is farther away than `radius`. Follows the incoming edges if
+    /// `reverse` is set.
+    fn grow_tree(
+        &mut self,
+        source: NodeIndex,
+        radius: Weight,
+        reverse: bool,
+        mut is_done: impl FnMut(NodeIndex) -> bool,
+    ) -> ShortestPathTree {
+        self.stats.init();
+        self.nodes_settled.clear();
+        if let Some(space) = &mut self.search_space {
+            space.clear();
+        }
+        info!("BEGIN DIJKSTRA ONE-TO-MANY SEARCH from {:?}", source);
+
+        let num_edges = self.g.edges.len() - self.g.num_shortcuts;
+        let mut neighbors = Vec::new();
+        let mut tree = ShortestPathTree::new(source, reverse);
+        let mut node_data: FxHashMap<NodeIndex, (Weight, Option<NodeIndex>)> = FxHashMap::default();
+        node_data.insert(source, (0.0, None));
+
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate::new(source, 0.0));
+
+        while let Some(Candidate {
+            weight,
+            node_idx: node,
+        }) = queue.pop()
+        {
+            if weight > radius {
+                break;
+            }
+            if !self.nodes_settled.insert(node) {
+                continue;
+            }
+            self.stats.nodes_settled += 1;
+            if let Some(space) = &mut self.search_space {
+                space.settle(node, !reverse);
+            }
+            tree.order.push(node);
+            tree.nodes.insert(node, node_data[&node]);
+
+            if is_done(node) {
+                break;
+            }
+
+            neighbors.clear();
+            if reverse {
+                neighbors.extend(
+                    self.g
+                        .neighbors_incoming(node)
+                        .filter(|(edge_idx, _)| edge_idx.index() < num_edges)
+                        .map(|(_, edge)| (edge.source, edge.weight)),
+                );
+            } else {
+                neighbors.extend(
+                    self.g
+                        .neighbors_outgoing(node)
+                        .filter(|(edge_idx, _)| edge_idx.index() < num_edges)
+                        .map(|(_, edge)| (edge.target, edge.weight)),
+                );
+            }
+
+            for &(next, edge_weight) in &neighbors {
+                if let Some(space) = &mut self.search_space {
+                    match reverse {
+                        true => space.relax(next, node, false),
+                        false => space.relax(node, next, true),
+                    }
+                }
+                let new_distance = weight + edge_weight;
+                if new_distance < node_data.get(&next).map_or(Weight::INFINITY, |d| d.0) {
+                    node_data.insert(next, (new_distance, Some(node)));
+                    queue.push(Candidate::new(next, new_distance));
+                }
+            }
+        }
+        self.stats.finish();
+        info!("{}, {} nodes in tree", self.stats, tree.len());
+
+        tree
+    }
+}
+
+impl ShortestPathAlgorithm for Dijkstra<'_> {
+    fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath> {
+        Dijkstra::search(self, source, target)
+    }
+
+    fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    fn settled_fwd(&self) -> &FxHashSet<NodeIndex> {
+        &self.nodes_settled
+    }
+
+    fn record_search_space(&mut self, record: bool) {
+        self.search_space = record.then(SearchSpace::default);
+    }
+
+    fn search_space(&self) -> Option<&SearchSpace> {
+        self.search_space.as_ref()
+    }
 }
 
 #[cfg(test)]
@@ -204,4 +440,90 @@ mod tests {
 
         assert_path(vec![0, 2, 3, 1], 3.0, d.search(a, b));
     }
+
+    #[test]
+    fn one_to_all_matches_search() {
+        let g = crate::util::test_graphs::generate_complex_graph();
+        let mut d = Dijkstra::new(&g);
+
+        for s in 0..11 {
+            let tree = d.one_to_all(node_index(s));
+            assert_eq!(11, tree.len());
+            assert_eq!(Some(node_index(s)), tree.node_with_rank(1));
+
+            let mut last_distance = 0.0;
+            for (rank, node) in tree.settled().iter().enumerate() {
+                let distance = tree.distance(*node).unwrap();
+                assert!(distance >= last_distance);
+                last_distance = distance;
+                assert_eq!(Some(*node), tree.node_with_rank(rank + 1));
+
+                let sp = d.search(node_index(s), *node).unwrap();
+                assert_eq!(sp.weight, distance);
+                assert_eq!(sp.weight, tree.path_to(*node).unwrap().weight);
+            }
+        }
+    }
+
+    #[test]
+    fn all_to_one_matches_search() {
+        let g = crate::util::test_graphs::generate_complex_graph();
+        let mut d = Dijkstra::new(&g);
+
+        for t in 0..11 {
+            let tree = d.all_to_one(node_index(t));
+            for node in tree.settled() {
+                let sp = d.search(*node, node_index(t)).unwrap();
+                assert_eq!(Some(sp.weight), tree.distance(*node));
+
+                let path = tree.path_to(*node).unwrap();
+                assert_eq!(sp.weight, path.weight);
+                assert_eq!(Some(node), path.nodes.first());
+                assert_eq!(Some(&node_index(t)), path.nodes.last());
+            }
+        }
+    }
+
+    #[test]
+    fn one_to_set_stops_at_last_target() {
+        // 0 -> 1 -> 2 -> 3 -> 4
+        let mut g = Graph::new();
+        for i in 0..5 {
+            g.add_node(Node::new(i, 0.0, 0.0));
+        }
+        for i in 0..4 {
+            g.add_edge(Edge::new(node_index(i), node_index(i + 1), 1.0));
+        }
+
+        let mut d = Dijkstra::new(&g);
+        let tree = d.one_to_set(node_index(0), &[node_index(2), node_index(1)]);
+
+        assert_eq!(3, tree.len());
+        assert_eq!(Some(2.0), tree.distance(node_index(2)));
+        assert_eq!(Some(node_index(1)), tree.predecessor(node_index(2)));
+        assert_eq!(None, tree.predecessor(node_index(0)));
+        assert!(!tree.contains(node_index(3)));
+        assert_path(vec![0, 1, 2], 2.0, tree.path_to(node_index(2)));
+
+        // Unreachable targets settle everything reachable
+        let tree = d.one_to_set(node_index(2), &[node_index(0)]);
+        assert_eq!(3, tree.len());
+        assert_no_path(tree.path_to(node_index(0)));
+    }
+
+    #[test]
+    fn within_radius() {
+        let g = crate::util::test_graphs::generate_complex_graph();
+        let mut d = Dijkstra::new(&g);
+        let all = d.one_to_all(node_index(0));
+
+        for radius in [0.0, 3.0, 5.5, 8.0, 100.0] {
+            let tree = d.within_radius(node_index(0), radius);
+            for node in all.settled() {
+                let distance = all.distance(*node).unwrap();
+                assert_eq!(distance <= radius, tree.contains(*node));
+            }
+            assert_eq!(tree.len(), d.stats.nodes_settled);
+        }
+    }
 }
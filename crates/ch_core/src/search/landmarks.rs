@@ -0,0 +1,552 @@
+//! Landmark preprocessing for the ALT (A*, landmarks, triangle inequality) heuristic.
+//!
+//! For every landmark `l` the distances `d(l, v)` and `d(v, l)` to all nodes are precomputed.
+//! The triangle inequality then yields two lower bounds for the distance between `v` and `t`:
+//! `d(l, t) - d(l, v)` and `d(v, l) - d(t, l)`. The maximum over all landmarks is a consistent
+//! potential, which guides [`AStar`] and [`BidirAStar`] much better than the straight line
+//! distance if the weights are travel times.
+//!
+//! # Examples
+//! ```
+//! use ch_core::prelude::*;
+//! use ch_core::search::landmarks::{LandmarkSelection, Landmarks};
+//! use ch_core::search::AStar;
+//!
+//! let g = generate_simple_graph();
+//! let landmarks = Landmarks::new(&g, 2, LandmarkSelection::Avoid);
+//!
+//! let (s, t) = (node_index(4), node_index(1));
+//! let mut astar = AStar::new(&g);
+//! let sp = astar.search_with_potential(s, t, landmarks.potential(t));
+//! assert_eq!(sp.unwrap().weight, 3.0);
+//! ```
+//! [`AStar`]: crate::search::AStar
+//! [`BidirAStar`]: crate::search::BidirAStar
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use log::{debug, info, warn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::Weight,
+    graph::{Graph, NodeIndex},
+    serialization::{self, BuildMetadata, Compression, FormatError, LANDMARKS_MAGIC},
+};
+
+use super::dijkstra::{Dijkstra, ShortestPathTree};
+
+/// Default number of landmarks
+pub const DEFAULT_NUM_LANDMARKS: usize = 16;
+
+/// Seed for the random choices during landmark selection, so results are reproducible
+const SELECTION_SEED: u64 = 187;
+
+/// Strategy to select landmarks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LandmarkSelection {
+    /// Every new landmark is the node farthest away from all previously selected landmarks.
+    #[default]
+    Farthest,
+    /// Goldberg and Werneck's avoid heuristic: Grows a shortest path tree from a random root
+    /// and selects a leaf of the subtree, in which the current landmarks give the worst lower
+    /// bounds.
+    Avoid,
+}
+
+impl FromStr for LandmarkSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "farthest" => Ok(LandmarkSelection::Farthest),
+            "avoid" => Ok(LandmarkSelection::Avoid),
+            _ => Err(format!("Unknown landmark selection: {}", s)),
+        }
+    }
+}
+
+impl Display for LandmarkSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LandmarkSelection::Farthest => write!(f, "farthest"),
+            LandmarkSelection::Avoid => write!(f, "avoid"),
+        }
+    }
+}
+
+/// Selected landmarks with their forward and backward distance tables.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Landmarks {
+    pub selection: LandmarkSelection,
+    landmarks: Vec<NodeIndex>,
+    num_nodes: usize,
+    /// `dist_from[v * k + i]` is the distance from landmark `i` to node `v`
+    dist_from: Vec<Weight>,
+    /// `dist_to[v * k + i]` is the distance from node `v` to landmark `i`
+    dist_to: Vec<Weight>,
+    /// [`Graph::fingerprint`] of the graph the tables were computed for
+    #[serde(skip)]
+    fingerprint: Option<u64>,
+}
+
+impl Landmarks {
+    /// Selects up to `num_landmarks` landmarks with `selection` and computes their distance
+    /// tables. Shortcuts are ignored.
+    ///
+    /// Fewer landmarks are selected if the graph does not contain enough suitable nodes.
+    pub fn new(g: &Graph, num_landmarks: usize, selection: LandmarkSelection) -> Self {
+        info!(
+            "BEGIN selecting {} landmarks ({})",
+            num_landmarks, selection
+        );
+        let mut tables = Tables::default();
+        let num_nodes = g.nodes.len();
+        let mut rng = StdRng::seed_from_u64(SELECTION_SEED);
+
+        if num_nodes > 0 && num_landmarks > 0 {
+            // Both strategies start with the node farthest away from a random node
+            let root = NodeIndex::new(rng.gen_range(0..num_nodes));
+            let first =
+                farthest_node(&distances(g, Dijkstra::new(g).one_to_all(root))).unwrap_or(root);
+            tables.add(g, first);
+        }
+
+        while tables.landmarks.len() < num_landmarks.min(num_nodes) {
+            let next = match selection {
+                LandmarkSelection::Farthest => tables.farthest(),
+                LandmarkSelection::Avoid => tables.avoid(g, &mut rng),
+            };
+            match next {
+                Some(landmark) => tables.add(g, landmark),
+                None => {
+                    warn!("Found only {} suitable landmarks", tables.landmarks.len());
+                    break;
+                }
+            }
+        }
+        info!("FINISHED selecting landmarks: {:?}", tables.landmarks);
+
+        let mut landmarks = tables.into_landmarks(num_nodes, selection);
+        landmarks.fingerprint = g.fingerprint;
+        landmarks
+    }
+
+    /// Selected landmarks
+    pub fn landmarks(&self) -> &[NodeIndex] {
+        &self.landmarks
+    }
+
+    /// Number of selected landmarks
+    pub fn len(&self) -> usize {
+        self.landmarks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.landmarks.is_empty()
+    }
+
+    /// Number of nodes of the graph the tables were computed for
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes
+    }
+
+    /// Distance from the `i`-th landmark to `node`. Infinite if `node` is not reachable.
+    pub fn dist_from(&self, i: usize, node: NodeIndex) -> Weight {
+        self.dist_from[node.index() * self.len() + i]
+    }
+
+    /// Distance from `node` to the `i`-th landmark. Infinite if the landmark is not reachable.
+    pub fn dist_to(&self, i: usize, node: NodeIndex) -> Weight {
+        self.dist_to[node.index() * self.len() + i]
+    }
+
+    /// Lower bound of the distance from `source` to `target`.
+    ///
+    /// Returns [`Weight::INFINITY`] if the tables prove, that `target` is not reachable from
+    /// `source`.
+    pub fn lower_bound(&self, source: NodeIndex, target: NodeIndex) -> Weight {
+        let k = self.len();
+        let from_s = &self.dist_from[source.index() * k..][..k];
+        let from_t = &self.dist_from[target.index() * k..][..k];
+        let to_s = &self.dist_to[source.index() * k..][..k];
+        let to_t = &self.dist_to[target.index() * k..][..k];
+
+        let mut bound: Weight = 0.0;
+        for i in 0..k {
+            // d(l, t) <= d(l, s) + d(s, t)
+            if from_s[i].is_finite() && from_t[i].is_finite() {
+                bound = bound.max(from_t[i] - from_s[i]);
+            } else if from_s[i].is_finite() {
+                // l reaches s but not t, so s can't reach t either
+                return Weight::INFINITY;
+            }
+
+            // d(s, l) <= d(s, t) + d(t, l)
+            if to_s[i].is_finite() && to_t[i].is_finite() {
+                bound = bound.max(to_s[i] - to_t[i]);
+            } else if to_t[i].is_finite() {
+                // t reaches l but s doesn't, so s can't reach t either
+                return Weight::INFINITY;
+            }
+        }
+        bound
+    }
+
+    /// Potential for a search towards `target`, usable with
+    /// [`AStar::search_with_potential`](crate::search::AStar::search_with_potential).
+    pub fn potential(&self, target: NodeIndex) -> impl Fn(NodeIndex) -> Weight + '_ {
+        move |node| self.lower_bound(node, target)
+    }
+
+    /// Path of the landmark file belonging to the graph file at `graph_path`
+    /// (`<graph_path>.landmarks`).
+    pub fn path_for(graph_path: &Path) -> PathBuf {
+        let mut path = graph_path.as_os_str().to_owned();
+        path.push(".landmarks");
+        PathBuf::from(path)
+    }
+
+    /// Writes the landmarks and their tables to `path`.
+    ///
+    /// Returns the number of bytes written.
+    pub fn encode(&self, path: impl Into<PathBuf>) -> Result<usize, FormatError> {
+        serialization::write_file(
+            &path.into(),
+            LANDMARKS_MAGIC,
+            &BuildMetadata {
+                fingerprint: self.fingerprint,
+                ..Default::default()
+            },
+            Compression::Zstd,
+            self,
+        )
+    }
+
+    /// Reads landmarks written by [`Landmarks::encode`].
+    pub fn from_decode(path: impl Into<PathBuf>) -> Result<Self, FormatError> {
+        let mut body = serialization::read_file(&path.into(), LANDMARKS_MAGIC)?;
+        if body.header().version == 0 {
+            return Err(FormatError::InvalidMagic);
+        }
+        let mut landmarks: Landmarks = body.decode()?;
+        landmarks.fingerprint = body.finish()?.metadata.fingerprint;
+        Ok(landmarks)
+    }
+
+    /// Loads the landmarks stored next to the graph file at `graph_path` (see
+    /// [`Landmarks::path_for`]). If there is no such file, or it was computed for a different
+    /// graph or with other parameters, the landmarks are computed and stored. Graphs are
+    /// compared by their size and their [`Graph::fingerprint`]. Graphs without fingerprint
+    /// (not imported from OSM) can't be told apart, their landmarks are always recomputed.
+    pub fn load_or_compute(
+        g: &Graph,
+        graph_path: &Path,
+        num_landmarks: usize,
+        selection: LandmarkSelection,
+    ) -> anyhow::Result<Self> {
+        let path = Landmarks::path_for(graph_path);
+
+        match Landmarks::from_decode(&path) {
+            Ok(landmarks)
+                if landmarks.num_nodes == g.nodes.len()
+                    && g.fingerprint.is_some()
+                    && landmarks.fingerprint == g.fingerprint
+                    && landmarks.selection == selection
+                    && landmarks.len() == num_landmarks.min(g.nodes.len()) =>
+            {
+                info!("Loaded landmarks from {:?}", path);
+                return Ok(landmarks);
+            }
+            Ok(_) => info!("Landmarks in {:?} do not match the graph", path),
+            Err(e) => debug!("Could not load landmarks from {:?}: {}", path, e),
+        }
+
+        let landmarks = Landmarks::new(g, num_landmarks, selection);
+        landmarks.encode(&path)?;
+        Ok(landmarks)
+    }
+}
+
+/// Landmark major distance tables used during selection
+#[derive(Default)]
+struct Tables {
+    landmarks: Vec<NodeIndex>,
+    dist_from: Vec<Vec<Weight>>,
+    dist_to: Vec<Vec<Weight>>,
+}
+
+impl Tables {
+    fn add(&mut self, g: &Graph, landmark: NodeIndex) {
+        debug!("Add landmark {:?}", landmark);
+        self.landmarks.push(landmark);
+        let mut dijkstra = Dijkstra::new(g);
+        self.dist_from
+            .push(distances(g, dijkstra.one_to_all(landmark)));
+        self.dist_to
+            .push(distances(g, dijkstra.all_to_one(landmark)));
+    }
+
+    fn is_landmark(&self, node: NodeIndex) -> bool {
+        self.landmarks.contains(&node)
+    }
+
+    /// Node with the largest distance to its closest landmark
+    fn farthest(&self) -> Option<NodeIndex> {
+        let num_nodes = self.dist_from.first()?.len();
+        let min_dist = (0..num_nodes)
+            .map(|v| {
+                self.dist_from
+                    .iter()
+                    .zip(&self.dist_to)
+                    .map(|(from, to)| from[v] + to[v])
+                    .fold(Weight::INFINITY, Weight::min)
+            })
+            .collect::<Vec<_>>();
+
+        farthest_node(&min_dist).filter(|node| !self.is_landmark(*node))
+    }
+
+    fn avoid(&self, g: &Graph, rng: &mut StdRng) -> Option<NodeIndex> {
+        const MAX_TRIES: usize = 16;
+
+        let num_nodes = g.nodes.len();
+        for _ in 0..MAX_TRIES {
+            let root = NodeIndex::new(rng.gen_range(0..num_nodes));
+            if let Some(landmark) = self.avoid_from(g, root) {
+                return Some(landmark);
+            }
+        }
+        None
+    }
+
+    /// Grows a shortest path tree from `root`, weights every node with the difference between
+    /// its distance and the current lower bound and descends into the heaviest subtrees without
+    /// a landmark until a leaf is reached.
+    fn avoid_from(&self, g: &Graph, root: NodeIndex) -> Option<NodeIndex> {
+        let tree = Dijkstra::new(g).one_to_all(root);
+        let num_nodes = g.nodes.len();
+
+        let mut size = vec![0.0; num_nodes];
+        let mut has_landmark = vec![false; num_nodes];
+        for &node in tree.settled() {
+            let dist = tree.distance(node).unwrap_or(Weight::INFINITY);
+            size[node.index()] = dist - self.lower_bound(root, node);
+            has_landmark[node.index()] = self.is_landmark(node);
+        }
+
+        // Children are settled after their parents, so all subtrees are complete once their
+        // root is reached in reverse order
+        for &node in tree.settled().iter().rev() {
+            if has_landmark[node.index()] {
+                size[node.index()] = 0.0;
+            }
+            if let Some(parent) = tree.predecessor(node) {
+                size[parent.index()] += size[node.index()];
+                has_landmark[parent.index()] |= has_landmark[node.index()];
+            }
+        }
+
+        let mut heaviest_child: Vec<Option<NodeIndex>> = vec![None; num_nodes];
+        for &node in tree.settled() {
+            if let Some(parent) = tree.predecessor(node) {
+                let heaviest = heaviest_child[parent.index()];
+                if size[node.index()] > heaviest.map_or(0.0, |child| size[child.index()]) {
+                    heaviest_child[parent.index()] = Some(node);
+                }
+            }
+        }
+
+        // The tree of the root itself always contains the landmarks, so start at its children
+        let mut node = heaviest_child[root.index()]?;
+        while let Some(child) = heaviest_child[node.index()] {
+            node = child;
+        }
+
+        Some(node)
+    }
+
+    fn lower_bound(&self, source: NodeIndex, target: NodeIndex) -> Weight {
+        let (s, t) = (source.index(), target.index());
+        self.dist_from
+            .iter()
+            .zip(&self.dist_to)
+            .flat_map(|(from, to)| [from[t] - from[s], to[s] - to[t]])
+            .filter(|bound| bound.is_finite())
+            .fold(0.0, Weight::max)
+    }
+
+    /// Converts the tables to the node major layout used by [`Landmarks`].
+    fn into_landmarks(self, num_nodes: usize, selection: LandmarkSelection) -> Landmarks {
+        let transpose = |tables: &[Vec<Weight>]| {
+            (0..num_nodes)
+                .flat_map(|v| tables.iter().map(move |table| table[v]))
+                .collect::<Vec<_>>()
+        };
+
+        Landmarks {
+            selection,
+            dist_from: transpose(&self.dist_from),
+            dist_to: transpose(&self.dist_to),
+            landmarks: self.landmarks,
+            num_nodes,
+            fingerprint: None,
+        }
+    }
+}
+
+/// Dense distances of `tree`, nodes outside of the tree are unreachable
+fn distances(g: &Graph, tree: ShortestPathTree) -> Vec<Weight> {
+    let mut dist = vec![Weight::INFINITY; g.nodes.len()];
+    for &node in tree.settled() {
+        dist[node.index()] = tree.distance(node).unwrap_or(Weight::INFINITY);
+    }
+    dist
+}
+
+/// Node with the largest finite distance
+fn farthest_node(dist: &[Weight]) -> Option<NodeIndex> {
+    dist.iter()
+        .enumerate()
+        .filter(|(_, d)| d.is_finite() && **d > 0.0)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(v, _)| NodeIndex::new(v))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        graph::node_index,
+        search::{AStar, Dijkstra},
+        util::test_graphs::generate_complex_graph,
+    };
+
+    use super::*;
+
+    fn assert_lower_bounds(g: &Graph, landmarks: &Landmarks) {
+        let mut d = Dijkstra::new(g);
+        for s in 0..g.nodes.len() {
+            for t in 0..g.nodes.len() {
+                let bound = landmarks.lower_bound(node_index(s), node_index(t));
+                match d.search(node_index(s), node_index(t)) {
+                    Some(sp) => assert!(bound <= sp.weight, "{} > {}", bound, sp.weight),
+                    None => assert!(bound.is_infinite() || bound >= 0.0),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lower_bounds_are_admissible() {
+        let g = generate_complex_graph();
+
+        for selection in [LandmarkSelection::Farthest, LandmarkSelection::Avoid] {
+            let landmarks = Landmarks::new(&g, 3, selection);
+            assert_eq!(3, landmarks.len());
+            assert_lower_bounds(&g, &landmarks);
+        }
+    }
+
+    #[test]
+    fn tables_contain_distances() {
+        let g = generate_complex_graph();
+        let landmarks = Landmarks::new(&g, 2, LandmarkSelection::Farthest);
+        let mut d = Dijkstra::new(&g);
+
+        for (i, &l) in landmarks.landmarks().iter().enumerate() {
+            for v in 0..g.nodes.len() {
+                let v = node_index(v);
+                assert_eq!(d.search(l, v).unwrap().weight, landmarks.dist_from(i, v));
+                assert_eq!(d.search(v, l).unwrap().weight, landmarks.dist_to(i, v));
+            }
+        }
+    }
+
+    #[test]
+    fn unreachable_target() {
+        let g = crate::util::test_graphs::generate_simple_graph();
+        let landmarks = Landmarks::new(&g, 5, LandmarkSelection::Farthest);
+
+        // Node 4 (E) has no incoming edges
+        assert_lower_bounds(&g, &landmarks);
+        assert!(landmarks.len() <= 5);
+    }
+
+    #[test]
+    fn astar_with_landmarks() {
+        let g = generate_complex_graph();
+        let landmarks = Landmarks::new(&g, 4, LandmarkSelection::Avoid);
+        let mut d = Dijkstra::new(&g);
+        let mut astar = AStar::new(&g);
+
+        for s in 0..g.nodes.len() {
+            for t in 0..g.nodes.len() {
+                let (s, t) = (node_index(s), node_index(t));
+                assert_eq!(
+                    d.search(s, t).map(|sp| sp.weight),
+                    astar
+                        .search_with_potential(s, t, landmarks.potential(t))
+                        .map(|sp| sp.weight)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let mut g = generate_complex_graph();
+        g.fingerprint = Some(7);
+        let landmarks = Landmarks::new(&g, 3, LandmarkSelection::Avoid);
+
+        let dir = tempfile::tempdir().unwrap();
+        let graph_path = dir.path().join("landmarks_test_graph.bin");
+        let graph_path = graph_path.as_path();
+        let path = Landmarks::path_for(graph_path);
+        assert_eq!(dir.path().join("landmarks_test_graph.bin.landmarks"), path);
+
+        landmarks.encode(&path).unwrap();
+        assert_eq!(landmarks, Landmarks::from_decode(&path).unwrap());
+
+        // Matching file is loaded, otherwise the landmarks are recomputed
+        let loaded = Landmarks::load_or_compute(&g, graph_path, 3, LandmarkSelection::Avoid);
+        assert_eq!(landmarks, loaded.unwrap());
+        let recomputed = Landmarks::load_or_compute(&g, graph_path, 2, LandmarkSelection::Avoid);
+        assert_eq!(2, recomputed.unwrap().len());
+        assert_eq!(2, Landmarks::from_decode(&path).unwrap().len());
+
+        // Same size, but imported with other options
+        g.fingerprint = Some(42);
+        let recomputed = Landmarks::load_or_compute(&g, graph_path, 2, LandmarkSelection::Avoid);
+        assert_eq!(Some(42), recomputed.unwrap().fingerprint);
+        assert_eq!(Some(42), Landmarks::from_decode(&path).unwrap().fingerprint);
+    }
+
+    #[test]
+    fn recompute_without_fingerprint() {
+        let g = generate_complex_graph();
+        let dir = tempfile::tempdir().unwrap();
+        let graph_path = dir.path().join("landmarks_test_graph.bin");
+        let landmarks =
+            Landmarks::load_or_compute(&g, &graph_path, 2, LandmarkSelection::Farthest).unwrap();
+
+        // Same size and no fingerprint either, but other weights
+        let mut other = Graph::new();
+        for node in g.nodes() {
+            other.add_node(node.clone());
+        }
+        for edge in g.edges() {
+            let mut edge = edge.clone();
+            edge.weight *= 2.0;
+            other.add_edge(edge);
+        }
+        let recomputed =
+            Landmarks::load_or_compute(&other, &graph_path, 2, LandmarkSelection::Farthest)
+                .unwrap();
+        assert_ne!(landmarks, recomputed);
+        assert_lower_bounds(&other, &recomputed);
+    }
+}
@@ -4,7 +4,17 @@
 //! - [`Dijkstra`]
 //! - [`BidirDijkstra`]
 //! - [`AStar`]
+//! - [`BidirAStar`]
 //! - [`CHSearch`]
+//! - [`CoreALT`]
+//!
+//! All of them implement [`ShortestPathAlgorithm`], so tools and benchmarks can be written
+//! generically over the algorithm.
+//!
+//! Their [`SearchSpace`] can be recorded for debugging and visualisation.
+//!
+//! [`landmarks`] contains the preprocessing for the ALT heuristic used by [`AStar`] and
+//! [`BidirAStar`].
 //! # Examples
 //! ```
 //! use ch_core::prelude::*;
@@ -22,23 +32,89 @@
 //! [`Dijkstra`]: crate::search::Dijkstra
 //! [`BidirDijkstra`]: crate::search::BidirDijkstra
 //! [`AStar`]: crate::search::AStar
+//! [`BidirAStar`]: crate::search::BidirAStar
 //! [`CHSearch`]: crate::search::CHSearch
-use rustc_hash::FxHashMap;
+//! [`CoreALT`]: crate::search::CoreALT
+//! [`ShortestPathAlgorithm`]: crate::search::ShortestPathAlgorithm
+//! [`SearchSpace`]: crate::search::SearchSpace
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::graph::NodeIndex;
+use crate::statistics::SearchStats;
 
 pub mod astar;
+pub mod bidir_astar;
 pub mod bidir_dijkstra;
 pub mod ch_search;
+pub mod core_alt;
 pub mod dijkstra;
+pub mod landmarks;
+pub mod search_space;
 pub mod shortest_path;
 
 pub use astar::AStar;
+pub use bidir_astar::BidirAStar;
 pub use bidir_dijkstra::BidirDijkstra;
 pub use ch_search::CHSearch;
-pub use dijkstra::Dijkstra;
+pub use core_alt::CoreALT;
+pub use dijkstra::{Dijkstra, ShortestPathTree};
+pub use search_space::SearchSpace;
 pub use shortest_path::ShortestPath;
 
+/// Common interface of all shortest path algorithms.
+///
+/// Algorithms which need additional input, e.g. the heuristic of [`AStar`], use a sensible
+/// default. The inherent methods of the algorithms take precedence over this trait, so call
+/// `ShortestPathAlgorithm::search(&mut algo, s, t)` where the signatures differ.
+///
+/// # Examples
+/// ```
+/// use ch_core::prelude::*;
+/// use ch_core::prelude::search::*;
+///
+/// let g = generate_simple_graph();
+/// let mut algos: Vec<Box<dyn ShortestPathAlgorithm>> = vec![
+///     Box::new(Dijkstra::new(&g)),
+///     Box::new(AStar::new(&g)),
+///     Box::new(BidirDijkstra::new(&g)),
+/// ];
+///
+/// for algo in algos.iter_mut() {
+///     let sp = algo.search(node_index(4), node_index(1)).unwrap();
+///     assert_eq!(sp.weight, 3.0);
+///     assert!(algo.settled_fwd().contains(&node_index(4)));
+/// }
+/// ```
+pub trait ShortestPathAlgorithm {
+    /// Finds the shortest path from `source` to `target`.
+    fn search(&mut self, source: NodeIndex, target: NodeIndex) -> Option<ShortestPath>;
+
+    /// Statistics of the last search.
+    fn stats(&self) -> &SearchStats;
+
+    /// Nodes settled by the last (forward) search.
+    fn settled_fwd(&self) -> &FxHashSet<NodeIndex>;
+
+    /// Nodes settled by the backward search of the last search. `None` for unidirectional
+    /// algorithms.
+    fn settled_bwd(&self) -> Option<&FxHashSet<NodeIndex>> {
+        None
+    }
+
+    /// Node where the forward and backward search met in the last search. `None` for
+    /// unidirectional algorithms or if no path was found.
+    fn meeting_node(&self) -> Option<NodeIndex> {
+        None
+    }
+
+    /// Enables or disables recording the [`SearchSpace`] of the following searches. Disabled by
+    /// default.
+    fn record_search_space(&mut self, record: bool);
+
+    /// Search space of the last search. `None` unless recording is enabled.
+    fn search_space(&self) -> Option<&SearchSpace>;
+}
+
 pub fn reconstruct_path(
     target: NodeIndex,
     source: NodeIndex,
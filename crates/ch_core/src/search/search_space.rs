@@ -0,0 +1,178 @@
+//! Record of the search space explored by a query.
+//!
+//! Every [`ShortestPathAlgorithm`] can record the nodes it settled, the edges it relaxed and the
+//! node where the forward and backward search met. Recording is disabled by default to avoid the
+//! overhead during benchmarks and has to be enabled with
+//! [`ShortestPathAlgorithm::record_search_space`].
+//!
+//! # Examples
+//! ```
+//! use ch_core::prelude::*;
+//! use ch_core::prelude::search::*;
+//!
+//! let g = generate_simple_graph();
+//! let mut bidir = BidirDijkstra::new(&g);
+//! bidir.record_search_space(true);
+//!
+//! ShortestPathAlgorithm::search(&mut bidir, node_index(4), node_index(1));
+//!
+//! let space = bidir.search_space().unwrap();
+//! assert_eq!(space.settled_fwd[0], node_index(4));
+//! assert_eq!(space.settled_bwd[0], node_index(1));
+//! assert!(space.meeting_node.is_some());
+//! ```
+//!
+//! [`ShortestPathAlgorithm`]: crate::search::ShortestPathAlgorithm
+//! [`ShortestPathAlgorithm::record_search_space`]: crate::search::ShortestPathAlgorithm::record_search_space
+use crate::graph::NodeIndex;
+
+/// Search space of a single query.
+///
+/// Unidirectional algorithms only fill the forward part.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SearchSpace {
+    /// Nodes settled by the forward search in the order they were settled
+    pub settled_fwd: Vec<NodeIndex>,
+    /// Nodes settled by the backward search in the order they were settled
+    pub settled_bwd: Vec<NodeIndex>,
+    /// Edges relaxed by the forward search as `(source, target)`
+    pub relaxed_fwd: Vec<(NodeIndex, NodeIndex)>,
+    /// Edges relaxed by the backward search as `(source, target)` in the direction of the edge
+    pub relaxed_bwd: Vec<(NodeIndex, NodeIndex)>,
+    /// Node where the forward and backward search met on the shortest path
+    pub meeting_node: Option<NodeIndex>,
+}
+
+impl SearchSpace {
+    /// Total number of settled nodes of both search directions.
+    pub fn num_settled(&self) -> usize {
+        self.settled_fwd.len() + self.settled_bwd.len()
+    }
+
+    /// Total number of relaxed edges of both search directions.
+    pub fn num_relaxed(&self) -> usize {
+        self.relaxed_fwd.len() + self.relaxed_bwd.len()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.settled_fwd.clear();
+        self.settled_bwd.clear();
+        self.relaxed_fwd.clear();
+        self.relaxed_bwd.clear();
+        self.meeting_node = None;
+    }
+
+    pub(crate) fn settle(&mut self, node: NodeIndex, fwd: bool) {
+        if fwd {
+            self.settled_fwd.push(node);
+        } else {
+            self.settled_bwd.push(node);
+        }
+    }
+
+    pub(crate) fn relax(&mut self, source: NodeIndex, target: NodeIndex, fwd: bool) {
+        if fwd {
+            self.relaxed_fwd.push((source, target));
+        } else {
+            self.relaxed_bwd.push((source, target));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        contraction_strategy::{ContractionStrategy, CoreSize, UpdateStrategy},
+        graph::node_index,
+        node_contraction::NodeContractor,
+        search::{
+            landmarks::{LandmarkSelection, Landmarks},
+            *,
+        },
+        util::test_graphs::generate_complex_graph,
+    };
+
+    fn assert_recorded(algo: &mut dyn ShortestPathAlgorithm) {
+        for (s, t) in (0..11).flat_map(|s| (0..11).map(move |t| (node_index(s), node_index(t)))) {
+            algo.record_search_space(true);
+            let sp = algo.search(s, t);
+            let space = algo.search_space().unwrap();
+
+            assert_eq!(algo.stats().nodes_settled, space.num_settled());
+            // The core search of CoreALT may stop before settling a core source
+            if let Some(first) = space.settled_fwd.first() {
+                assert_eq!(s, *first);
+            }
+            assert_eq!(algo.meeting_node(), space.meeting_node);
+            if let (Some(sp), Some(meeting_node)) = (sp, space.meeting_node) {
+                assert!(sp.nodes.contains(&meeting_node));
+            }
+
+            algo.record_search_space(false);
+            algo.search(s, t);
+            assert!(algo.search_space().is_none());
+        }
+    }
+
+    #[test]
+    fn recorded_by_all_algorithms() {
+        let mut g = generate_complex_graph();
+        let landmarks = Landmarks::new(&g, 2, LandmarkSelection::Farthest);
+
+        assert_recorded(&mut Dijkstra::new(&g));
+        assert_recorded(&mut AStar::new(&g));
+        assert_recorded(&mut BidirDijkstra::new(&g));
+        assert_recorded(&mut BidirAStar::new(&g));
+        assert_recorded(&mut BidirAStar::with_landmarks(&g, &landmarks));
+
+        let strategy = UpdateStrategy::new().set_core_size(CoreSize::Nodes(3));
+        let overlay_graph = NodeContractor::new(&mut g.clone())
+            .run_with_strategy(ContractionStrategy::LazyUpdate(strategy));
+        assert_recorded(&mut CoreALT::new(&overlay_graph, &landmarks));
+
+        let overlay_graph = NodeContractor::new(&mut g).run();
+        assert_recorded(&mut CHSearch::new(&overlay_graph));
+    }
+
+    #[test]
+    fn nodes_are_settled_once() {
+        let g = generate_complex_graph();
+        let algos: [Box<dyn ShortestPathAlgorithm>; 2] =
+            [Box::new(Dijkstra::new(&g)), Box::new(AStar::new(&g))];
+
+        for mut algo in algos {
+            algo.record_search_space(true);
+            for s in 0..g.nodes.len() {
+                for t in 0..g.nodes.len() {
+                    algo.search(node_index(s), node_index(t));
+                    let space = algo.search_space().unwrap();
+
+                    let mut settled = space.settled_fwd.clone();
+                    settled.sort();
+                    settled.dedup();
+                    assert_eq!(space.settled_fwd.len(), settled.len());
+
+                    let mut relaxed = space.relaxed_fwd.clone();
+                    relaxed.sort();
+                    relaxed.dedup();
+                    assert_eq!(space.relaxed_fwd.len(), relaxed.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bidirectional_search_meets() {
+        let g = generate_complex_graph();
+        let mut bidir = BidirDijkstra::new(&g);
+        bidir.record_search_space(true);
+
+        let sp = ShortestPathAlgorithm::search(&mut bidir, node_index(0), node_index(10)).unwrap();
+        let space = bidir.search_space().unwrap();
+
+        assert!(!space.settled_bwd.is_empty());
+        assert!(!space.relaxed_fwd.is_empty());
+        assert!(!space.relaxed_bwd.is_empty());
+        assert!(sp.nodes.contains(&space.meeting_node.unwrap()));
+    }
+}
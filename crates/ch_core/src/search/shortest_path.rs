@@ -1,6 +1,10 @@
 //! Shortest path data structure.
 
-use crate::{constants::Weight, graph::NodeIndex};
+use crate::{
+    constants::Weight,
+    graph::{EdgeIndex, Graph, NodeIndex},
+    ways::WayInfo,
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ShortestPath {
@@ -12,4 +16,98 @@ impl ShortestPath {
     pub fn new(nodes: Vec<NodeIndex>, weight: Weight) -> Self {
         ShortestPath { nodes, weight }
     }
+
+    /// Returns the original edges between the consecutive nodes of the path together with
+    /// their OSM ways. Paths of a [`CHSearch`](crate::search::CHSearch) are already unpacked,
+    /// pass [`OverlayGraph::road_graph`](crate::overlay_graph::OverlayGraph::road_graph) as
+    /// `g`.
+    ///
+    /// Returns `None` if two consecutive nodes are not connected by an original edge of `g`.
+    pub fn segments<'a>(&self, g: &'a Graph) -> Option<Vec<Segment<'a>>> {
+        let num_edges = g.edges.len() - g.num_shortcuts;
+
+        self.nodes
+            .windows(2)
+            .map(|pair| {
+                let (source, target) = (pair[0], pair[1]);
+                // Parallel edges are possible, the search always takes the cheapest one
+                let (edge_idx, edge) = g
+                    .neighbors_outgoing(source)
+                    .filter(|(edge_idx, edge)| {
+                        edge_idx.index() < num_edges && edge.target == target
+                    })
+                    .min_by(|(_, a), (_, b)| a.weight.total_cmp(&b.weight))?;
+
+                Some(Segment {
+                    source,
+                    target,
+                    edge: edge_idx,
+                    weight: edge.weight,
+                    way: g.ways.way(edge_idx),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Original edge of a [`ShortestPath`], see [`ShortestPath::segments`]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Segment<'a> {
+    pub source: NodeIndex,
+    pub target: NodeIndex,
+    pub edge: EdgeIndex,
+    pub weight: Weight,
+    /// OSM way of the edge. `None` if the graph was not imported from OSM.
+    pub way: Option<&'a WayInfo>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        graph::node_index,
+        node_contraction::NodeContractor,
+        search::{CHSearch, Dijkstra},
+        util::test_graphs::{generate_complex_graph, minimal_osm_path},
+    };
+
+    use super::*;
+
+    #[test]
+    fn segments_of_contracted_graph() {
+        let mut g = generate_complex_graph();
+        let mut dijkstra = Dijkstra::new(&g);
+        let expected = dijkstra.search(node_index(0), node_index(8)).unwrap();
+        drop(dijkstra);
+
+        let overlay_graph = NodeContractor::new(&mut g).run();
+        let mut ch = CHSearch::new(&overlay_graph);
+        let path = ch.search(node_index(0), node_index(8)).unwrap();
+
+        let segments = path.segments(overlay_graph.road_graph()).unwrap();
+        assert_eq!(path.nodes.len() - 1, segments.len());
+        let weight: Weight = segments.iter().map(|segment| segment.weight).sum();
+        assert!((expected.weight - weight).abs() < 1e-9);
+        assert!(segments.iter().all(|segment| segment.way.is_none()));
+    }
+
+    #[test]
+    fn segments_have_ways() {
+        let path = minimal_osm_path();
+        let g = Graph::from_pbf(&path).unwrap();
+
+        let mut dijkstra = Dijkstra::new(&g);
+        let path = dijkstra.search(node_index(0), node_index(1)).unwrap();
+        let segments = path.segments(&g).unwrap();
+
+        assert_eq!(1, segments.len());
+        assert_eq!(Some(12), segments[0].way.map(|way| way.id));
+    }
+
+    #[test]
+    fn unconnected_nodes_have_no_segments() {
+        let g = generate_complex_graph();
+        let path = ShortestPath::new(vec![node_index(0), node_index(7)], 1.0);
+
+        assert_eq!(None, path.segments(&g));
+    }
 }
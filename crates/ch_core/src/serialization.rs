@@ -0,0 +1,740 @@
+//! Versioned binary file format for graphs.
+//!
+//! Every file starts with a fixed preamble followed by a bincode encoded [`FileHeader`] and
+//! the body, which is optionally compressed with zstd or lz4. The body is encoded, compressed
+//! and checksummed on the fly, so the whole file is never buffered in memory:
+//! ```text
+//! +----------------+
+//! | Magic          | 8 bytes, identifies the kind of file
+//! | Version        | u32 (little endian)
+//! | Header length  | u32 (little endian)
+//! +----------------+
+//! | FileHeader     | build metadata, compression, body length and checksum,
+//! |                | followed by zero padding up to the header length
+//! +----------------+
+//! | Body           |
+//! +----------------+
+//! ```
+//! The header is written last, once the length and the checksum of the body are known. The
+//! header length in the preamble reserves enough space for it.
+//!
+//! Overlay graph files written before the header was introduced (raw bincode without magic)
+//! are treated as version `0` and are still readable.
+//!
+//! # Versions
+//! - `0`: Raw bincode of the overlay graph without header
+//! - `1`: Header with build metadata and compression. The body contains the delta encoded
+//!   adjacency lists, the uncontracted core, the OSM ways of the original edges (see
+//!   [`crate::ways`]), the metric of the import and the tagged components
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Take, Write},
+    path::Path,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{contraction_params::ContractionParams, graph::EdgeIndex};
+
+/// Magic bytes of files containing an [`OverlayGraph`](crate::overlay_graph::OverlayGraph)
+pub const OVERLAY_GRAPH_MAGIC: [u8; 8] = *b"CHOVRLAY";
+
+/// Magic bytes of files containing a [`Graph`](crate::graph::Graph)
+pub const GRAPH_MAGIC: [u8; 8] = *b"CHGRAPH\0";
+
+/// Magic bytes of files containing [`Landmarks`](crate::search::landmarks::Landmarks)
+pub const LANDMARKS_MAGIC: [u8; 8] = *b"CHLNDMRK";
+
+/// Current version of the file format. Version `0` denotes legacy files without a header.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Compression level used for zstd
+const ZSTD_LEVEL: i32 = 3;
+
+/// Maximal length of the encoded [`FileHeader`]. The header is decoded before the checksum
+/// of the body can be verified, this bounds the memory a corrupted header can request.
+const MAX_HEADER_LEN: usize = 1 << 16;
+
+/// Errors which can occur while reading or writing a graph file.
+#[derive(Debug)]
+pub enum FormatError {
+    Io(std::io::Error),
+    /// The file does not start with the expected magic bytes and is no legacy file either
+    InvalidMagic,
+    /// The file was written by a newer version of this crate
+    UnsupportedVersion {
+        found: u32,
+        supported: u32,
+    },
+    /// The checksum of the body does not match the header
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// The file could not be decoded
+    Corrupted(String),
+    Encode(String),
+    /// The body could not be (de-)compressed
+    Compression(String),
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::Io(e) => write!(f, "I/O error: {}", e),
+            FormatError::InvalidMagic => write!(f, "Invalid magic bytes. Unknown file format"),
+            FormatError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "Unsupported format version {} (supported up to {}). File was written by a newer version",
+                found, supported
+            ),
+            FormatError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "Checksum mismatch (expected {:#x}, found {:#x}). File is corrupted",
+                expected, found
+            ),
+            FormatError::Corrupted(reason) => write!(f, "Failed to decode file: {}", reason),
+            FormatError::Encode(reason) => write!(f, "Failed to encode file: {}", reason),
+            FormatError::Compression(reason) => write!(f, "Compression error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FormatError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for FormatError {
+    fn from(e: std::io::Error) -> Self {
+        FormatError::Io(e)
+    }
+}
+
+/// Information about how a graph file was built.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildMetadata {
+    /// Name of the file the graph was parsed from, e.g. the pbf file
+    pub source: Option<String>,
+    /// Parameters used for the contraction
+    pub params: Option<ContractionParams>,
+    /// Creation time in seconds since the UNIX epoch
+    pub created: u64,
+    /// Fingerprint of the import options of the graph, see
+    /// [`PbfReader::fingerprint`](osm_reader::reader::PbfReader::fingerprint)
+    pub fingerprint: Option<u64>,
+}
+
+impl BuildMetadata {
+    /// Creates new metadata with the current time as creation time.
+    pub fn new(source: Option<String>, params: Option<ContractionParams>) -> Self {
+        BuildMetadata {
+            source,
+            params,
+            created: now(),
+            fingerprint: None,
+        }
+    }
+}
+
+impl Display for BuildMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "source: {}, created: {} (UNIX time), params: {:?}",
+            self.source.as_deref().unwrap_or("unknown"),
+            self.created,
+            self.params
+        )
+    }
+}
+
+/// Compression algorithm applied to the body of a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+    Lz4,
+}
+
+impl Compression {
+    /// Wraps `wtr` into a writer which compresses everything written to it
+    fn encoder<W: Write>(self, wtr: W) -> Result<Encoder<W>, FormatError> {
+        Ok(match self {
+            Compression::None => Encoder::None(wtr),
+            Compression::Zstd => Encoder::Zstd(
+                zstd::stream::write::Encoder::new(wtr, ZSTD_LEVEL)
+                    .map_err(|e| FormatError::Compression(e.to_string()))?,
+            ),
+            Compression::Lz4 => Encoder::Lz4(lz4_flex::frame::FrameEncoder::new(wtr)),
+        })
+    }
+
+    /// Wraps `rdr` into a reader which decompresses everything read from it
+    fn decoder<R: Read>(self, rdr: R) -> Result<Decoder<R>, FormatError> {
+        Ok(match self {
+            Compression::None => Decoder::None(rdr),
+            Compression::Zstd => Decoder::Zstd(
+                zstd::stream::read::Decoder::new(rdr)
+                    .map_err(|e| FormatError::Compression(e.to_string()))?,
+            ),
+            Compression::Lz4 => Decoder::Lz4(lz4_flex::frame::FrameDecoder::new(rdr)),
+        })
+    }
+}
+
+/// Streaming compression of the body, see [`Compression::encoder`]
+enum Encoder<W: Write> {
+    None(W),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Lz4(lz4_flex::frame::FrameEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Writes the end of the compressed stream and returns the inner writer
+    fn finish(self) -> Result<W, FormatError> {
+        match self {
+            Encoder::None(wtr) => Ok(wtr),
+            Encoder::Zstd(wtr) => wtr
+                .finish()
+                .map_err(|e| FormatError::Compression(e.to_string())),
+            Encoder::Lz4(wtr) => wtr
+                .finish()
+                .map_err(|e| FormatError::Compression(e.to_string())),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Encoder::None(wtr) => wtr.write(buf),
+            Encoder::Zstd(wtr) => wtr.write(buf),
+            Encoder::Lz4(wtr) => wtr.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Encoder::None(wtr) => wtr.flush(),
+            Encoder::Zstd(wtr) => wtr.flush(),
+            Encoder::Lz4(wtr) => wtr.flush(),
+        }
+    }
+}
+
+/// Streaming decompression of the body, see [`Compression::decoder`]
+enum Decoder<R: Read> {
+    None(R),
+    Zstd(zstd::stream::read::Decoder<'static, BufReader<R>>),
+    Lz4(lz4_flex::frame::FrameDecoder<R>),
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Decoder::None(rdr) => rdr.read(buf),
+            Decoder::Zstd(rdr) => rdr.read(buf),
+            Decoder::Lz4(rdr) => rdr.read(buf),
+        }
+    }
+}
+
+/// Computes the length and the CRC32 of everything written to the inner writer
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+    len: u64,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        ChecksumWriter {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.hasher.update(&buf[..len]);
+        self.len += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Computes the CRC32 of everything read from the inner reader
+struct ChecksumReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.hasher.update(&buf[..len]);
+        Ok(len)
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "zstd" => Ok(Compression::Zstd),
+            "lz4" => Ok(Compression::Lz4),
+            _ => Err(format!("Unknown compression: {}", s)),
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compression::None => write!(f, "none"),
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Lz4 => write!(f, "lz4"),
+        }
+    }
+}
+
+/// Header stored in front of the body of a graph file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileHeader {
+    /// Version the file was written with
+    #[serde(skip)]
+    pub version: u32,
+    pub metadata: BuildMetadata,
+    pub compression: Compression,
+    /// Length of the stored (compressed) body in bytes
+    pub body_len: u64,
+    /// CRC32 of the stored (compressed) body
+    pub checksum: u32,
+}
+
+/// Encodes every adjacency list as the differences between consecutive edge indices.
+///
+/// Neighboring edges usually have similar indices, so the differences are small and take
+/// only one or two bytes with the variable length integer encoding of bincode.
+pub(crate) fn delta_encode(lists: &[Vec<EdgeIndex>]) -> Vec<Vec<i64>> {
+    lists
+        .iter()
+        .map(|list| {
+            let mut prev = 0;
+            list.iter()
+                .map(|edge_idx| {
+                    let idx = edge_idx.index() as i64;
+                    let delta = idx - prev;
+                    prev = idx;
+                    delta
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Reverses [`delta_encode`]
+pub(crate) fn delta_decode(lists: Vec<Vec<i64>>) -> Vec<Vec<EdgeIndex>> {
+    lists
+        .into_iter()
+        .map(|list| {
+            let mut prev = 0;
+            list.into_iter()
+                .map(|delta| {
+                    prev += delta;
+                    EdgeIndex::new(prev as usize)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub(crate) fn encode_to_vec<T: Serialize>(value: &T) -> Result<Vec<u8>, FormatError> {
+    bincode::serde::encode_to_vec(value, bincode::config::standard())
+        .map_err(|e| FormatError::Encode(e.to_string()))
+}
+
+pub(crate) fn decode_from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, FormatError> {
+    let (value, _) = bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+        .map_err(|e| FormatError::Corrupted(e.to_string()))?;
+    Ok(value)
+}
+
+/// Writes `value` with a header containing `metadata` to `path`. The body is compressed
+/// with `compression`.
+///
+/// Returns the number of bytes written.
+pub(crate) fn write_file<T: Serialize>(
+    path: &Path,
+    magic: [u8; 8],
+    metadata: &BuildMetadata,
+    compression: Compression,
+    value: &T,
+) -> Result<usize, FormatError> {
+    // The placeholders take the most space in the variable length encoding, so the final
+    // header always fits into the reserved space
+    let mut header = FileHeader {
+        version: FORMAT_VERSION,
+        metadata: metadata.clone(),
+        compression,
+        body_len: u64::MAX,
+        checksum: u32::MAX,
+    };
+    let header_len = encode_to_vec(&header)?.len();
+
+    let mut wtr = BufWriter::new(File::create(path)?);
+    wtr.write_all(&magic)?;
+    wtr.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    wtr.write_all(&(header_len as u32).to_le_bytes())?;
+    wtr.write_all(&vec![0; header_len])?;
+
+    let mut body = BufWriter::new(compression.encoder(ChecksumWriter::new(&mut wtr))?);
+    bincode::serde::encode_into_std_write(value, &mut body, bincode::config::standard())
+        .map_err(|e| FormatError::Encode(e.to_string()))?;
+    let body = body.into_inner().map_err(|e| e.into_error())?.finish()?;
+    header.body_len = body.len;
+    header.checksum = body.hasher.finalize();
+
+    let mut header_bytes = encode_to_vec(&header)?;
+    header_bytes.resize(header_len, 0);
+    wtr.seek(SeekFrom::Start(16))?;
+    wtr.write_all(&header_bytes)?;
+    wtr.flush()?;
+
+    Ok(magic.len() + 8 + header_len + header.body_len as usize)
+}
+
+/// Reads the header of the file at `path` without decoding the body.
+///
+/// Legacy files without a header result in a header with version `0` and empty metadata.
+pub fn read_header(path: &Path, magic: [u8; 8]) -> Result<FileHeader, FormatError> {
+    let file_len = std::fs::metadata(path)?.len();
+    let mut rdr = BufReader::new(File::open(path)?);
+
+    Ok(read_header_from(&mut rdr, file_len, magic)?.unwrap_or_else(|| legacy_header(file_len)))
+}
+
+/// Reads the preamble and the header from the start of a file of `file_len` bytes. Returns
+/// `None` for legacy files.
+fn read_header_from(
+    rdr: &mut impl Read,
+    file_len: u64,
+    magic: [u8; 8],
+) -> Result<Option<FileHeader>, FormatError> {
+    let mut preamble = [0; 16];
+    let len = read_up_to(rdr, &mut preamble)?;
+
+    if len < preamble.len() || preamble[0..8] != magic {
+        return Ok(None);
+    }
+
+    let (version, header_len) = parse_preamble(&preamble)?;
+    if file_len < (preamble.len() + header_len) as u64 {
+        return Err(FormatError::Corrupted("File is truncated".to_string()));
+    }
+    let mut header_bytes = vec![0; header_len];
+    rdr.read_exact(&mut header_bytes)?;
+
+    parse_header(&header_bytes, version).map(Some)
+}
+
+/// Opens a file written by [`write_file`] and returns a reader for the decompressed body.
+///
+/// The layout of the body depends on [`FileHeader::version`]. Legacy files without a header
+/// are returned as version `0` with the whole file as body.
+pub(crate) fn read_file(path: &Path, magic: [u8; 8]) -> Result<BodyReader, FormatError> {
+    let file_len = std::fs::metadata(path)?.len();
+    let mut rdr = BufReader::new(File::open(path)?);
+
+    let Some(header) = read_header_from(&mut rdr, file_len, magic)? else {
+        warn!("Reading legacy file without header. Save it again to upgrade to the current format");
+        rdr.seek(SeekFrom::Start(0))?;
+        return BodyReader::new(legacy_header(file_len), rdr);
+    };
+
+    let body_len = file_len - rdr.stream_position()?;
+    if body_len != header.body_len {
+        return Err(FormatError::Corrupted(format!(
+            "Expected body of {} bytes, found {} bytes",
+            header.body_len, body_len
+        )));
+    }
+
+    // Length prefixes are trusted by bincode, a corrupted one could request gigabytes of
+    // memory. Verify the whole body before anything is decoded.
+    let body_start = rdr.stream_position()?;
+    let mut body = ChecksumReader {
+        inner: (&mut rdr).take(header.body_len),
+        hasher: crc32fast::Hasher::new(),
+    };
+    std::io::copy(&mut body, &mut std::io::sink())?;
+    let checksum = body.hasher.finalize();
+    if checksum != header.checksum {
+        return Err(FormatError::ChecksumMismatch {
+            expected: header.checksum,
+            found: checksum,
+        });
+    }
+    rdr.seek(SeekFrom::Start(body_start))?;
+
+    debug!(
+        "Decompressing body of {} bytes ({})",
+        body_len, header.compression
+    );
+    BodyReader::new(header, rdr)
+}
+
+/// Decompresses and decodes the body of a file returned by [`read_file`] while it is read.
+///
+/// The checksum of the body was already verified by [`read_file`].
+pub(crate) struct BodyReader {
+    header: FileHeader,
+    rdr: BufReader<Decoder<Take<BufReader<File>>>>,
+}
+
+impl BodyReader {
+    fn new(header: FileHeader, rdr: BufReader<File>) -> Result<Self, FormatError> {
+        let body = rdr.take(header.body_len);
+        let rdr = BufReader::new(header.compression.decoder(body)?);
+        Ok(BodyReader { header, rdr })
+    }
+
+    pub(crate) fn header(&self) -> &FileHeader {
+        &self.header
+    }
+
+    /// Decodes the next value of the body. Legacy files which can't be decoded are most
+    /// likely no graph files at all and reported as [`FormatError::InvalidMagic`].
+    pub(crate) fn decode<T: DeserializeOwned>(&mut self) -> Result<T, FormatError> {
+        match bincode::serde::decode_from_std_read(&mut self.rdr, bincode::config::standard()) {
+            Ok(value) => Ok(value),
+            Err(_) if self.header.version == 0 => Err(FormatError::InvalidMagic),
+            Err(e) => Err(FormatError::Corrupted(e.to_string())),
+        }
+    }
+
+    /// Returns the header once all values are decoded
+    pub(crate) fn finish(self) -> Result<FileHeader, FormatError> {
+        Ok(self.header)
+    }
+}
+
+fn legacy_header(body_len: u64) -> FileHeader {
+    FileHeader {
+        version: 0,
+        metadata: BuildMetadata::default(),
+        compression: Compression::None,
+        body_len,
+        checksum: 0,
+    }
+}
+
+fn parse_preamble(preamble: &[u8]) -> Result<(u32, usize), FormatError> {
+    let version = u32::from_le_bytes(preamble[8..12].try_into().unwrap());
+    let header_len = u32::from_le_bytes(preamble[12..16].try_into().unwrap()) as usize;
+
+    if version > FORMAT_VERSION || version == 0 {
+        return Err(FormatError::UnsupportedVersion {
+            found: version,
+            supported: FORMAT_VERSION,
+        });
+    }
+    if header_len > MAX_HEADER_LEN {
+        return Err(FormatError::Corrupted(format!(
+            "Header of {} bytes exceeds the maximum of {} bytes",
+            header_len, MAX_HEADER_LEN
+        )));
+    }
+    Ok((version, header_len))
+}
+
+fn parse_header(bytes: &[u8], version: u32) -> Result<FileHeader, FormatError> {
+    let config = bincode::config::standard().with_limit::<MAX_HEADER_LEN>();
+    let (header, _) = bincode::serde::decode_from_slice::<FileHeader, _>(bytes, config)
+        .map_err(|e| FormatError::Corrupted(e.to_string()))?;
+    Ok(FileHeader { version, ..header })
+}
+
+fn read_up_to(rdr: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match rdr.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAGIC: [u8; 8] = *b"TESTFILE";
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        values: Vec<u64>,
+        name: String,
+    }
+
+    fn payload() -> Payload {
+        Payload {
+            values: (0..100).collect(),
+            name: "payload".to_string(),
+        }
+    }
+
+    fn read(path: &Path) -> Result<(FileHeader, Payload), FormatError> {
+        let mut body = read_file(path, MAGIC)?;
+        let value = body.decode()?;
+        Ok((body.finish()?, value))
+    }
+
+    #[test]
+    fn write_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = &dir.path().join("serialization_roundtrip.bin");
+        let metadata = BuildMetadata::new(
+            Some("test.osm.pbf".to_string()),
+            Some(ContractionParams::default()),
+        );
+
+        for compression in [Compression::None, Compression::Zstd, Compression::Lz4] {
+            write_file(path, MAGIC, &metadata, compression, &payload()).unwrap();
+
+            let (header, value) = read(path).unwrap();
+            assert_eq!(header.version, FORMAT_VERSION);
+            assert_eq!(header.compression, compression);
+            assert_eq!(header.metadata, metadata);
+            assert_eq!(value, payload());
+
+            assert_eq!(read_header(path, MAGIC).unwrap(), header);
+        }
+    }
+
+    #[test]
+    fn read_legacy_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = &dir.path().join("serialization_legacy.bin");
+        std::fs::write(path, encode_to_vec(&payload()).unwrap()).unwrap();
+
+        let (header, value) = read(path).unwrap();
+        assert_eq!(header.version, 0);
+        assert_eq!(value, payload());
+    }
+
+    #[test]
+    fn read_values_in_sequence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = &dir.path().join("serialization_sequence.bin");
+
+        for compression in [Compression::None, Compression::Zstd, Compression::Lz4] {
+            let values = (payload(), 42u64, "rest of the body".to_string());
+            let len =
+                write_file(path, MAGIC, &BuildMetadata::default(), compression, &values).unwrap();
+            assert_eq!(len as u64, std::fs::metadata(path).unwrap().len());
+
+            let mut body = read_file(path, MAGIC).unwrap();
+            assert_eq!(payload(), body.decode::<Payload>().unwrap());
+            assert_eq!(42, body.decode::<u64>().unwrap());
+            // The rest of the body doesn't have to be decoded
+            let header = body.finish().unwrap();
+            assert_eq!(header, read_header(path, MAGIC).unwrap());
+        }
+    }
+
+    #[test]
+    fn delta_encoding() {
+        let lists = vec![
+            vec![EdgeIndex::new(10), EdgeIndex::new(12), EdgeIndex::new(3)],
+            vec![],
+            vec![EdgeIndex::new(100_000)],
+        ];
+
+        let encoded = delta_encode(&lists);
+        assert_eq!(encoded, vec![vec![10, 2, -9], vec![], vec![100_000]]);
+        assert_eq!(delta_decode(encoded), lists);
+    }
+
+    #[test]
+    fn detect_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = &dir.path().join("serialization_errors.bin");
+        write_file(
+            path,
+            MAGIC,
+            &BuildMetadata::default(),
+            Compression::Zstd,
+            &payload(),
+        )
+        .unwrap();
+        let mut bytes = std::fs::read(path).unwrap();
+
+        // Corrupted body
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        std::fs::write(path, &bytes).unwrap();
+        assert!(matches!(
+            read(path),
+            Err(FormatError::ChecksumMismatch { .. })
+        ));
+
+        // Corrupted length prefix, which would request a huge allocation if decoded
+        let name = "a name which is long enough to be overwritten".to_string();
+        write_file(
+            path,
+            MAGIC,
+            &BuildMetadata::default(),
+            Compression::None,
+            &name,
+        )
+        .unwrap();
+        let mut corrupted = std::fs::read(path).unwrap();
+        let body_start = corrupted.len() - encode_to_vec(&name).unwrap().len();
+        corrupted[body_start] = 253;
+        corrupted[body_start + 1..body_start + 9].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        std::fs::write(path, &corrupted).unwrap();
+        assert!(matches!(
+            read_file(path, MAGIC).map(|_| ()),
+            Err(FormatError::ChecksumMismatch { .. })
+        ));
+
+        // Newer version
+        bytes[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        std::fs::write(path, &bytes).unwrap();
+        assert!(matches!(
+            read(path),
+            Err(FormatError::UnsupportedVersion { .. })
+        ));
+
+        // Unknown file
+        std::fs::write(path, b"definitely not a graph").unwrap();
+        assert!(matches!(read(path), Err(FormatError::InvalidMagic)));
+    }
+}
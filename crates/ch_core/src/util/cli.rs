@@ -1,10 +1,18 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
+use osm_reader::{
+    clip::{BoundingBox, Clip, CrossingWays, Polygon, Region},
+    geo::EarthModel,
+    profile::SpeedProfile,
+    reader::PbfReader,
+};
 
 use crate::{
+    components::ComponentFilter,
     contraction_params::ContractionParams,
     contraction_strategy::{ContractionStrategy, UpdateStrategy},
+    graph::Graph,
     prelude::PriorityParams,
 };
 
@@ -40,14 +48,70 @@ struct Cli {
     /// Enable periodic updates
     #[arg(short, long, value_name = "periodic")]
     periodic: bool,
+
+    /// Handling of strongly connected components. Possible values are "none", "largest" (keep
+    /// only the largest component) and "tag" (skip queries between components)
+    #[arg(long, value_name = "filter", default_value = "none")]
+    components: ComponentFilter,
+
+    /// TOML or JSON file with the speeds used for the weights, see osm_reader::profile
+    #[arg(long, value_name = "file")]
+    profile: Option<PathBuf>,
+
+    /// Model of the earth for the lengths of the roads and the A* estimates. Possible values
+    /// are "haversine", "vincenty" and "karney"
+    #[arg(long, value_name = "model", default_value = "haversine")]
+    earth_model: EarthModel,
+
+    /// Only import the roads inside a bounding box "min_lat,min_lon,max_lat,max_lon" or the
+    /// polygon of a .poly or GeoJSON file
+    #[arg(long, value_name = "region", value_parser = parse_region)]
+    clip: Option<Region>,
+
+    /// Handling of roads crossing the border of the clip region. Possible values are "keep",
+    /// "split" and "drop"
+    #[arg(long, value_name = "handling", default_value = "split")]
+    crossing_ways: CrossingWays,
+}
+
+/// Parses a bounding box or reads the polygon of a file. Comma separated numbers are always
+/// parsed as bounding box.
+fn parse_region(value: &str) -> anyhow::Result<Region> {
+    let is_numeric = |part: &str| part.trim().parse::<f64>().is_ok();
+    if value.contains(',') && value.split(',').all(is_numeric) {
+        Ok(value.parse::<BoundingBox>()?.into())
+    } else {
+        Ok(Polygon::from_file(Path::new(value))?.into())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Cfg<'a> {
     pub pbf_file: PathBuf,
     pub simplify: bool,
+    pub components: ComponentFilter,
     pub params: ContractionParams,
     pub strategy: ContractionStrategy<'a>,
+    pub profile: Option<PathBuf>,
+    pub earth_model: EarthModel,
+    pub clip: Option<Clip>,
+}
+
+impl Cfg<'_> {
+    /// Reads the graph from the pbf file, simplified, clipped and with the speed profile if
+    /// requested
+    pub fn read_graph(&self) -> anyhow::Result<Graph> {
+        let mut reader = PbfReader::new(&self.pbf_file)
+            .simplify(self.simplify)
+            .earth_model(self.earth_model);
+        if let Some(path) = &self.profile {
+            reader = reader.profile(SpeedProfile::from_file(path)?);
+        }
+        if let Some(clip) = &self.clip {
+            reader = reader.clip(clip.clone());
+        }
+        Graph::from_pbf_reader(&reader)
+    }
 }
 
 pub fn parse<'a>() -> Cfg<'a> {
@@ -91,5 +155,28 @@ pub fn parse<'a>() -> Cfg<'a> {
         params: ContractionParams::new().priority_params(priority_params),
         strategy: ContractionStrategy::LazyUpdate(lazy_strategy),
         simplify: !cli.raw_graph,
+        components: cli.components,
+        profile: cli.profile,
+        earth_model: cli.earth_model,
+        clip: cli
+            .clip
+            .map(|region| Clip::new(region).crossing_ways(cli.crossing_ways)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bbox_region() {
+        assert!(matches!(
+            parse_region("48.0,11.0,49.0,12.0"),
+            Ok(Region::BoundingBox(_))
+        ));
+
+        let err = parse_region("48.0,11.0,49.0").unwrap_err().to_string();
+        assert!(err.contains("min_lat,min_lon,max_lat,max_lon"), "{}", err);
+        assert!(parse_region("munich.poly").is_err());
     }
 }
@@ -1,15 +1,58 @@
+use osm_reader::{geo::EarthModel, RoadType};
+use serde::{Deserialize, Serialize};
+
 use crate::{constants::Weight, graph::Node};
 
-pub fn straight_line(src: &Node, dst: &Node) -> Weight {
-    // Calculate the distance between two nodes using the Haversine formula
-    let lat1 = src.lat.to_radians();
-    let lat2 = dst.lat.to_radians();
-    let lon1 = src.lon.to_radians();
-    let lon2 = dst.lon.to_radians();
-    let a = (lat2 - lat1) / 2.0;
-    let b = (lon2 - lon1) / 2.0;
-    let c = a.sin().powi(2) + lat1.cos() * lat2.cos() * b.sin().powi(2);
-    let d = 2.0 * c.sqrt().asin();
-
-    6371.0 * d / 110.0 * 3600.0 // Umrechnung in Sekunden
+/// Distances between nodes and the straight line estimate of the travel time between them.
+///
+/// The estimate is only a lower bound of the real travel time, i.e. A* stays admissible, if
+/// the weights of the edges were calculated with the same [`EarthModel`] and no edge is faster
+/// than [`Metric::max_speed`]. Graphs imported from OSM take both from the import, see
+/// [`RoadGraph::get_earth_model`](osm_reader::RoadGraph::get_earth_model).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Metric {
+    pub earth_model: EarthModel,
+    /// Highest speed in km/h of all edges
+    pub max_speed: f64,
+}
+
+impl Metric {
+    pub fn new(earth_model: EarthModel, max_speed: f64) -> Self {
+        Metric {
+            earth_model,
+            max_speed,
+        }
+    }
+
+    /// Distance between two nodes in metres
+    pub fn distance(&self, src: &Node, dst: &Node) -> f64 {
+        self.earth_model
+            .distance(src.lat, src.lon, dst.lat, dst.lon)
+    }
+
+    /// Lower bound of the travel time in seconds from `src` to `dst`
+    pub fn straight_line(&self, src: &Node, dst: &Node) -> Weight {
+        self.distance(src, dst) / (self.max_speed / 3.6)
+    }
+}
+
+/// Haversine distances and the speeds of [`RoadType::velocity`], like the default import
+impl Default for Metric {
+    fn default() -> Self {
+        let max_speed = RoadType::ALL
+            .iter()
+            .map(|road_type| road_type.velocity())
+            .fold(0.0, f64::max);
+        Metric::new(EarthModel::default(), max_speed)
+    }
+}
+
+/// Initial bearing from `src` to `dst` in degrees, clockwise from north in `[0, 360)`
+pub fn bearing(src: &Node, dst: &Node) -> f64 {
+    let (lat1, lat2) = (src.lat.to_radians(), dst.lat.to_radians());
+    let d_lon = (dst.lon - src.lon).to_radians();
+    let y = d_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+
+    y.atan2(x).to_degrees().rem_euclid(360.0)
 }
@@ -74,6 +74,12 @@ pub fn generate_simple_graph() -> Graph {
     g
 }
 
+/// Path of the minimal OSM XML file in the test data of `osm_reader`
+#[cfg(test)]
+pub fn minimal_osm_path() -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../osm_reader/test_data/minimal.osm")
+}
+
 pub fn graph_vaterstetten() -> Graph {
     let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
         .join("../osm_reader/test_data/vaterstetten_pp.osm.pbf");
@@ -102,6 +108,14 @@ pub fn graph_saarland() -> Graph {
     Graph::from_pbf_with_simplification(&path).unwrap()
 }
 
+/// Same as [`graph_saarland`], but the parsed graph is cached next to the pbf file.
+pub fn graph_saarland_cached() -> Graph {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../osm_reader/test_data/saarland_pp.osm.pbf");
+
+    Graph::from_pbf_cached(&path, true).unwrap()
+}
+
 pub fn graph_saarland_raw() -> Graph {
     let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
         .join("../osm_reader/test_data/saarland_pp.osm.pbf");
@@ -0,0 +1,885 @@
+//! Verification of contraction hierarchies against plain Dijkstra.
+//!
+//! Runs queries with [`CHSearch`] on an [`OverlayGraph`] and with [`Dijkstra`] on its
+//! [`road_graph`] and compares the results. Every pair is generated from its own seed, so a
+//! mismatch can be reproduced with `Verification::new(&g).seed(mismatch.seed).num_pairs(1)`.
+//!
+//! [`validate`] checks the structure of the hierarchy itself, e.g. after loading it from CSV.
+//!
+//! # Examples
+//! ```
+//! use ch_core::prelude::*;
+//! use ch_core::util::test_graphs::generate_complex_graph;
+//! use ch_core::verification::{PairSelection, Verification};
+//!
+//! let mut g = generate_complex_graph();
+//! let overlay_graph = NodeContractor::new(&mut g).run();
+//!
+//! let report = Verification::new(&overlay_graph)
+//!     .num_pairs(100)
+//!     .selection(PairSelection::DijkstraRank)
+//!     .run();
+//!
+//! assert!(report.is_ok(), "{}", report);
+//! ```
+//!
+//! [`CHSearch`]: crate::search::CHSearch
+//! [`Dijkstra`]: crate::search::Dijkstra
+//! [`road_graph`]: crate::overlay_graph::OverlayGraph::road_graph
+use std::{fmt::Display, str::FromStr};
+
+use indicatif::ProgressBar;
+use log::{info, warn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    constants::Weight,
+    graph::{EdgeIndex, Graph, NodeIndex},
+    overlay_graph::OverlayGraph,
+    search::{shortest_path::ShortestPath, CHSearch, Dijkstra},
+};
+
+/// Default number of verified pairs
+pub const DEFAULT_NUM_PAIRS: usize = 1_000;
+
+/// Default seed of the first pair
+pub const DEFAULT_SEED: u64 = 187;
+
+/// Default tolerance for comparing weights
+pub const DEFAULT_TOLERANCE: Weight = 1e-6;
+
+/// How source and target of a pair are selected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PairSelection {
+    /// Source and target are chosen uniformly at random.
+    #[default]
+    Random,
+    /// The source is chosen at random, the target is the node with Dijkstra rank `2^k` for a
+    /// random `k >= 1`. Covers short and long queries evenly.
+    DijkstraRank,
+}
+
+impl FromStr for PairSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "random" => Ok(PairSelection::Random),
+            "rank" => Ok(PairSelection::DijkstraRank),
+            _ => Err(format!("Unknown pair selection: {}", s)),
+        }
+    }
+}
+
+impl Display for PairSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PairSelection::Random => write!(f, "random"),
+            PairSelection::DijkstraRank => write!(f, "rank"),
+        }
+    }
+}
+
+/// Verifies the queries on an [`OverlayGraph`]. See the [module documentation](self).
+#[derive(Clone)]
+pub struct Verification<'a> {
+    g: &'a OverlayGraph,
+    num_pairs: usize,
+    seed: u64,
+    tolerance: Weight,
+    selection: PairSelection,
+}
+
+impl<'a> Verification<'a> {
+    pub fn new(g: &'a OverlayGraph) -> Self {
+        Verification {
+            g,
+            num_pairs: DEFAULT_NUM_PAIRS,
+            seed: DEFAULT_SEED,
+            tolerance: DEFAULT_TOLERANCE,
+            selection: PairSelection::default(),
+        }
+    }
+
+    /// Number of verified pairs
+    pub fn num_pairs(mut self, num_pairs: usize) -> Self {
+        self.num_pairs = num_pairs;
+        self
+    }
+
+    /// Seed of the first pair. Pair `i` uses the seed `seed + i`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Maximal absolute difference of two weights considered equal
+    pub fn tolerance(mut self, tolerance: Weight) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// How source and target of the pairs are selected, see [`PairSelection`]
+    pub fn selection(mut self, selection: PairSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Runs all queries and collects the mismatches.
+    pub fn run(&self) -> VerificationReport {
+        info!(
+            "BEGIN verifying {} {} pairs, seed: {}",
+            self.num_pairs, self.selection, self.seed
+        );
+        if self.g.has_core() {
+            warn!("The overlay graph has an uncontracted core, CHSearch will miss paths");
+        }
+
+        let road_graph = self.g.road_graph();
+        let mut report = VerificationReport {
+            num_pairs: self.num_pairs,
+            ..Default::default()
+        };
+        if road_graph.nodes.is_empty() {
+            return report;
+        }
+
+        let mut ch = CHSearch::new(self.g);
+        let mut dijkstra = Dijkstra::new(road_graph);
+
+        let pb = ProgressBar::new(self.num_pairs as u64);
+        for i in 0..self.num_pairs as u64 {
+            let seed = self.seed.wrapping_add(i);
+            let (source, target) = self.pair(&mut dijkstra, seed);
+
+            let sp_ch = ch.search(source, target);
+            let sp_dijkstra = dijkstra.search(source, target);
+            if sp_dijkstra.is_some() {
+                report.num_paths += 1;
+            }
+
+            for issue in self.compare(road_graph, source, target, sp_ch, sp_dijkstra) {
+                report.mismatches.push(Mismatch {
+                    source,
+                    target,
+                    seed,
+                    issue,
+                });
+            }
+            pb.inc(1);
+        }
+        pb.finish_and_clear();
+
+        info!(
+            "FINISHED verification: {} mismatches",
+            report.mismatches.len()
+        );
+        report
+    }
+
+    /// Generates the pair with the given seed
+    fn pair(&self, dijkstra: &mut Dijkstra, seed: u64) -> (NodeIndex, NodeIndex) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let num_nodes = self.g.road_graph().nodes.len();
+        let source = NodeIndex::new(rng.gen_range(0..num_nodes));
+
+        match self.selection {
+            PairSelection::Random => (source, NodeIndex::new(rng.gen_range(0..num_nodes))),
+            PairSelection::DijkstraRank => {
+                let tree = dijkstra.one_to_all(source);
+                // Rank 2^0 is the source itself, only isolated sources are paired with themselves
+                let max_exp = tree.len().ilog2();
+                if max_exp == 0 {
+                    return (source, source);
+                }
+                let rank = 1usize << rng.gen_range(1..=max_exp);
+                (source, tree.node_with_rank(rank).unwrap())
+            }
+        }
+    }
+
+    fn compare(
+        &self,
+        road_graph: &Graph,
+        source: NodeIndex,
+        target: NodeIndex,
+        sp_ch: Option<ShortestPath>,
+        sp_dijkstra: Option<ShortestPath>,
+    ) -> Vec<Issue> {
+        let (sp_ch, sp_dijkstra) = match (sp_ch, sp_dijkstra) {
+            (None, None) => return vec![],
+            (Some(_), None) => return vec![Issue::MissingPath { found_by_ch: true }],
+            (None, Some(_)) => return vec![Issue::MissingPath { found_by_ch: false }],
+            (Some(sp_ch), Some(sp_dijkstra)) => (sp_ch, sp_dijkstra),
+        };
+
+        let mut issues = vec![];
+        if (sp_ch.weight - sp_dijkstra.weight).abs() > self.tolerance {
+            issues.push(Issue::WeightMismatch {
+                ch: sp_ch.weight,
+                dijkstra: sp_dijkstra.weight,
+            });
+        }
+
+        if sp_ch.nodes.first() != Some(&source) || sp_ch.nodes.last() != Some(&target) {
+            issues.push(Issue::InvalidEndpoints);
+        }
+
+        let mut sum = 0.0;
+        for pair in sp_ch.nodes.windows(2) {
+            match lightest_edge(road_graph, pair[0], pair[1]) {
+                Some(weight) => sum += weight,
+                None => issues.push(Issue::MissingEdge {
+                    from: pair[0],
+                    to: pair[1],
+                }),
+            }
+        }
+        if (sum - sp_ch.weight).abs() > self.tolerance {
+            issues.push(Issue::WeightSumMismatch {
+                sum,
+                reported: sp_ch.weight,
+            });
+        }
+
+        issues
+    }
+}
+
+/// Weight of the lightest original edge from `from` to `to`
+fn lightest_edge(g: &Graph, from: NodeIndex, to: NodeIndex) -> Option<Weight> {
+    let num_edges = g.edges.len() - g.num_shortcuts;
+    g.neighbors_outgoing(from)
+        .filter(|(edge_idx, edge)| edge_idx.index() < num_edges && edge.target == to)
+        .map(|(_, edge)| edge.weight)
+        .reduce(Weight::min)
+}
+
+/// Problem found for a single pair.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// Only one of both algorithms found a path
+    MissingPath { found_by_ch: bool },
+    /// The weights of both paths differ by more than the tolerance
+    WeightMismatch { ch: Weight, dijkstra: Weight },
+    /// The CH path doesn't start at the source or doesn't end at the target
+    InvalidEndpoints,
+    /// Two consecutive nodes of the CH path aren't connected by an edge
+    MissingEdge { from: NodeIndex, to: NodeIndex },
+    /// The edge weights of the CH path don't sum up to its weight
+    WeightSumMismatch { sum: Weight, reported: Weight },
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::MissingPath { found_by_ch: true } => write!(f, "only CH found a path"),
+            Issue::MissingPath { found_by_ch: false } => write!(f, "only Dijkstra found a path"),
+            Issue::WeightMismatch { ch, dijkstra } => {
+                write!(f, "weight {} differs from Dijkstra's {}", ch, dijkstra)
+            }
+            Issue::InvalidEndpoints => write!(f, "path doesn't connect source and target"),
+            Issue::MissingEdge { from, to } => {
+                write!(f, "no edge from {} to {}", from.index(), to.index())
+            }
+            Issue::WeightSumMismatch { sum, reported } => {
+                write!(f, "edge weights sum up to {} instead of {}", sum, reported)
+            }
+        }
+    }
+}
+
+/// Issue found for the pair generated from `seed`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub source: NodeIndex,
+    pub target: NodeIndex,
+    pub seed: u64,
+    pub issue: Issue,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> {} (seed {}): {}",
+            self.source.index(),
+            self.target.index(),
+            self.seed,
+            self.issue
+        )
+    }
+}
+
+/// Result of a [`Verification`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerificationReport {
+    /// Number of verified pairs
+    pub num_pairs: usize,
+    /// Number of pairs connected by a path according to Dijkstra
+    pub num_paths: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl VerificationReport {
+    /// `true` if no mismatches were found
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl Display for VerificationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Verified {} pairs ({} connected): {} mismatches",
+            self.num_pairs,
+            self.num_paths,
+            self.mismatches.len()
+        )?;
+        for mismatch in &self.mismatches {
+            writeln!(f, "  {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+/// Structural problem found by [`validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    /// `node_order` doesn't contain a rank for every node. The ranks of the edges aren't checked.
+    NodeOrderLength { expected: usize, found: usize },
+    /// `edges_fwd` or `edges_bwd` doesn't contain an adjacency list for every node
+    AdjacencyLength {
+        fwd: bool,
+        expected: usize,
+        found: usize,
+    },
+    /// An adjacency list references an edge or node that doesn't exist
+    DanglingEdge {
+        node: NodeIndex,
+        edge_idx: EdgeIndex,
+        fwd: bool,
+    },
+    /// An upward edge doesn't start at `node` or a downward edge doesn't end at `node`
+    MisplacedEdge {
+        node: NodeIndex,
+        edge_idx: EdgeIndex,
+        fwd: bool,
+    },
+    /// An upward edge leads to a node with a lower rank or a downward edge comes from one
+    RankViolation {
+        node: NodeIndex,
+        edge_idx: EdgeIndex,
+        fwd: bool,
+    },
+    /// The number of entries in `shortcuts` differs from the number of shortcuts in the graph
+    ShortcutCount { expected: usize, found: usize },
+    /// The shortcut or one of the edges it replaces doesn't exist
+    DanglingShortcut { shortcut: EdgeIndex },
+    /// Unpacking the shortcut leads back to the shortcut itself
+    CyclicShortcut { shortcut: EdgeIndex },
+    /// The unpacked edges don't form a path from the source to the target of the shortcut
+    DisconnectedShortcut { shortcut: EdgeIndex },
+    /// The weights of the unpacked edges don't sum up to the weight of the shortcut
+    ShortcutWeight {
+        shortcut: EdgeIndex,
+        sum: Weight,
+        weight: Weight,
+    },
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let direction = |fwd: &bool| if *fwd { "upward" } else { "downward" };
+        match self {
+            ValidationIssue::NodeOrderLength { expected, found } => {
+                write!(f, "{} of {} nodes have a rank", found, expected)
+            }
+            ValidationIssue::AdjacencyLength {
+                fwd,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} of {} nodes have {} edges",
+                found,
+                expected,
+                direction(fwd)
+            ),
+            ValidationIssue::DanglingEdge {
+                node,
+                edge_idx,
+                fwd,
+            } => write!(
+                f,
+                "{} edge {} of node {} doesn't exist",
+                direction(fwd),
+                edge_idx.index(),
+                node.index()
+            ),
+            ValidationIssue::MisplacedEdge {
+                node,
+                edge_idx,
+                fwd,
+            } => write!(
+                f,
+                "{} edge {} isn't incident to node {}",
+                direction(fwd),
+                edge_idx.index(),
+                node.index()
+            ),
+            ValidationIssue::RankViolation {
+                node,
+                edge_idx,
+                fwd,
+            } => write!(
+                f,
+                "{} edge {} of node {} connects a lower ranked node",
+                direction(fwd),
+                edge_idx.index(),
+                node.index()
+            ),
+            ValidationIssue::ShortcutCount { expected, found } => {
+                write!(f, "{} of {} shortcuts can be unpacked", found, expected)
+            }
+            ValidationIssue::DanglingShortcut { shortcut } => {
+                write!(f, "shortcut {} references a missing edge", shortcut.index())
+            }
+            ValidationIssue::CyclicShortcut { shortcut } => {
+                write!(f, "shortcut {} unpacks to itself", shortcut.index())
+            }
+            ValidationIssue::DisconnectedShortcut { shortcut } => write!(
+                f,
+                "shortcut {} doesn't unpack to a path between its endpoints",
+                shortcut.index()
+            ),
+            ValidationIssue::ShortcutWeight {
+                shortcut,
+                sum,
+                weight,
+            } => write!(
+                f,
+                "shortcut {} unpacks to weight {} instead of {}",
+                shortcut.index(),
+                sum,
+                weight
+            ),
+        }
+    }
+}
+
+/// Result of [`validate`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// `true` if no issues were found
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Validated overlay graph: {} issues", self.issues.len())?;
+        for issue in &self.issues {
+            writeln!(f, "  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the structure of the hierarchy without running any queries:
+/// - every upward edge leads to and every downward edge comes from a higher ranked node
+/// - every shortcut unpacks to a path of original edges between its endpoints whose weights
+///   sum up to the weight of the shortcut
+/// - all edge indices point to existing edges
+///
+/// Used by [`OverlayGraph::validate`].
+pub fn validate(g: &OverlayGraph) -> ValidationReport {
+    let road_graph = g.road_graph();
+    let num_nodes = road_graph.nodes.len();
+    let mut report = ValidationReport::default();
+
+    let has_ranks = g.node_order.len() == num_nodes;
+    if !has_ranks {
+        report.issues.push(ValidationIssue::NodeOrderLength {
+            expected: num_nodes,
+            found: g.node_order.len(),
+        });
+    }
+
+    for (fwd, adjacency) in [(true, &g.edges_fwd), (false, &g.edges_bwd)] {
+        if adjacency.len() != num_nodes {
+            report.issues.push(ValidationIssue::AdjacencyLength {
+                fwd,
+                expected: num_nodes,
+                found: adjacency.len(),
+            });
+        }
+
+        for (node, edges) in adjacency.iter().enumerate() {
+            let node = NodeIndex::new(node);
+            for &edge_idx in edges {
+                let edge = road_graph.edges.get(edge_idx.index()).filter(|edge| {
+                    edge.source.index() < num_nodes && edge.target.index() < num_nodes
+                });
+                let Some(edge) = edge else {
+                    report.issues.push(ValidationIssue::DanglingEdge {
+                        node,
+                        edge_idx,
+                        fwd,
+                    });
+                    continue;
+                };
+
+                let (own, other) = match fwd {
+                    true => (edge.source, edge.target),
+                    false => (edge.target, edge.source),
+                };
+                if own != node {
+                    report.issues.push(ValidationIssue::MisplacedEdge {
+                        node,
+                        edge_idx,
+                        fwd,
+                    });
+                } else if has_ranks && g.node_order[other.index()] <= g.node_order[node.index()] {
+                    report.issues.push(ValidationIssue::RankViolation {
+                        node,
+                        edge_idx,
+                        fwd,
+                    });
+                }
+            }
+        }
+    }
+
+    if g.shortcuts.len() != road_graph.num_shortcuts {
+        report.issues.push(ValidationIssue::ShortcutCount {
+            expected: road_graph.num_shortcuts,
+            found: g.shortcuts.len(),
+        });
+    }
+    validate_shortcuts(g, &mut report.issues);
+
+    report
+}
+
+/// Unpacks every shortcut once, bottom up, and remembers the source, target and weight of the
+/// path of original edges it represents. Invalid shortcuts are remembered as `None` so they are
+/// only reported once and not again for every shortcut containing them.
+fn validate_shortcuts(g: &OverlayGraph, issues: &mut Vec<ValidationIssue>) {
+    let mut unpacked: FxHashMap<EdgeIndex, Option<(NodeIndex, NodeIndex, Weight)>> =
+        FxHashMap::default();
+
+    let mut shortcuts: Vec<_> = g.shortcuts.keys().copied().collect();
+    shortcuts.sort_unstable();
+    for shortcut in shortcuts {
+        // Depth-first traversal, `ancestors` contains the shortcuts currently being unpacked
+        let mut stack = vec![shortcut];
+        let mut ancestors = FxHashSet::default();
+        while let Some(&edge_idx) = stack.last() {
+            if unpacked.contains_key(&edge_idx) {
+                stack.pop();
+                continue;
+            }
+            ancestors.insert(edge_idx);
+
+            let replaces = g.shortcuts[&edge_idx];
+            if replaces.iter().any(|e| ancestors.contains(e)) {
+                issues.push(ValidationIssue::CyclicShortcut { shortcut: edge_idx });
+                ancestors.remove(&edge_idx);
+                unpacked.insert(edge_idx, None);
+                continue;
+            }
+
+            let pending: Vec<_> = replaces
+                .into_iter()
+                .filter(|e| g.shortcuts.contains_key(e) && !unpacked.contains_key(e))
+                .collect();
+            if pending.is_empty() {
+                ancestors.remove(&edge_idx);
+                let path = unpack_shortcut(g, edge_idx, replaces, &unpacked, issues);
+                unpacked.insert(edge_idx, path);
+            } else {
+                stack.extend(pending);
+            }
+        }
+    }
+}
+
+/// Checks a single shortcut whose replaced shortcuts are already unpacked
+fn unpack_shortcut(
+    g: &OverlayGraph,
+    shortcut: EdgeIndex,
+    [incoming, outgoing]: [EdgeIndex; 2],
+    unpacked: &FxHashMap<EdgeIndex, Option<(NodeIndex, NodeIndex, Weight)>>,
+    issues: &mut Vec<ValidationIssue>,
+) -> Option<(NodeIndex, NodeIndex, Weight)> {
+    let edges = &g.road_graph().edges;
+    if [shortcut, incoming, outgoing]
+        .iter()
+        .any(|edge_idx| edge_idx.index() >= edges.len())
+    {
+        issues.push(ValidationIssue::DanglingShortcut { shortcut });
+        return None;
+    }
+
+    let path = |edge_idx: EdgeIndex| match unpacked.get(&edge_idx) {
+        Some(path) => *path,
+        None => {
+            let edge = &edges[edge_idx.index()];
+            Some((edge.source, edge.target, edge.weight))
+        }
+    };
+    // Invalid replaced shortcuts have been reported already
+    let (first, second) = (path(incoming)?, path(outgoing)?);
+
+    let edge = &edges[shortcut.index()];
+    if first.0 != edge.source || first.1 != second.0 || second.1 != edge.target {
+        issues.push(ValidationIssue::DisconnectedShortcut { shortcut });
+        return None;
+    }
+
+    let sum = first.2 + second.2;
+    if (sum - edge.weight).abs() > DEFAULT_TOLERANCE {
+        issues.push(ValidationIssue::ShortcutWeight {
+            shortcut,
+            sum,
+            weight: edge.weight,
+        });
+    }
+    Some((edge.source, edge.target, sum))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        contraction_strategy::{ContractionStrategy, CoreSize, UpdateStrategy},
+        node_contraction::NodeContractor,
+        util::test_graphs::generate_complex_graph,
+    };
+
+    use super::*;
+
+    #[test]
+    fn contracted_graph_is_correct() {
+        let mut g = generate_complex_graph();
+        let overlay_graph = NodeContractor::new(&mut g).run();
+
+        for selection in [PairSelection::Random, PairSelection::DijkstraRank] {
+            let report = Verification::new(&overlay_graph)
+                .num_pairs(200)
+                .selection(selection)
+                .run();
+
+            assert!(report.is_ok(), "{}", report);
+            assert_eq!(200, report.num_pairs);
+            assert_eq!(200, report.num_paths);
+        }
+    }
+
+    #[test]
+    fn rank_pairs_skip_the_source() {
+        let mut g = generate_complex_graph();
+        let overlay_graph = NodeContractor::new(&mut g).run();
+        let verification = Verification::new(&overlay_graph).selection(PairSelection::DijkstraRank);
+        let mut dijkstra = Dijkstra::new(overlay_graph.road_graph());
+
+        for seed in 0..100 {
+            let (source, target) = verification.pair(&mut dijkstra, seed);
+            if dijkstra.one_to_all(source).len() > 1 {
+                assert_ne!(source, target);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_reproducible_mismatches() {
+        let mut g = generate_complex_graph();
+        let mut overlay_graph = NodeContractor::new(&mut g).run();
+        // Break the hierarchy by removing the upward edges of a node
+        let node = overlay_graph
+            .edges_fwd
+            .iter()
+            .position(|edges| !edges.is_empty())
+            .unwrap();
+        overlay_graph.edges_fwd[node].clear();
+
+        let report = Verification::new(&overlay_graph).num_pairs(200).run();
+        assert!(!report.is_ok());
+
+        for mismatch in &report.mismatches {
+            let rerun = Verification::new(&overlay_graph)
+                .seed(mismatch.seed)
+                .num_pairs(1)
+                .run();
+            assert!(rerun.mismatches.contains(mismatch));
+        }
+    }
+
+    #[test]
+    fn detects_invalid_paths() {
+        let mut g = generate_complex_graph();
+        let overlay_graph = NodeContractor::new(&mut g).run();
+        let verification = Verification::new(&overlay_graph);
+
+        let (a, b, j) = (NodeIndex::new(0), NodeIndex::new(1), NodeIndex::new(9));
+        let sp = ShortestPath::new(vec![a, j], 7.0);
+        let issues = verification.compare(
+            overlay_graph.road_graph(),
+            a,
+            b,
+            Some(sp.clone()),
+            Some(ShortestPath::new(vec![a, b], 3.0)),
+        );
+
+        assert_eq!(
+            vec![
+                Issue::WeightMismatch {
+                    ch: 7.0,
+                    dijkstra: 3.0
+                },
+                Issue::InvalidEndpoints,
+                Issue::MissingEdge { from: a, to: j },
+                Issue::WeightSumMismatch {
+                    sum: 0.0,
+                    reported: 7.0
+                },
+            ],
+            issues
+        );
+    }
+
+    /// Contracts the hub J first, which requires shortcuts
+    fn contract_hub_first() -> OverlayGraph {
+        let mut g = generate_complex_graph();
+        let order: Vec<_> = [9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 10]
+            .into_iter()
+            .map(NodeIndex::new)
+            .collect();
+        NodeContractor::new(&mut g).run_with_order(&order)
+    }
+
+    #[test]
+    fn contracted_graph_is_valid() {
+        let overlay_graph = contract_hub_first();
+        assert!(!overlay_graph.shortcuts.is_empty());
+        let report = overlay_graph.validate();
+        assert!(report.is_ok(), "{}", report);
+
+        let mut g = generate_complex_graph();
+        let overlay_graph = NodeContractor::new(&mut g.clone()).run();
+        let report = overlay_graph.validate();
+        assert!(report.is_ok(), "{}", report);
+
+        let strategy = UpdateStrategy::new().set_core_size(CoreSize::Nodes(3));
+        let overlay_graph = NodeContractor::new(&mut g)
+            .run_with_strategy(ContractionStrategy::LazyUpdate(strategy));
+        let report = overlay_graph.validate();
+        assert!(report.is_ok(), "{}", report);
+    }
+
+    #[test]
+    fn detects_invalid_edges() {
+        let mut g = generate_complex_graph();
+        let mut overlay_graph = NodeContractor::new(&mut g).run();
+
+        let node = overlay_graph
+            .edges_fwd
+            .iter()
+            .position(|edges| !edges.is_empty())
+            .unwrap();
+        let edge_idx = overlay_graph.edges_fwd[node][0];
+        let target = overlay_graph.edge(edge_idx).target;
+        let node = NodeIndex::new(node);
+
+        // Rank the node above its upward neighbor
+        overlay_graph.node_order[node.index()] = overlay_graph.node_order[target.index()] + 1;
+        let report = overlay_graph.validate();
+        assert!(report.issues.contains(&ValidationIssue::RankViolation {
+            node,
+            edge_idx,
+            fwd: true
+        }));
+
+        overlay_graph.edges_bwd[node.index()].push(edge_idx);
+        overlay_graph.edges_bwd[0].push(EdgeIndex::new(usize::MAX));
+        let report = overlay_graph.validate();
+        assert!(report.issues.contains(&ValidationIssue::MisplacedEdge {
+            node,
+            edge_idx,
+            fwd: false
+        }));
+        assert!(report.issues.contains(&ValidationIssue::DanglingEdge {
+            node: NodeIndex::new(0),
+            edge_idx: EdgeIndex::new(usize::MAX),
+            fwd: false
+        }));
+    }
+
+    #[test]
+    fn detects_invalid_shortcuts() {
+        let overlay_graph = contract_hub_first();
+        let shortcut = *overlay_graph.shortcuts.keys().min().unwrap();
+        let [incoming, outgoing] = overlay_graph.shortcuts[&shortcut];
+
+        let mut road_graph = overlay_graph.road_graph().clone();
+        road_graph.edges[shortcut.index()].weight += 1.0;
+        let mut broken = OverlayGraph::new(
+            overlay_graph.edges_fwd.clone(),
+            overlay_graph.edges_bwd.clone(),
+            road_graph,
+            overlay_graph.shortcuts.clone(),
+            overlay_graph.node_order.clone(),
+        );
+        let weight = broken.edge(shortcut).weight;
+        assert_eq!(
+            vec![ValidationIssue::ShortcutWeight {
+                shortcut,
+                sum: weight - 1.0,
+                weight
+            }],
+            broken.validate().issues
+        );
+
+        broken.shortcuts.insert(shortcut, [outgoing, incoming]);
+        assert!(broken
+            .validate()
+            .issues
+            .contains(&ValidationIssue::DisconnectedShortcut { shortcut }));
+
+        broken.shortcuts.insert(shortcut, [shortcut, outgoing]);
+        assert!(broken
+            .validate()
+            .issues
+            .contains(&ValidationIssue::CyclicShortcut { shortcut }));
+
+        broken
+            .shortcuts
+            .insert(shortcut, [incoming, EdgeIndex::new(usize::MAX)]);
+        assert!(broken
+            .validate()
+            .issues
+            .contains(&ValidationIssue::DanglingShortcut { shortcut }));
+    }
+
+    #[test]
+    fn missing_ranks_are_reported() {
+        let mut g = generate_complex_graph();
+        let mut overlay_graph = NodeContractor::new(&mut g).run();
+        overlay_graph.node_order.clear();
+
+        assert_eq!(
+            vec![ValidationIssue::NodeOrderLength {
+                expected: 11,
+                found: 0
+            }],
+            overlay_graph.validate().issues
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Side table with the OSM ways the original edges of a [`Graph`] were created from.
+//!
+//! An [`Edge`](crate::graph::Edge) only knows its source, target and weight. The way id, the
+//! `name` and `ref` tags and the [`RoadType`] are stored separately, so the edges stay small
+//! for the searches. Shortcuts have no way, they are unpacked into original edges first, see
+//! [`ShortestPath::segments`](crate::search::shortest_path::ShortestPath::segments).
+//!
+//! # Examples
+//! ```no_run
+//! use std::path::Path;
+//! use ch_core::prelude::*;
+//!
+//! let g = Graph::from_pbf(Path::new("path/to/pbf/file.osm.pbf")).unwrap();
+//! let mut dijkstra = search::Dijkstra::new(&g);
+//! let path = dijkstra.search(node_index(3), node_index(20)).unwrap();
+//!
+//! for segment in path.segments(&g).unwrap() {
+//!     if let Some(way) = segment.way {
+//!         println!("{:?} ({})", way.name, way.road_type);
+//!     }
+//! }
+//! ```
+use serde::{Deserialize, Serialize};
+
+pub use osm_reader::{RoadType, WayInfo};
+
+use crate::graph::EdgeIndex;
+
+/// Marks edges without a way
+const NO_WAY: u32 = u32::MAX;
+
+/// Ways of the original edges of a graph. Every way is stored once, the edges refer to it by
+/// its index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WayTable {
+    ways: Vec<WayInfo>,
+    /// Index into `ways` for every edge. Edges without a way and shortcuts are either
+    /// `NO_WAY` or behind the end of the vector.
+    edge_ways: Vec<u32>,
+}
+
+impl WayTable {
+    /// Adds a way to the table and returns its index
+    pub fn add_way(&mut self, way: WayInfo) -> usize {
+        self.ways.push(way);
+        self.ways.len() - 1
+    }
+
+    /// Sets the way of `edge_idx` to the way with the index `way_idx`
+    pub fn set(&mut self, edge_idx: EdgeIndex, way_idx: usize) {
+        let edge_idx = edge_idx.index();
+        if edge_idx >= self.edge_ways.len() {
+            self.edge_ways.resize(edge_idx + 1, NO_WAY);
+        }
+        self.edge_ways[edge_idx] = way_idx as u32;
+    }
+
+    /// Index of the way of `edge_idx`
+    pub fn way_idx(&self, edge_idx: EdgeIndex) -> Option<usize> {
+        match self.edge_ways.get(edge_idx.index()) {
+            Some(&way_idx) if way_idx != NO_WAY => Some(way_idx as usize),
+            _ => None,
+        }
+    }
+
+    /// Way the edge was created from. Returns `None` for shortcuts and graphs which were not
+    /// imported from OSM.
+    pub fn way(&self, edge_idx: EdgeIndex) -> Option<&WayInfo> {
+        self.way_idx(edge_idx).map(|way_idx| &self.ways[way_idx])
+    }
+
+    /// Copy of the table with all ways, but without edges
+    pub(crate) fn without_edges(&self) -> WayTable {
+        WayTable {
+            ways: self.ways.clone(),
+            edge_ways: Vec::new(),
+        }
+    }
+
+    /// All ways of the table
+    pub fn ways(&self) -> &[WayInfo] {
+        &self.ways
+    }
+
+    /// Number of ways
+    pub fn len(&self) -> usize {
+        self.ways.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ways.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn way(id: i64, name: &str) -> WayInfo {
+        WayInfo {
+            id,
+            name: Some(name.to_string()),
+            reference: None,
+            road_type: RoadType::Residential,
+            roundabout: false,
+        }
+    }
+
+    #[test]
+    fn edges_share_ways() {
+        let mut table = WayTable::default();
+        let a = table.add_way(way(10, "Hauptstraße"));
+        let b = table.add_way(way(11, "Bahnhofstraße"));
+        table.set(EdgeIndex::new(0), a);
+        table.set(EdgeIndex::new(1), a);
+        table.set(EdgeIndex::new(4), b);
+
+        assert_eq!(2, table.len());
+        assert_eq!(Some(10), table.way(EdgeIndex::new(1)).map(|way| way.id));
+        assert_eq!(Some(11), table.way(EdgeIndex::new(4)).map(|way| way.id));
+        // Gaps and edges behind the end have no way
+        assert_eq!(None, table.way(EdgeIndex::new(2)));
+        assert_eq!(None, table.way(EdgeIndex::new(5)));
+    }
+
+    #[test]
+    fn road_types_are_encoded_by_index() {
+        // bincode encodes enums by variant index, so renaming the variants for speed profiles
+        // doesn't change graph files
+        let config = bincode::config::standard();
+        for (index, road_type) in RoadType::ALL.into_iter().enumerate() {
+            let bytes = bincode::serde::encode_to_vec(road_type, config).unwrap();
+            assert_eq!(
+                bincode::serde::encode_to_vec(index as u32, config).unwrap(),
+                bytes
+            );
+            let (decoded, _): (RoadType, _) =
+                bincode::serde::decode_from_slice(&bytes, config).unwrap();
+            assert_eq!(road_type, decoded);
+        }
+    }
+}
@@ -1,13 +1,12 @@
 use ch_core::{
     graph::{Graph, Node, NodeIndex},
     overlay_graph::OverlayGraph,
-    search::shortest_path::ShortestPath,
+    search::{shortest_path::ShortestPath, SearchSpace, ShortestPathAlgorithm},
 };
 
 use macroquad::prelude::*;
 
 use crossbeam_channel::{Receiver, Sender};
-use rustc_hash::FxHashSet;
 use std::f32::{MAX, MIN};
 
 use crate::{
@@ -18,8 +17,23 @@ use crate::{
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct SearchResult {
     pub sp: ShortestPath,
-    pub settled_fwd: Option<FxHashSet<NodeIndex>>,
-    pub settled_bwd: Option<FxHashSet<NodeIndex>>,
+    pub search_space: SearchSpace,
+}
+
+impl SearchResult {
+    /// Runs `algo` and records its search space
+    pub(crate) fn run(
+        algo: &mut dyn ShortestPathAlgorithm,
+        source: NodeIndex,
+        target: NodeIndex,
+    ) -> Option<Self> {
+        algo.record_search_space(true);
+        let sp = algo.search(source, target)?;
+        Some(Self {
+            sp,
+            search_space: algo.search_space().cloned().unwrap_or_default(),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -28,6 +42,7 @@ pub(crate) struct GraphViewOptions {
     pub draw_nodes: bool,
     pub draw_graph_upward: bool,
     pub draw_graph_downward: bool,
+    pub draw_relaxed_edges: bool,
     pub draw_shortest_path: bool,
     pub search_result: Option<SearchResult>,
     pub node_radius: f32,
@@ -40,6 +55,7 @@ impl Default for GraphViewOptions {
             draw_nodes: false,
             draw_graph_upward: false,
             draw_graph_downward: false,
+            draw_relaxed_edges: false,
             draw_shortest_path: true,
             search_result: None,
             node_radius: 2.0,
@@ -155,41 +171,44 @@ impl<'a> GraphView<'a> {
         }
 
         // Draw shortest path, Upward and Downward graph
-        if let Some(SearchResult {
-            sp,
-            settled_fwd,
-            settled_bwd,
-        }) = &self.options.search_result
-        {
+        if let Some(SearchResult { sp, search_space }) = &self.options.search_result {
             let scale = self.scale();
 
+            // Draw relaxed edges
+            if self.options.draw_relaxed_edges {
+                let color = COLOR_THEME.lock().unwrap().graph_up_color();
+                for (source, target) in &search_space.relaxed_fwd {
+                    self.draw_edge(*source, *target, scale, color);
+                }
+                let color = COLOR_THEME.lock().unwrap().graph_down_color();
+                for (source, target) in &search_space.relaxed_bwd {
+                    self.draw_edge(*source, *target, scale, color);
+                }
+            }
+
             // Draw upward graph
             if self.options.draw_graph_upward {
-                if let Some(settled_fwd) = settled_fwd {
-                    for node_idx in settled_fwd {
-                        let node = self.g.node(*node_idx).unwrap();
-                        self.draw_node(
-                            node,
-                            scale,
-                            self.options.node_radius,
-                            COLOR_THEME.lock().unwrap().graph_up_color(),
-                        );
-                    }
+                for node_idx in &search_space.settled_fwd {
+                    let node = self.g.node(*node_idx).unwrap();
+                    self.draw_node(
+                        node,
+                        scale,
+                        self.options.node_radius,
+                        COLOR_THEME.lock().unwrap().graph_up_color(),
+                    );
                 }
             }
 
             // Draw downward graph
             if self.options.draw_graph_downward {
-                if let Some(settled_bwd) = settled_bwd {
-                    for node_idx in settled_bwd {
-                        let node = self.g.node(*node_idx).unwrap();
-                        self.draw_node(
-                            node,
-                            scale,
-                            self.options.node_radius,
-                            COLOR_THEME.lock().unwrap().graph_down_color(),
-                        );
-                    }
+                for node_idx in &search_space.settled_bwd {
+                    let node = self.g.node(*node_idx).unwrap();
+                    self.draw_node(
+                        node,
+                        scale,
+                        self.options.node_radius,
+                        COLOR_THEME.lock().unwrap().graph_down_color(),
+                    );
                 }
             }
 
@@ -213,6 +232,16 @@ impl<'a> GraphView<'a> {
                         COLOR_THEME.lock().unwrap().sp_color(),
                     );
                 }
+
+                // Draw meeting node of bidirectional searches
+                if let Some(node_idx) = search_space.meeting_node {
+                    self.draw_node(
+                        self.g.node(node_idx).unwrap(),
+                        scale,
+                        4.0,
+                        COLOR_THEME.lock().unwrap().sp_color(),
+                    );
+                }
             }
         }
         // Draw start and target node
@@ -346,6 +375,16 @@ impl<'a> GraphView<'a> {
         log::debug!("Rendered edges: {}", rendered_edges);
     }
 
+    fn draw_edge(&self, source: NodeIndex, target: NodeIndex, scale: f32, color: Color) {
+        let from = node_to_vec(self.g.node(source).unwrap());
+        let to = node_to_vec(self.g.node(target).unwrap());
+
+        let from = (from - self.rect.point()) * scale;
+        let to = (to - self.rect.point()) * scale;
+
+        draw_line(from.x, from.y, to.x, to.y, 1.5, color);
+    }
+
     fn draw_nodes(&self) {
         let scale = self.scale();
 
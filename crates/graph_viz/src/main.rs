@@ -1,6 +1,6 @@
-use std::{path::Path, sync::Mutex};
+use std::sync::Mutex;
 
-use ch_core::{graph::Graph, node_contraction::NodeContractor, util::cli};
+use ch_core::{node_contraction::NodeContractor, util::cli};
 use color_theme::ActiveTheme;
 use crossbeam_channel::bounded;
 use egui::{Style, Visuals};
@@ -90,13 +90,8 @@ async fn main() {
     let (tx_contraction, rx_contraction) = bounded(1);
 
     std::thread::spawn(move || {
-        let pbf_path = cfg.pbf_file;
-
-        let mut g = if cfg.simplify {
-            Graph::from_pbf_with_simplification(Path::new(&pbf_path)).unwrap()
-        } else {
-            Graph::from_pbf(Path::new(&pbf_path)).unwrap()
-        };
+        let mut g = cfg.read_graph().unwrap();
+        g.filter_components(cfg.components);
 
         let mut node_contractor = NodeContractor::new_with_params(&mut g, cfg.params);
 
@@ -1,10 +1,7 @@
 use ch_core::{
     graph::NodeIndex,
     overlay_graph::OverlayGraph,
-    search::{
-        astar::AStar, bidir_dijkstra::BidirDijkstra, ch_search::CHSearch, dijkstra::Dijkstra,
-    },
-    util::math::straight_line,
+    search::{AStar, BidirDijkstra, CHSearch, Dijkstra},
 };
 use crossbeam_channel::{Receiver, Sender};
 use egui::{CollapsingHeader, Context, Slider, Window};
@@ -31,6 +28,8 @@ pub(crate) struct UserInputWidget<'g> {
 
     options: GraphViewOptions,
     dark_theme: bool,
+    /// Enter and show OSM node ids instead of node indices
+    osm_ids: bool,
 }
 
 impl<'g> UserInputWidget<'g> {
@@ -56,6 +55,26 @@ impl<'g> UserInputWidget<'g> {
             tx_search,
 
             dark_theme: true,
+            osm_ids: false,
+        }
+    }
+
+    /// Parses the node entered as `text`
+    fn parse_node(&self, text: &str) -> Option<NodeIndex> {
+        let id = text.parse::<usize>().ok()?;
+        if self.osm_ids {
+            self.overlay_graph.node_by_osm_id(id)
+        } else {
+            Some(NodeIndex::new(id))
+        }
+    }
+
+    /// Text of the input field for `node`
+    fn node_text(&self, node: Option<NodeIndex>) -> String {
+        match node {
+            Some(node) if self.osm_ids => self.overlay_graph.osm_id(node).unwrap().to_string(),
+            Some(node) => node.index().to_string(),
+            None => "".to_string(),
         }
     }
 }
@@ -65,11 +84,11 @@ impl<'g> super::MyWidget for UserInputWidget<'g> {
         if let Ok((source, target)) = self.rx_search.try_recv() {
             match (source, target) {
                 (Some(s), None) => {
-                    self.source_text = s.index().to_string();
+                    self.source_text = self.node_text(Some(s));
                     self.source_node = Some(s);
                 }
                 (None, Some(t)) => {
-                    self.target_text = t.index().to_string();
+                    self.target_text = self.node_text(Some(t));
                     self.target_node = Some(t);
                 }
                 _ => unreachable!(),
@@ -78,14 +97,12 @@ impl<'g> super::MyWidget for UserInputWidget<'g> {
 
         if is_key_pressed(KeyCode::F) && self.source_node.is_some() && self.target_node.is_some() {
             let mut bidir_search = CHSearch::new(self.overlay_graph);
-            if let Some(sp) =
-                bidir_search.search(self.source_node.unwrap(), self.target_node.unwrap())
-            {
-                self.options.search_result = Some(SearchResult {
-                    sp,
-                    settled_fwd: Some(bidir_search.settled_fwd),
-                    settled_bwd: Some(bidir_search.settled_bwd),
-                });
+            if let Some(result) = SearchResult::run(
+                &mut bidir_search,
+                self.source_node.unwrap(),
+                self.target_node.unwrap(),
+            ) {
+                self.options.search_result = Some(result);
             }
         }
 
@@ -105,6 +122,7 @@ impl<'g> super::MyWidget for UserInputWidget<'g> {
                     &mut self.options.draw_graph_downward,
                     "Draw Search Space Down",
                 );
+                ui.checkbox(&mut self.options.draw_relaxed_edges, "Draw Relaxed Edges");
                 // ui.checkbox(
                 //     &mut self.options.draw_top_important_nodes,
                 //     "Draw Most Important Nodes",
@@ -130,6 +148,11 @@ impl<'g> super::MyWidget for UserInputWidget<'g> {
             CollapsingHeader::new("Search")
                 .default_open(true)
                 .show(ui, |ui| {
+                    if ui.checkbox(&mut self.osm_ids, "OSM ids").changed() {
+                        self.source_text = self.node_text(self.source_node);
+                        self.target_text = self.node_text(self.target_node);
+                    }
+
                     // Input fields for start and end node
                     ui.horizontal(|ui| {
                         ui.label("Source Node");
@@ -142,8 +165,8 @@ impl<'g> super::MyWidget for UserInputWidget<'g> {
                             || response.lost_focus()
                             || response.clicked_elsewhere()
                         {
-                            if let Ok(source) = self.source_text.parse::<usize>() {
-                                self.source_node = Some(NodeIndex::new(source));
+                            if let Some(source) = self.parse_node(&self.source_text) {
+                                self.source_node = Some(source);
                             } else {
                                 self.source_text = "".to_string();
                                 self.source_node = None;
@@ -162,8 +185,8 @@ impl<'g> super::MyWidget for UserInputWidget<'g> {
                             || response.lost_focus()
                             || response.clicked_elsewhere()
                         {
-                            if let Ok(target) = self.target_text.parse::<usize>() {
-                                self.target_node = Some(NodeIndex::new(target));
+                            if let Some(target) = self.parse_node(&self.target_text) {
+                                self.target_node = Some(target);
                             } else {
                                 self.target_text = "".to_string();
                                 self.target_node = None;
@@ -184,14 +207,12 @@ impl<'g> super::MyWidget for UserInputWidget<'g> {
                             log::debug!("Search button clicked.");
 
                             let mut bidir_search = CHSearch::new(self.overlay_graph);
-                            if let Some(sp) = bidir_search
-                                .search(self.source_node.unwrap(), self.target_node.unwrap())
-                            {
-                                self.options.search_result = Some(SearchResult {
-                                    sp,
-                                    settled_fwd: Some(bidir_search.settled_fwd),
-                                    settled_bwd: Some(bidir_search.settled_bwd),
-                                });
+                            if let Some(result) = SearchResult::run(
+                                &mut bidir_search,
+                                self.source_node.unwrap(),
+                                self.target_node.unwrap(),
+                            ) {
+                                self.options.search_result = Some(result);
                             }
                         }
                         if ui
@@ -205,14 +226,12 @@ impl<'g> super::MyWidget for UserInputWidget<'g> {
                             log::debug!("Dijk button clicked.");
 
                             let mut dijk_search = Dijkstra::new(self.overlay_graph.road_graph());
-                            if let Some(sp) = dijk_search
-                                .search(self.source_node.unwrap(), self.target_node.unwrap())
-                            {
-                                self.options.search_result = Some(SearchResult {
-                                    sp,
-                                    settled_fwd: Some(dijk_search.nodes_settled),
-                                    settled_bwd: None,
-                                });
+                            if let Some(result) = SearchResult::run(
+                                &mut dijk_search,
+                                self.source_node.unwrap(),
+                                self.target_node.unwrap(),
+                            ) {
+                                self.options.search_result = Some(result);
                             }
                         }
                         if ui
@@ -226,16 +245,12 @@ impl<'g> super::MyWidget for UserInputWidget<'g> {
                             log::debug!("AStar button clicked.");
 
                             let mut astar_search = AStar::new(self.overlay_graph.road_graph());
-                            if let Some(sp) = astar_search.search(
+                            if let Some(result) = SearchResult::run(
+                                &mut astar_search,
                                 self.source_node.unwrap(),
                                 self.target_node.unwrap(),
-                                straight_line,
                             ) {
-                                self.options.search_result = Some(SearchResult {
-                                    sp,
-                                    settled_fwd: Some(astar_search.nodes_settled),
-                                    settled_bwd: None,
-                                });
+                                self.options.search_result = Some(result);
                             }
                         }
                         if ui
@@ -250,14 +265,12 @@ impl<'g> super::MyWidget for UserInputWidget<'g> {
 
                             let mut bdir_djik_search =
                                 BidirDijkstra::new(self.overlay_graph.road_graph());
-                            if let Some(sp) = bdir_djik_search
-                                .search(self.source_node.unwrap(), self.target_node.unwrap())
-                            {
-                                self.options.search_result = Some(SearchResult {
-                                    sp,
-                                    settled_fwd: Some(bdir_djik_search.settled_fwd),
-                                    settled_bwd: Some(bdir_djik_search.settled_bwd),
-                                });
+                            if let Some(result) = SearchResult::run(
+                                &mut bdir_djik_search,
+                                self.source_node.unwrap(),
+                                self.target_node.unwrap(),
+                            ) {
+                                self.options.search_result = Some(result);
                             }
                         }
                     });
@@ -1,25 +1,35 @@
-use std::{fs::File, path::PathBuf};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use osm_reader::RoadGraph;
+use osm_reader::{reader::PbfReader, RoadGraph};
 use std::io::Write;
 
 fn main() -> std::io::Result<()> {
     let args = std::env::args().skip(1).filter(|p| p.ends_with(".pbf"));
 
     let mut file = File::create("simplify.csv")?;
-    writeln!(&mut file, "file,nodes,edges,nodes_simple,edges_simple")?;
+    writeln!(
+        &mut file,
+        "file,nodes,edges,nodes_simple,edges_simple,sequential_ms,parallel_ms"
+    )?;
 
     for pbf_path in args {
         let path_buf = PathBuf::from(&pbf_path);
         let ((nodes, edges), (nodes_simple, edges_simple)) = run_bench(&pbf_path);
+        let (sequential, parallel) = run_parallel_bench(&path_buf);
         writeln!(
             &mut file,
-            "{},{},{},{},{}",
+            "{},{},{},{},{},{},{}",
             path_buf.iter().last().unwrap().to_str().unwrap(),
             nodes,
             edges,
             nodes_simple,
-            edges_simple
+            edges_simple,
+            sequential.as_millis(),
+            parallel.as_millis()
         )?;
     }
 
@@ -48,3 +58,26 @@ fn run_bench<P: Into<PathBuf>>(pbf_path: P) -> ((usize, usize), (usize, usize))
         (g_simple.get_nodes().len(), g_simple.get_arcs().len()),
     )
 }
+
+/// Times the simplified import on a single thread and on all available threads
+fn run_parallel_bench(path: &Path) -> (Duration, Duration) {
+    let read = |parallel: bool| {
+        let now = Instant::now();
+        let g = PbfReader::new(path)
+            .simplify(true)
+            .parallel(parallel)
+            .read()
+            .unwrap();
+        (now.elapsed(), g.get_nodes().len(), g.get_arcs().len())
+    };
+
+    let (sequential, nodes, arcs) = read(false);
+    let (parallel, nodes_parallel, arcs_parallel) = read(true);
+    assert_eq!((nodes, arcs), (nodes_parallel, arcs_parallel));
+
+    println!(
+        "Import: sequential {:?}, parallel {:?}",
+        sequential, parallel
+    );
+    (sequential, parallel)
+}
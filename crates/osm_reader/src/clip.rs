@@ -0,0 +1,443 @@
+//! Clipping of road networks to a bounding box or a polygon.
+//!
+//! Polygons are read from [Osmosis poly files](https://wiki.openstreetmap.org/wiki/Osmosis/Polygon_Filter_File_Format)
+//! or GeoJSON files containing a `Polygon` or `MultiPolygon`.
+//!
+//! # Examples
+//! ```
+//! use osm_reader::clip::{BoundingBox, Clip, CrossingWays};
+//!
+//! // Munich
+//! let bbox: BoundingBox = "48.06,11.36,48.25,11.72".parse().unwrap();
+//! let clip = Clip::new(bbox).crossing_ways(CrossingWays::Keep);
+//! assert!(clip.region().contains(48.137, 11.575));
+//! ```
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{bail, Context};
+use rustc_hash::FxHashMap;
+use serde_json::Value;
+
+/// Rectangle given by its south-west and north-east corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    pub fn new(min_lat: f64, min_lon: f64, max_lat: f64, max_lon: f64) -> Self {
+        BoundingBox {
+            min_lat,
+            min_lon,
+            max_lat,
+            max_lon,
+        }
+    }
+
+    /// Smallest bounding box containing all `points` given as `[lat, lon]`
+    fn around<'a>(points: impl Iterator<Item = &'a [f64; 2]>) -> Self {
+        points.fold(
+            BoundingBox::new(f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |bbox, [lat, lon]| BoundingBox {
+                min_lat: bbox.min_lat.min(*lat),
+                min_lon: bbox.min_lon.min(*lon),
+                max_lat: bbox.max_lat.max(*lat),
+                max_lon: bbox.max_lon.max(*lon),
+            },
+        )
+    }
+
+    /// Returns true if the point lies inside or on the border of the box
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        (self.min_lat..=self.max_lat).contains(&lat) && (self.min_lon..=self.max_lon).contains(&lon)
+    }
+}
+
+/// Parses `min_lat,min_lon,max_lat,max_lon`
+impl FromStr for BoundingBox {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Invalid bounding box: {}", s))?;
+
+        match values[..] {
+            [min_lat, min_lon, max_lat, max_lon] if min_lat <= max_lat && min_lon <= max_lon => {
+                Ok(BoundingBox::new(min_lat, min_lon, max_lat, max_lon))
+            }
+            _ => bail!("Expected min_lat,min_lon,max_lat,max_lon, found: {}", s),
+        }
+    }
+}
+
+/// Union of areas enclosed by rings of `[lat, lon]` points. A point is inside an area if it is
+/// enclosed by an odd number of its rings, so holes are given as additional rings. Rings of the
+/// same area must not overlap, e.g. overlapping sections of a poly file cancel each other out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    areas: Vec<Vec<Vec<[f64; 2]>>>,
+    bbox: BoundingBox,
+}
+
+impl Polygon {
+    /// Creates a polygon with a single area from rings of `[lat, lon]` points. The rings don't
+    /// need to be closed.
+    pub fn new(rings: Vec<Vec<[f64; 2]>>) -> Self {
+        Polygon::union(vec![rings])
+    }
+
+    /// Creates the union of several areas, each given by its rings
+    fn union(areas: Vec<Vec<Vec<[f64; 2]>>>) -> Self {
+        let bbox = BoundingBox::around(areas.iter().flatten().flatten());
+        Polygon { areas, bbox }
+    }
+
+    /// Reads a polygon from a `.poly` file or a GeoJSON file (`.geojson` or `.json`)
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("poly") => Polygon::from_poly(&content),
+            Some("geojson" | "json") => Polygon::from_geojson(&content),
+            _ => bail!("Unknown polygon format: {}", path.display()),
+        }
+    }
+
+    /// Parses the Osmosis poly format. Sections starting with `!` are holes.
+    pub fn from_poly(content: &str) -> anyhow::Result<Self> {
+        let mut lines = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+        // The first line contains the name of the polygon
+        lines.next().context("Empty poly file")?;
+
+        let mut rings = Vec::new();
+        loop {
+            match lines.next() {
+                Some("END") => break,
+                Some(_section) => {
+                    let mut ring = Vec::new();
+                    for line in lines.by_ref() {
+                        if line == "END" {
+                            break;
+                        }
+                        let mut values = line.split_whitespace().map(str::parse::<f64>);
+                        match (values.next(), values.next()) {
+                            (Some(Ok(lon)), Some(Ok(lat))) => ring.push([lat, lon]),
+                            _ => bail!("Invalid coordinate in poly file: {}", line),
+                        }
+                    }
+                    rings.push(ring);
+                }
+                None => bail!("Poly file is missing the final END"),
+            }
+        }
+
+        Ok(Polygon::new(rings))
+    }
+
+    /// Parses a GeoJSON `Polygon` or `MultiPolygon`. Features and feature collections are
+    /// searched for the first polygon geometry. The polygons of a `MultiPolygon` may overlap.
+    pub fn from_geojson(content: &str) -> anyhow::Result<Self> {
+        let json: Value = serde_json::from_str(content).context("Invalid GeoJSON")?;
+        let geometry = find_polygon(&json).context("GeoJSON contains no polygon")?;
+
+        let coordinates = &geometry["coordinates"];
+        let polygons = match geometry["type"].as_str() {
+            Some("Polygon") => vec![coordinates],
+            _ => coordinates.as_array().into_iter().flatten().collect(),
+        };
+
+        let mut areas = Vec::new();
+        for polygon in polygons {
+            let mut rings = Vec::new();
+            for ring in polygon.as_array().into_iter().flatten() {
+                let ring = ring
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(
+                        |position| match (position[0].as_f64(), position[1].as_f64()) {
+                            (Some(lon), Some(lat)) => Ok([lat, lon]),
+                            _ => bail!("Invalid GeoJSON position: {}", position),
+                        },
+                    )
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                rings.push(ring);
+            }
+            areas.push(rings);
+        }
+
+        Ok(Polygon::union(areas))
+    }
+
+    /// Returns true if the point lies inside the polygon
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        if !self.bbox.contains(lat, lon) {
+            return false;
+        }
+
+        self.areas
+            .iter()
+            .any(|rings| Polygon::area_contains(rings, lat, lon))
+    }
+
+    /// Ray casting along the latitude
+    fn area_contains(rings: &[Vec<[f64; 2]>], lat: f64, lon: f64) -> bool {
+        let mut inside = false;
+        for ring in rings {
+            let edges = ring.iter().zip(ring.iter().cycle().skip(1));
+            for ([lat_a, lon_a], [lat_b, lon_b]) in edges {
+                if (*lat_a > lat) != (*lat_b > lat)
+                    && lon < (lon_b - lon_a) * (lat - lat_a) / (lat_b - lat_a) + lon_a
+                {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+}
+
+fn find_polygon(json: &Value) -> Option<&Value> {
+    match json["type"].as_str()? {
+        "Polygon" | "MultiPolygon" => Some(json),
+        "Feature" => find_polygon(&json["geometry"]),
+        "FeatureCollection" => json["features"].as_array()?.iter().find_map(find_polygon),
+        _ => None,
+    }
+}
+
+/// Area the road network is clipped to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    BoundingBox(BoundingBox),
+    Polygon(Polygon),
+}
+
+impl Region {
+    /// Returns true if the point lies inside the region
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        match self {
+            Region::BoundingBox(bbox) => bbox.contains(lat, lon),
+            Region::Polygon(polygon) => polygon.contains(lat, lon),
+        }
+    }
+}
+
+impl From<BoundingBox> for Region {
+    fn from(bbox: BoundingBox) -> Self {
+        Region::BoundingBox(bbox)
+    }
+}
+
+impl From<Polygon> for Region {
+    fn from(polygon: Polygon) -> Self {
+        Region::Polygon(polygon)
+    }
+}
+
+/// Handling of ways which cross the border of the region.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CrossingWays {
+    /// Keep the whole way if at least one node is inside
+    Keep,
+    /// Keep only the parts of the way between nodes inside the region
+    #[default]
+    Split,
+    /// Remove the whole way
+    Drop,
+}
+
+impl FromStr for CrossingWays {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep" => Ok(CrossingWays::Keep),
+            "split" => Ok(CrossingWays::Split),
+            "drop" => Ok(CrossingWays::Drop),
+            _ => Err(format!("Unknown handling of crossing ways: {}", s)),
+        }
+    }
+}
+
+/// Region and handling of crossing ways used by [`crate::RoadGraph::from_pbf_clipped`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    region: Region,
+    crossing_ways: CrossingWays,
+}
+
+impl Clip {
+    pub fn new(region: impl Into<Region>) -> Self {
+        Clip {
+            region: region.into(),
+            crossing_ways: CrossingWays::default(),
+        }
+    }
+
+    pub fn crossing_ways(mut self, crossing_ways: CrossingWays) -> Self {
+        self.crossing_ways = crossing_ways;
+        self
+    }
+
+    pub fn region(&self) -> &Region {
+        &self.region
+    }
+
+    /// Splits the way given by `node_ids` into the parts which are kept. Nodes without
+    /// coordinates are outside of the region.
+    pub(crate) fn apply(
+        &self,
+        node_ids: &[i64],
+        nodes: &FxHashMap<i64, [f64; 2]>,
+    ) -> Vec<Vec<i64>> {
+        let inside: Vec<bool> = node_ids
+            .iter()
+            .map(|id| {
+                nodes
+                    .get(id)
+                    .is_some_and(|[lat, lon]| self.region.contains(*lat, *lon))
+            })
+            .collect();
+
+        match self.crossing_ways {
+            CrossingWays::Keep if inside.contains(&true) => vec![node_ids.to_vec()],
+            CrossingWays::Drop if !inside.contains(&false) => vec![node_ids.to_vec()],
+            CrossingWays::Keep | CrossingWays::Drop => vec![],
+            CrossingWays::Split => node_ids
+                .iter()
+                .zip(&inside)
+                .collect::<Vec<_>>()
+                .split(|(_, inside)| !**inside)
+                .filter(|part| part.len() > 1)
+                .map(|part| part.iter().map(|(id, _)| **id).collect())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLY: &str = "munich
+1
+   11.0 48.0
+   12.0 48.0
+   12.0 49.0
+   11.0 49.0
+END
+!2
+   11.4 48.4
+   11.6 48.4
+   11.6 48.6
+   11.4 48.6
+END
+END
+";
+
+    #[test]
+    fn parse_bounding_box() {
+        let bbox: BoundingBox = "48.0, 11.0, 49.0, 12.0".parse().unwrap();
+        assert_eq!(BoundingBox::new(48.0, 11.0, 49.0, 12.0), bbox);
+        assert!(bbox.contains(48.5, 11.5));
+        assert!(!bbox.contains(47.9, 11.5));
+
+        assert!("49.0,11.0,48.0,12.0".parse::<BoundingBox>().is_err());
+        assert!("48.0,11.0,49.0".parse::<BoundingBox>().is_err());
+    }
+
+    #[test]
+    fn poly_with_hole() {
+        let polygon = Polygon::from_poly(POLY).unwrap();
+
+        assert!(polygon.contains(48.2, 11.2));
+        assert!(!polygon.contains(48.5, 11.5));
+        assert!(!polygon.contains(49.5, 11.5));
+
+        assert!(Polygon::from_poly("munich\n1\n 11.0 48.0\nEND\n").is_err());
+    }
+
+    #[test]
+    fn geojson_matches_poly() {
+        let geojson = r#"{
+            "type": "Feature",
+            "properties": {},
+            "geometry": {
+                "type": "MultiPolygon",
+                "coordinates": [[
+                    [[11.0, 48.0], [12.0, 48.0], [12.0, 49.0], [11.0, 49.0], [11.0, 48.0]],
+                    [[11.4, 48.4], [11.6, 48.4], [11.6, 48.6], [11.4, 48.6], [11.4, 48.4]]
+                ]]
+            }
+        }"#;
+        let polygon = Polygon::from_geojson(geojson).unwrap();
+        let poly = Polygon::from_poly(POLY).unwrap();
+
+        for (lat, lon) in [(48.2, 11.2), (48.5, 11.5), (49.5, 11.5), (48.9, 11.9)] {
+            assert_eq!(poly.contains(lat, lon), polygon.contains(lat, lon));
+        }
+        assert!(Polygon::from_geojson(r#"{"type": "Point", "coordinates": [0, 0]}"#).is_err());
+    }
+
+    #[test]
+    fn overlapping_multipolygon() {
+        let geojson = r#"{
+            "type": "MultiPolygon",
+            "coordinates": [
+                [[[11.0, 48.0], [12.0, 48.0], [12.0, 49.0], [11.0, 49.0]]],
+                [[[11.5, 48.5], [12.5, 48.5], [12.5, 49.5], [11.5, 49.5]]]
+            ]
+        }"#;
+        let polygon = Polygon::from_geojson(geojson).unwrap();
+
+        assert!(polygon.contains(48.2, 11.2));
+        assert!(polygon.contains(48.7, 11.7));
+        assert!(polygon.contains(49.2, 12.2));
+        assert!(!polygon.contains(49.2, 11.2));
+    }
+
+    #[test]
+    fn crossing_ways() {
+        // Nodes 1, 2, 4 and 5 are inside, 3 is outside
+        let nodes: FxHashMap<i64, [f64; 2]> = [
+            (1, [0.1, 0.1]),
+            (2, [0.2, 0.2]),
+            (3, [2.0, 2.0]),
+            (4, [0.4, 0.4]),
+            (5, [0.5, 0.5]),
+        ]
+        .into_iter()
+        .collect();
+        let way = [1, 2, 3, 4, 5];
+        let clip = Clip::new(BoundingBox::new(0.0, 0.0, 1.0, 1.0));
+
+        assert_eq!(vec![vec![1, 2], vec![4, 5]], clip.apply(&way, &nodes));
+        assert_eq!(
+            vec![way.to_vec()],
+            clip.clone()
+                .crossing_ways(CrossingWays::Keep)
+                .apply(&way, &nodes)
+        );
+        assert!(clip
+            .clone()
+            .crossing_ways(CrossingWays::Drop)
+            .apply(&way, &nodes)
+            .is_empty());
+        assert_eq!(
+            vec![vec![1, 2]],
+            clip.crossing_ways(CrossingWays::Drop)
+                .apply(&[1, 2], &nodes)
+        );
+    }
+}
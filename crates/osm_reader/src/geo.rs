@@ -0,0 +1,231 @@
+//! Distances between coordinates on the earth.
+//!
+//! The distances of the roads and the lower bounds of the goal directed searches have to be
+//! calculated with the same [`EarthModel`], otherwise the lower bounds may overestimate the
+//! real distances. The model is selected for the import with
+//! [`PbfReader::earth_model`](crate::reader::PbfReader::earth_model) and stored in the
+//! [`RoadGraph`](crate::RoadGraph).
+//!
+//! # Examples
+//! ```
+//! use osm_reader::geo::EarthModel;
+//!
+//! // Munich to Berlin
+//! let haversine = EarthModel::Haversine.distance(48.1372, 11.5756, 52.5200, 13.4050);
+//! let karney = EarthModel::Karney.distance(48.1372, 11.5756, 52.5200, 13.4050);
+//! assert!((haversine - karney).abs() < 0.005 * karney);
+//! ```
+use std::{fmt::Display, str::FromStr, sync::OnceLock};
+
+use geographiclib_rs::{Geodesic, InverseGeodesic};
+use serde::{Deserialize, Serialize};
+
+/// Mean radius of the earth in metres as defined by the IUGG
+pub const MEAN_RADIUS: f64 = 6_371_008.8;
+
+/// Semi-major axis of the WGS84 ellipsoid in metres
+const WGS84_A: f64 = 6_378_137.0;
+
+/// Flattening of the WGS84 ellipsoid
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// Vincenty's formula does not converge for nearly antipodal points
+const VINCENTY_MAX_ITERATIONS: usize = 200;
+
+/// Model of the earth used to calculate distances
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EarthModel {
+    /// Great-circle distance on a sphere with the [`MEAN_RADIUS`]. The fastest model, the
+    /// error is below 0.5%.
+    #[default]
+    Haversine,
+    /// Geodesic on the WGS84 ellipsoid with Vincenty's iterative formula. Accurate to less
+    /// than a millimetre. Nearly antipodal points, for which the formula does not converge,
+    /// are calculated with Karney's algorithm.
+    Vincenty,
+    /// Geodesic on the WGS84 ellipsoid with Karney's algorithm. Accurate to a few nanometres
+    /// for all points, but the slowest model.
+    Karney,
+}
+
+impl EarthModel {
+    /// Distance between two coordinates in degrees in metres
+    pub fn distance(self, lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+        match self {
+            EarthModel::Haversine => haversine(lat1, lon1, lat2, lon2),
+            EarthModel::Vincenty => {
+                vincenty(lat1, lon1, lat2, lon2).unwrap_or_else(|| karney(lat1, lon1, lat2, lon2))
+            }
+            EarthModel::Karney => karney(lat1, lon1, lat2, lon2),
+        }
+    }
+}
+
+impl FromStr for EarthModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "haversine" => Ok(EarthModel::Haversine),
+            "vincenty" => Ok(EarthModel::Vincenty),
+            "karney" => Ok(EarthModel::Karney),
+            _ => Err(format!("Unknown earth model '{}'", s)),
+        }
+    }
+}
+
+impl Display for EarthModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EarthModel::Haversine => write!(f, "haversine"),
+            EarthModel::Vincenty => write!(f, "vincenty"),
+            EarthModel::Karney => write!(f, "karney"),
+        }
+    }
+}
+
+/// Great-circle distance in metres on a sphere with the [`MEAN_RADIUS`]
+pub fn haversine(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1).to_radians() / 2.0).sin().powi(2);
+
+    // Unlike `asin`, `atan2` stays accurate for nearly antipodal points
+    2.0 * MEAN_RADIUS * a.sqrt().atan2((1.0 - a).max(0.0).sqrt())
+}
+
+/// Geodesic distance in metres on the WGS84 ellipsoid with Vincenty's inverse formula.
+/// Returns `None` if the formula does not converge, which happens for nearly antipodal points.
+pub fn vincenty(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> Option<f64> {
+    let b = (1.0 - WGS84_F) * WGS84_A;
+    let l = (lon2 - lon1).to_radians();
+    let (sin_u1, cos_u1) = ((1.0 - WGS84_F) * lat1.to_radians().tan()).atan().sin_cos();
+    let (sin_u2, cos_u2) = ((1.0 - WGS84_F) * lat2.to_radians().tan()).atan().sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..VINCENTY_MAX_ITERATIONS {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+        .sqrt();
+        if sin_sigma == 0.0 {
+            // Coincident points
+            return Some(0.0);
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos_sq_alpha = 1.0 - sin_alpha.powi(2);
+        // Both points lie on the equator if `cos_sq_alpha` is zero
+        let cos_2sigma_m = if cos_sq_alpha != 0.0 {
+            cos_sigma - 2.0 * sin_u1 * sin_u2 / cos_sq_alpha
+        } else {
+            0.0
+        };
+        let c = WGS84_F / 16.0 * cos_sq_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos_sq_alpha));
+
+        let lambda_prev = lambda;
+        lambda = l
+            + (1.0 - c)
+                * WGS84_F
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+
+        if (lambda - lambda_prev).abs() < 1e-12 {
+            let u_sq = cos_sq_alpha * (WGS84_A.powi(2) - b.powi(2)) / b.powi(2);
+            let big_a =
+                1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+            let big_b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+            let delta_sigma = big_b
+                * sin_sigma
+                * (cos_2sigma_m
+                    + big_b / 4.0
+                        * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))
+                            - big_b / 6.0
+                                * cos_2sigma_m
+                                * (-3.0 + 4.0 * sin_sigma.powi(2))
+                                * (-3.0 + 4.0 * cos_2sigma_m.powi(2))));
+
+            return Some(b * big_a * (sigma - delta_sigma));
+        }
+    }
+
+    None
+}
+
+/// Geodesic distance in metres on the WGS84 ellipsoid with Karney's algorithm
+pub fn karney(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    static WGS84: OnceLock<Geodesic> = OnceLock::new();
+    WGS84
+        .get_or_init(Geodesic::wgs84)
+        .inverse(lat1, lon1, lat2, lon2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flinders Peak to Buninyong, the example of Vincenty's paper
+    const FLINDERS_PEAK: [f64; 2] = [-37.951_033_416_7, 144.424_867_888_9];
+    const BUNINYONG: [f64; 2] = [-37.652_821_138_9, 143.926_495_527_8];
+    const DISTANCE: f64 = 54_972.271;
+
+    fn distance(model: EarthModel, [lat1, lon1]: [f64; 2], [lat2, lon2]: [f64; 2]) -> f64 {
+        model.distance(lat1, lon1, lat2, lon2)
+    }
+
+    #[test]
+    fn ellipsoid_distances() {
+        for model in [EarthModel::Vincenty, EarthModel::Karney] {
+            let d = distance(model, FLINDERS_PEAK, BUNINYONG);
+            assert!((d - DISTANCE).abs() < 1e-3, "{}: {}", model, d);
+        }
+    }
+
+    #[test]
+    fn haversine_distances() {
+        // One degree on the equator
+        let d = haversine(0.0, 0.0, 0.0, 1.0);
+        assert!((d - MEAN_RADIUS.to_radians()).abs() < 1e-6);
+
+        let d = distance(EarthModel::Haversine, FLINDERS_PEAK, BUNINYONG);
+        assert!((d - DISTANCE).abs() < 0.005 * DISTANCE);
+
+        // Half the circumference, up to the rounding errors of the coordinates
+        let antipodal = haversine(10.0, 20.0, -10.0, -160.0);
+        assert!((antipodal - MEAN_RADIUS * std::f64::consts::PI).abs() < 1.0);
+    }
+
+    #[test]
+    fn coincident_points() {
+        for model in [
+            EarthModel::Haversine,
+            EarthModel::Vincenty,
+            EarthModel::Karney,
+        ] {
+            assert_eq!(0.0, distance(model, BUNINYONG, BUNINYONG));
+        }
+    }
+
+    #[test]
+    fn vincenty_falls_back_for_antipodal_points() {
+        assert_eq!(None, vincenty(0.0, 0.0, 0.5, 179.7));
+
+        let d = EarthModel::Vincenty.distance(0.0, 0.0, 0.5, 179.7);
+        assert_eq!(karney(0.0, 0.0, 0.5, 179.7), d);
+    }
+
+    #[test]
+    fn parse_earth_models() {
+        for model in [
+            EarthModel::Haversine,
+            EarthModel::Vincenty,
+            EarthModel::Karney,
+        ] {
+            assert_eq!(Ok(model), model.to_string().parse());
+        }
+        assert!("sphere".parse::<EarthModel>().is_err());
+    }
+}
@@ -1,4 +1,5 @@
-//! Crate to parse *.osm.pbf files into a [RoadGraph].
+//! Crate to parse *.osm.pbf files into a [RoadGraph]. Plain OSM XML files (*.osm) are
+//! supported as well, which is handy for small hand-written test networks.
 //!
 //! # Basic usage
 //! ```
@@ -17,13 +18,28 @@
 //! println!("The graph has {} nodes and {} arcs", road_graph.get_nodes().len(), road_graph.get_arcs().len());
 //! ```
 //!
-use log::info;
-use osmpbf::{Element, IndexedReader};
+//! To import only a part of the file, e.g. a city out of a state extract, see [clip]. The
+//! speeds used for the weights can be configured with a [profile::SpeedProfile]. For more
+//! options, e.g. parsing on multiple threads, see [reader::PbfReader].
+//!
+use clip::Clip;
+use geo::EarthModel;
+use profile::SpeedProfile;
+use reader::PbfReader;
 use rustc_hash::FxHashMap;
-use std::{collections::HashMap, fs::File, io::BufWriter, path::Path, str::FromStr};
-
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+pub mod clip;
+pub mod geo;
+pub mod profile;
+pub mod reader;
 mod road_types;
-use road_types::RoadType;
+pub use road_types::RoadType;
+use serde::{Deserialize, Serialize};
 
 /// Represents a road in a graph.
 pub struct Arc {
@@ -33,22 +49,43 @@ pub struct Arc {
     pub target: i64,
     /// Costs to traverse the road
     pub weight: f64,
+    /// OSM id of the way the road belongs to, see [`RoadGraph::get_ways`]
+    pub way_id: i64,
 }
 
 impl Arc {
-    fn new(source: i64, target: i64, weight: f64) -> Self {
+    fn new(source: i64, target: i64, weight: f64, way_id: i64) -> Self {
         Self {
             source,
             target,
             weight,
+            way_id,
         }
     }
 }
 
+/// The parts of an OSM way which are kept in the graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WayInfo {
+    /// OSM id of the way
+    pub id: i64,
+    /// Value of the `name` tag
+    pub name: Option<String>,
+    /// Value of the `ref` tag, e.g. the number of a motorway
+    pub reference: Option<String>,
+    pub road_type: RoadType,
+    /// The way is part of a roundabout (`junction=roundabout` or `junction=circular`). Such
+    /// ways are oneway unless tagged with `oneway=no`.
+    pub roundabout: bool,
+}
+
 /// A road graph, containing a set of nodes and arcs
 pub struct RoadGraph {
     nodes: FxHashMap<i64, [f64; 2]>,
     arcs: Vec<Arc>,
+    ways: FxHashMap<i64, WayInfo>,
+    earth_model: EarthModel,
+    max_speed: f64,
 }
 
 impl RoadGraph {
@@ -57,6 +94,9 @@ impl RoadGraph {
         RoadGraph {
             nodes: FxHashMap::default(),
             arcs: Vec::new(),
+            ways: FxHashMap::default(),
+            earth_model: EarthModel::default(),
+            max_speed: SpeedProfile::default().max_speed(),
         }
     }
 
@@ -64,10 +104,6 @@ impl RoadGraph {
         self.nodes.insert(id, [lat, lon]);
     }
 
-    fn add_arc(&mut self, from: i64, to: i64, weight: f64) {
-        self.arcs.push(Arc::new(from, to, weight));
-    }
-
     /// Returns the nodes of the graph.
     pub fn get_nodes(&self) -> &FxHashMap<i64, [f64; 2]> {
         &self.nodes
@@ -78,225 +114,99 @@ impl RoadGraph {
         &self.arcs
     }
 
+    /// Returns the ways of all arcs by their OSM id
+    pub fn get_ways(&self) -> &FxHashMap<i64, WayInfo> {
+        &self.ways
+    }
+
+    /// Returns the model the distances of the arcs were calculated with
+    pub fn get_earth_model(&self) -> EarthModel {
+        self.earth_model
+    }
+
+    /// Returns the highest speed in km/h the weights of the arcs may be based on, see
+    /// [`SpeedProfile::max_speed`]
+    pub fn get_max_speed(&self) -> f64 {
+        self.max_speed
+    }
+
     /// Parses a pbf file and returns a road graph. Before the graph is returned it is simplified by removing nodes
     /// which are no "real" nodes (mostly nodes with degree `2`) in the context of graph theory.
     pub fn from_pbf_with_simplification(pbf_path: &Path) -> anyhow::Result<RoadGraph> {
-        let mut graph = RoadGraph::new();
-
-        let mut reader = IndexedReader::from_path(pbf_path)?;
-
-        let road_filter = |way: &osmpbf::Way| {
-            way.tags()
-                .any(|(key, value)| key == "highway" && value.parse::<RoadType>().is_ok())
-        };
-
-        // let mut edges = Vec::new();
-        let mut refs_count = HashMap::new();
-        let mut ways = Vec::new();
-
-        let mut nodes: FxHashMap<i64, [f64; 2]> = Default::default();
-
-        let now = std::time::Instant::now();
-        info!("BEGIN parsing {}", pbf_path.display());
-        reader.read_ways_and_deps(road_filter, |element| match element {
-            Element::Way(way) => {
-                let node_ids = way.refs().collect::<Vec<_>>();
-                let tags = way.tags().collect::<Vec<_>>();
-
-                // Find tag "highway" and extract value
-                let road_type = tags
-                    .iter()
-                    .find(|key_value| key_value.0 == "highway")
-                    .unwrap()
-                    .1;
-                let road_type = RoadType::from_str(road_type).unwrap();
-
-                let is_oneway = {
-                    if let Some((_, value)) = tags.iter().find(|(key, _)| *key == "oneway") {
-                        match *value {
-                            // Tag always has prio if explicitly set
-                            "yes" => true,
-                            "no" => false,
-                            // If no tag is found check the road type
-                            _ => road_type.is_oneway(),
-                        }
-                    } else {
-                        false
-                    }
-                };
-
-                (0..node_ids.len()).for_each(|i| {
-                    let from = node_ids[i];
-
-                    if i == 0 || i == node_ids.len() - 1 {
-                        // Start and End of a road should always be included
-                        *refs_count.entry(from).or_insert(0) += 2;
-                    } else {
-                        *refs_count.entry(from).or_insert(0) += 1;
-                    }
-                });
-
-                ways.push((node_ids, road_type, is_oneway));
-            }
-            Element::Node(node) => {
-                nodes.insert(node.id(), [node.lat(), node.lon()]);
-            }
-            Element::DenseNode(dense_node) => {
-                nodes.insert(dense_node.id(), [dense_node.lat(), dense_node.lon()]);
-            }
-            Element::Relation(_) => {}
-        })?;
-        info!("FINISHED parsing. Took {:?}", now.elapsed());
-
-        let now = std::time::Instant::now();
-        info!("BEGIN graph simplification");
-
-        graph.arcs = Vec::with_capacity(ways.len() * 2);
-        // Split ways, but only keep nodes that are referenced more than once
-        for (node_ids, road_type, is_oneway) in ways {
-            let mut nodes_to_keep = vec![];
-            (0..node_ids.len()).for_each(|i| {
-                let node_id = node_ids[i];
-                if refs_count.get(&node_id).unwrap() > &1 {
-                    nodes_to_keep.push(i);
-                }
-            });
-
-            // Add nodes to graph
-            for i in nodes_to_keep.iter() {
-                let node_id = node_ids[*i];
-                let [lat, lon] = nodes.get(&node_id).unwrap();
-                graph.add_node(node_id, *lat, *lon);
-            }
-
-            for i in 0..nodes_to_keep.len() - 1 {
-                let from = nodes_to_keep[i];
-                let to = nodes_to_keep[i + 1];
-
-                let mut total_weight = 0.0;
-                for j in from..to {
-                    let [from_lat, from_lon] = nodes.get(&node_ids[j]).unwrap();
-                    let [to_lat, to_lon] = nodes.get(&node_ids[j + 1]).unwrap();
-                    let distance = haversine_distance(*from_lat, *from_lon, *to_lat, *to_lon);
-
-                    total_weight += weight(distance, &road_type);
-                }
-
-                graph.add_arc(node_ids[from], node_ids[to], total_weight);
-                // If bidirectional add reverse edge
-                if !is_oneway {
-                    graph.add_arc(node_ids[to], node_ids[from], total_weight);
-                }
-            }
-        }
-        info!("FINISHED graph simplification. Took {:?}", now.elapsed());
+        PbfReader::new(pbf_path).simplify(true).read()
+    }
 
-        Ok(graph)
+    /// Like [`RoadGraph::from_pbf_with_simplification`], but only keeps the roads inside the
+    /// region of `clip`.
+    pub fn from_pbf_with_simplification_clipped(
+        pbf_path: &Path,
+        clip: &Clip,
+    ) -> anyhow::Result<RoadGraph> {
+        PbfReader::new(pbf_path)
+            .simplify(true)
+            .clip(clip.clone())
+            .read()
     }
 
     /// Parses a pbf file and returns a road graph.
+    ///
+    /// The graph contains every node of a road, also the node of a road consisting of a single
+    /// node, which has no arcs. Nodes which are no part of a road are skipped.
     pub fn from_pbf(pbf_path: &Path) -> anyhow::Result<RoadGraph> {
-        let mut graph = RoadGraph::new();
-
-        let mut reader = IndexedReader::from_path(pbf_path)?;
-
-        let road_filter = |way: &osmpbf::Way| {
-            way.tags()
-                .any(|(key, value)| key == "highway" && value.parse::<RoadType>().is_ok())
-        };
-
-        let mut edges = Vec::new();
+        PbfReader::new(pbf_path).read()
+    }
 
-        let now = std::time::Instant::now();
-        info!("BEGIN parsing {}", pbf_path.display());
-        reader.read_ways_and_deps(road_filter, |element| match element {
-            Element::Way(way) => {
-                let node_ids = way.refs().collect::<Vec<_>>();
-                let tags = way.tags().collect::<Vec<_>>();
-
-                // Find tag "highway" and extract value
-                let road_type = tags
-                    .iter()
-                    .find(|key_value| key_value.0 == "highway")
-                    .unwrap()
-                    .1;
-                let road_type = RoadType::from_str(road_type).unwrap();
-
-                let is_oneway = {
-                    if let Some((_, value)) = tags.iter().find(|(key, _)| *key == "oneway") {
-                        match *value {
-                            // Tag always has prio if explicitly set
-                            "yes" => true,
-                            "no" => false,
-                            // If no tag is found check the road type
-                            _ => road_type.is_oneway(),
-                        }
-                    } else {
-                        false
-                    }
-                };
-
-                for i in 0..node_ids.len() - 1 {
-                    let from = node_ids[i];
-                    let to = node_ids[i + 1];
-
-                    edges.push((from, to, road_type));
-
-                    // If bidirectional add reverse edge
-                    if !is_oneway {
-                        edges.push((to, from, road_type));
-                    }
-                }
-            }
-            Element::Node(node) => {
-                graph.add_node(node.id(), node.lat(), node.lon());
-            }
-            Element::DenseNode(dense_node) => {
-                graph.add_node(dense_node.id(), dense_node.lat(), dense_node.lon());
-            }
-            Element::Relation(_) => {}
-        })?;
-
-        // Calculate weights and add arcs to graph
-        graph.arcs = Vec::new();
-        for (from, to, road_type) in edges {
-            let [from_lat, from_lon] = graph.nodes.get(&from).unwrap();
-            let [to_lat, to_lon] = graph.nodes.get(&to).unwrap();
-
-            let distance = haversine_distance(*from_lat, *from_lon, *to_lat, *to_lon);
-
-            graph.add_arc(from, to, weight(distance, &road_type));
-        }
+    /// Like [`RoadGraph::from_pbf`], but only keeps the roads inside the region of `clip`.
+    /// Parts of roads with less than two nodes inside the region are removed.
+    pub fn from_pbf_clipped(pbf_path: &Path, clip: &Clip) -> anyhow::Result<RoadGraph> {
+        PbfReader::new(pbf_path).clip(clip.clone()).read()
+    }
 
-        info!("FINISHED parsing. Took {:?}", now.elapsed());
-        Ok(graph)
+    /// Like [`RoadGraph::from_pbf`], but calculates the weights with the speeds of `profile`.
+    /// To combine a profile with other options, see [`PbfReader::profile`].
+    pub fn from_pbf_with_profile(
+        pbf_path: &Path,
+        profile: &SpeedProfile,
+    ) -> anyhow::Result<RoadGraph> {
+        PbfReader::new(pbf_path).profile(profile.clone()).read()
     }
 
     /// Writes nodes to `nodes.csv` and edges to `edges.csv`.
     pub fn write_csv(&self) -> anyhow::Result<()> {
-        use std::io::Write;
+        self.write_csv_to(".")
+    }
 
-        let nodes_file = File::create("nodes.csv")?;
-        let edges_file = File::create("edges.csv")?;
+    /// Writes nodes to `nodes.csv` and edges to `edges.csv` in `dir`. The directory is created
+    /// if it does not exist.
+    pub fn write_csv_to(&self, dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
 
-        let mut nodes_writer = BufWriter::new(nodes_file);
-        let _ = nodes_writer.write("id,lat,lon\n".as_bytes())?;
+        let nodes_file = File::create(dir.join("nodes.csv"))?;
+        let edges_file = File::create(dir.join("edges.csv"))?;
+
+        self.write_csv_into(BufWriter::new(nodes_file), BufWriter::new(edges_file))
+    }
+
+    /// Writes nodes and edges as CSV into the given writers.
+    pub fn write_csv_into<W: Write>(&self, mut nodes: W, mut edges: W) -> anyhow::Result<()> {
+        nodes.write_all("id,lat,lon\n".as_bytes())?;
         for (id, [lat, lon]) in self.nodes.iter() {
-            let _ = nodes_writer.write(format!("{},{},{}\n", id, lat, lon).as_bytes())?;
+            nodes.write_all(format!("{},{},{}\n", id, lat, lon).as_bytes())?;
         }
-        nodes_writer.flush()?;
+        nodes.flush()?;
 
-        let mut edges_writer = BufWriter::new(edges_file);
-        let _ = edges_writer.write("source,target,weight\n".as_bytes())?;
+        edges.write_all("source,target,weight\n".as_bytes())?;
         for Arc {
             weight,
             source,
             target,
+            ..
         } in self.arcs.iter()
         {
-            let _ = edges_writer.write(format!("{},{},{}\n", source, target, weight).as_bytes())?;
+            edges.write_all(format!("{},{},{}\n", source, target, weight).as_bytes())?;
         }
-        edges_writer.flush()?;
+        edges.flush()?;
 
         Ok(())
     }
@@ -308,25 +218,14 @@ impl Default for RoadGraph {
     }
 }
 
-// Assumes average speed in km/h for different road types and calculates the time [sec] as weight.
+// Calculates the time [sec] to travel `distance` metres at `speed` km/h as weight.
 // v = s / t => t = s / v
-fn weight(distance: f64, road_type: &RoadType) -> f64 {
+fn weight(distance: f64, speed: f64) -> f64 {
     // velocity km/h in m/s
-    let velocity = road_type.velocity() / 3.6;
+    let velocity = speed / 3.6;
     distance / velocity
 }
 
-// Calculates the great-circle distance between two points in metres
-fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
-    let r = 6_378_100.0; // FIXME: Find good radius for germany
-    let d_lat = (lat2 - lat1).to_radians();
-    let d_lon = (lon2 - lon1).to_radians();
-    let a = (d_lat / 2.0).sin().powi(2)
-        + (d_lon / 2.0).sin().powi(2) * lat1.to_radians().cos() * lat2.to_radians().cos();
-    let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
-    r * c
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -349,6 +248,27 @@ mod tests {
         graph.write_csv().unwrap();
     }
 
+    #[test]
+    fn write_csv_into_writers() {
+        let mut graph = RoadGraph::new();
+        graph.nodes.insert(1, [48.0, 11.0]);
+        graph.nodes.insert(2, [48.1, 11.1]);
+        graph.arcs.push(Arc::new(1, 2, 10.5, 3));
+
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        graph.write_csv_into(&mut nodes, &mut edges).unwrap();
+
+        let nodes = String::from_utf8(nodes).unwrap();
+        assert!(nodes.starts_with("id,lat,lon\n"));
+        assert!(nodes.contains("1,48,11\n"));
+        assert!(nodes.contains("2,48.1,11.1\n"));
+        assert_eq!(
+            String::from_utf8(edges).unwrap(),
+            "source,target,weight\n1,2,10.5\n"
+        );
+    }
+
     #[test]
     fn graph_from_pbf_without_geometry_works() {
         let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/node_refs.osm.pbf");
@@ -358,17 +278,17 @@ mod tests {
         assert_eq!(graph.nodes.len(), 8);
         assert_eq!(graph.arcs.len(), 14);
 
-        assert_eq!(
-            weight(
-                haversine_distance(0., 0., 0., 1.) * 3.0,
-                &RoadType::Secondary
-            ),
-            graph
-                .arcs
-                .iter()
-                .find(|arc| arc.source == 2 && arc.target == 5)
-                .unwrap()
-                .weight
+        let expected = weight(
+            geo::haversine(0., 0., 0., 1.) * 3.0,
+            RoadType::Secondary.velocity(),
         );
+        let actual = graph
+            .arcs
+            .iter()
+            .find(|arc| arc.source == 2 && arc.target == 5)
+            .unwrap()
+            .weight;
+        // The weight is the sum of three segments
+        assert!((expected - actual).abs() < 1e-9);
     }
 }
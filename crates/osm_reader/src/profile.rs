@@ -0,0 +1,320 @@
+//! Speed profiles which determine the travel time, i.e. the weight, of the roads.
+//!
+//! A profile assigns a speed to every [`RoadType`] and optionally limits the speed by the
+//! `surface` of a road. The built-in [`SpeedProfile::default`] uses the speeds of
+//! [`RoadType::velocity`].
+//!
+//! Profiles are read from TOML or JSON files. All tables are optional. Road types missing in
+//! the `highway` table get the `urban` or `rural` default speed if the road lies inside or
+//! outside of a settlement and keep their built-in speed otherwise:
+//! ```toml
+//! # Default speed inside (urban) and outside (rural) of settlements
+//! urban = 50
+//! rural = 100
+//!
+//! # Speeds in km/h
+//! [highway]
+//! motorway = 120
+//! residential = 25
+//!
+//! # Maximal speed by the value of the `surface` tag
+//! [surface]
+//! gravel = 40
+//! unpaved = 30
+//! ```
+//! Settlements are recognized by implicit speed limits like `maxspeed=DE:urban` or by the
+//! `zone:traffic`, `zone:maxspeed` and `source:maxspeed` tags.
+//!
+//! # Examples
+//! ```no_run
+//! use std::path::Path;
+//! use osm_reader::{profile::SpeedProfile, reader::PbfReader};
+//!
+//! let profile = SpeedProfile::from_file(Path::new("path/to/profile.toml")).unwrap();
+//! let road_graph = PbfReader::new(Path::new("path/to/pbf/file.osm.pbf"))
+//!     .profile(profile)
+//!     .read()
+//!     .unwrap();
+//! ```
+use std::{fs, path::Path};
+
+use anyhow::{bail, Context};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::RoadType;
+
+/// Tags which mark a road as inside or outside of a settlement, e.g. `DE:urban`
+const AREA_TAGS: [&str; 4] = [
+    "maxspeed",
+    "source:maxspeed",
+    "zone:traffic",
+    "zone:maxspeed",
+];
+
+/// Speeds in km/h used to calculate the travel time of the roads
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpeedProfile {
+    /// Speed of every road type. Road types without an entry use the `urban` or `rural`
+    /// speed in settlements or outside of them and [`RoadType::velocity`] otherwise.
+    #[serde(default)]
+    pub highway: FxHashMap<RoadType, f64>,
+    /// Maximal speed by the value of the `surface` tag
+    #[serde(default)]
+    pub surface: FxHashMap<String, f64>,
+    /// Default speed of roads inside of settlements
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub urban: Option<f64>,
+    /// Default speed of roads outside of settlements
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rural: Option<f64>,
+}
+
+impl SpeedProfile {
+    /// Reads a profile from a `.toml` or `.json` file
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => SpeedProfile::from_toml(&content),
+            Some("json") => SpeedProfile::from_json(&content),
+            _ => bail!("Unknown profile format: {}", path.display()),
+        }
+    }
+
+    pub fn from_toml(content: &str) -> anyhow::Result<Self> {
+        let profile: SpeedProfile = toml::from_str(content).context("Invalid TOML profile")?;
+        profile.validate()
+    }
+
+    pub fn from_json(content: &str) -> anyhow::Result<Self> {
+        let profile: SpeedProfile =
+            serde_json::from_str(content).context("Invalid JSON profile")?;
+        profile.validate()
+    }
+
+    /// Speed in km/h of a road of type `road_type` with the tags `tags`
+    pub fn speed(&self, road_type: RoadType, tags: &[(&str, &str)]) -> f64 {
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+        };
+
+        let area_speed = || {
+            AREA_TAGS
+                .iter()
+                .filter_map(|name| tag(name))
+                .find_map(|value| match value.rsplit(':').next() {
+                    Some("urban") => Some(self.urban),
+                    Some("rural") => Some(self.rural),
+                    _ => None,
+                })
+                .flatten()
+        };
+
+        let mut speed = self
+            .highway
+            .get(&road_type)
+            .copied()
+            .or_else(area_speed)
+            .unwrap_or_else(|| road_type.velocity());
+
+        if let Some(max_speed) = tag("surface").and_then(|surface| self.surface.get(surface)) {
+            speed = speed.min(*max_speed);
+        }
+
+        speed
+    }
+
+    /// Highest speed of all road types and settlement defaults. The surface only lowers the
+    /// speed, so no road is faster.
+    pub fn max_speed(&self) -> f64 {
+        RoadType::ALL
+            .iter()
+            .map(|road_type| self.speed(*road_type, &[]))
+            .chain(self.urban)
+            .chain(self.rural)
+            .fold(0.0, f64::max)
+    }
+
+    /// Checks that all speeds are positive
+    fn validate(self) -> anyhow::Result<Self> {
+        let speeds = self
+            .highway
+            .iter()
+            .map(|(road_type, speed)| (road_type.to_string(), *speed))
+            .chain(
+                self.surface
+                    .iter()
+                    .map(|(surface, speed)| (format!("surface {}", surface), *speed)),
+            )
+            .chain(self.urban.map(|speed| ("urban".to_string(), speed)))
+            .chain(self.rural.map(|speed| ("rural".to_string(), speed)));
+
+        for (name, speed) in speeds {
+            if !(speed.is_finite() && speed > 0.0) {
+                bail!("Speed of {} must be positive, found {}", name, speed);
+            }
+        }
+
+        Ok(self)
+    }
+}
+
+/// The speeds of [`RoadType::velocity`] without any limits
+impl Default for SpeedProfile {
+    fn default() -> Self {
+        SpeedProfile {
+            highway: RoadType::ALL
+                .iter()
+                .map(|road_type| (*road_type, road_type.velocity()))
+                .collect(),
+            surface: FxHashMap::default(),
+            urban: None,
+            rural: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: &str = r#"
+        urban = 50
+        rural = 100
+
+        [highway]
+        motorway = 120
+        residential = 25
+
+        [surface]
+        gravel = 40
+    "#;
+
+    #[test]
+    fn default_speeds() {
+        let profile = SpeedProfile::default();
+        for road_type in RoadType::ALL {
+            assert_eq!(road_type.velocity(), profile.speed(road_type, &[]));
+        }
+        assert_eq!(
+            RoadType::Primary.velocity(),
+            profile.speed(
+                RoadType::Primary,
+                &[("surface", "gravel"), ("maxspeed", "DE:urban")]
+            )
+        );
+    }
+
+    #[test]
+    fn read_toml_profile() {
+        let profile = SpeedProfile::from_toml(PROFILE).unwrap();
+
+        assert_eq!(120.0, profile.speed(RoadType::Motorway, &[]));
+        assert_eq!(25.0, profile.speed(RoadType::Residential, &[]));
+        // Missing road types keep the default speed
+        assert_eq!(70.0, profile.speed(RoadType::Primary, &[]));
+
+        assert_eq!(
+            40.0,
+            profile.speed(RoadType::Primary, &[("surface", "gravel")])
+        );
+        assert_eq!(
+            70.0,
+            profile.speed(RoadType::Primary, &[("surface", "asphalt")])
+        );
+        // Settlements set the speed of road types missing in the profile
+        assert_eq!(
+            50.0,
+            profile.speed(RoadType::Primary, &[("maxspeed", "DE:urban")])
+        );
+        assert_eq!(
+            50.0,
+            profile.speed(RoadType::Primary, &[("zone:traffic", "DE:urban")])
+        );
+        assert_eq!(
+            100.0,
+            profile.speed(RoadType::Primary, &[("maxspeed", "AT:rural")])
+        );
+        assert_eq!(
+            40.0,
+            profile.speed(
+                RoadType::Primary,
+                &[("maxspeed", "AT:rural"), ("surface", "gravel")]
+            )
+        );
+        // The speeds of the profile take precedence
+        assert_eq!(
+            120.0,
+            profile.speed(RoadType::Motorway, &[("maxspeed", "AT:rural")])
+        );
+        assert_eq!(
+            25.0,
+            profile.speed(RoadType::Residential, &[("maxspeed", "DE:urban")])
+        );
+    }
+
+    #[test]
+    fn read_json_profile() {
+        let json =
+            r#"{"highway": {"living_street": 7, "motorway_link": 60}, "surface": {"sand": 10}}"#;
+        let profile = SpeedProfile::from_json(json).unwrap();
+
+        assert_eq!(7.0, profile.speed(RoadType::LivingStreet, &[]));
+        assert_eq!(60.0, profile.speed(RoadType::MotorwayLink, &[]));
+        assert_eq!(
+            10.0,
+            profile.speed(RoadType::Residential, &[("surface", "sand")])
+        );
+        assert_eq!(None, profile.urban);
+    }
+
+    #[test]
+    fn max_speed_of_profiles() {
+        assert_eq!(110.0, SpeedProfile::default().max_speed());
+        assert_eq!(120.0, SpeedProfile::from_toml(PROFILE).unwrap().max_speed());
+        assert_eq!(
+            130.0,
+            SpeedProfile::from_toml("rural = 130").unwrap().max_speed()
+        );
+    }
+
+    #[test]
+    fn write_and_read_default_profile() {
+        let profile = SpeedProfile::default();
+        let toml = toml::to_string(&profile).unwrap();
+        assert_eq!(profile, SpeedProfile::from_toml(&toml).unwrap());
+    }
+
+    #[test]
+    fn invalid_profiles_are_errors() {
+        assert!(SpeedProfile::from_toml("[highway]\nmotorway = 0").is_err());
+        assert!(SpeedProfile::from_toml("[highway]\nmotorway = -5").is_err());
+        assert!(SpeedProfile::from_toml("[highway]\nfootway = 5").is_err());
+        assert!(SpeedProfile::from_toml("urban = \"fast\"").is_err());
+        assert!(SpeedProfile::from_toml("unknown = 5").is_err());
+        assert!(SpeedProfile::from_json("{\"surface\": {\"gravel\": 0}}").is_err());
+    }
+
+    #[test]
+    fn read_profile_files() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let path = dir.path().join("profile.toml");
+        fs::write(&path, PROFILE).unwrap();
+        assert_eq!(
+            SpeedProfile::from_toml(PROFILE).unwrap(),
+            SpeedProfile::from_file(&path).unwrap()
+        );
+
+        // Unknown formats are rejected even if the content is valid
+        let path = dir.path().join("profile.yaml");
+        fs::write(&path, PROFILE).unwrap();
+        let err = SpeedProfile::from_file(&path).unwrap_err().to_string();
+        assert!(err.contains("Unknown profile format"), "{}", err);
+    }
+}
@@ -0,0 +1,584 @@
+//! Configurable import of pbf files and plain OSM XML files. The format is detected by the
+//! file extension, see [`PbfReader::read`].
+//!
+//! # Examples
+//! ```no_run
+//! use std::path::Path;
+//! use osm_reader::reader::PbfReader;
+//!
+//! let road_graph = PbfReader::new(Path::new("path/to/pbf/file.osm.pbf"))
+//!     .simplify(true)
+//!     .parallel(true)
+//!     .read()
+//!     .expect("Failed to create graph from pbf file");
+//! ```
+//!
+//! Continent-sized files don't fit into memory with the default import. The streaming import
+//! only keeps a sorted array of the referenced node ids and their coordinates in fixed-point,
+//! see [`PbfReader::streaming`].
+use std::{
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use log::info;
+use osmpbf::{BlobDecode, BlobReader, Element, IndexedReader};
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHasher};
+
+use crate::{
+    clip::Clip, geo::EarthModel, profile::SpeedProfile, road_types::RoadType, weight, Arc,
+    RoadGraph, WayInfo,
+};
+
+mod streaming;
+mod xml;
+
+/// Version of the import, part of [`PbfReader::fingerprint`]. Must be increased whenever a
+/// change of the import changes the resulting graph, so that cached graphs are rebuilt.
+pub const IMPORT_VERSION: u32 = 1;
+
+/// Coordinates `[lat, lon]` of the nodes by their OSM id
+type Coordinates = FxHashMap<i64, [f64; 2]>;
+
+/// Reads a pbf file into a [`RoadGraph`]. By default the file is read on a single thread,
+/// without simplification and without clipping.
+#[derive(Debug, Clone)]
+pub struct PbfReader {
+    path: PathBuf,
+    simplify: bool,
+    parallel: bool,
+    clip: Option<Clip>,
+    streaming: bool,
+    spill_dir: Option<PathBuf>,
+    profile: SpeedProfile,
+    earth_model: EarthModel,
+}
+
+impl PbfReader {
+    pub fn new(path: &Path) -> Self {
+        PbfReader {
+            path: path.to_path_buf(),
+            simplify: false,
+            parallel: false,
+            clip: None,
+            streaming: false,
+            spill_dir: None,
+            profile: SpeedProfile::default(),
+            earth_model: EarthModel::default(),
+        }
+    }
+
+    /// Removes nodes which are no "real" nodes (mostly nodes with degree `2`) in the context of
+    /// graph theory. See [`RoadGraph::from_pbf_with_simplification`].
+    pub fn simplify(mut self, simplify: bool) -> Self {
+        self.simplify = simplify;
+        self
+    }
+
+    /// Decodes the blobs of the file and calculates the arcs on all available threads. The
+    /// resulting graph is the same as with a single thread. Like the sequential import, only the
+    /// coordinates of nodes which are part of a road are kept.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Only keeps the roads inside the region of `clip`
+    pub fn clip(mut self, clip: Clip) -> Self {
+        self.clip = Some(clip);
+        self
+    }
+
+    /// Reads the file in two passes without keeping the ways in memory. The first pass counts
+    /// the references of every node into a sorted id array, the second pass resolves the
+    /// coordinates, stored as fixed-point numbers, and builds the arcs. The file has to be
+    /// sorted (nodes before ways), which is the case for all common extracts. Clipping is not
+    /// supported, `parallel` is ignored.
+    pub fn streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Writes the node ids of the first pass of the streaming import in sorted runs to `dir`
+    /// instead of keeping them in memory. Enables the streaming import. The runs get unique
+    /// names and are removed as soon as the import finishes or fails.
+    pub fn spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.streaming = true;
+        self.spill_dir = Some(dir.into());
+        self
+    }
+
+    /// Calculates the weights with the speeds of `profile` instead of the default speeds
+    pub fn profile(mut self, profile: SpeedProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Calculates the lengths of the roads with `earth_model` instead of the haversine formula
+    pub fn earth_model(mut self, earth_model: EarthModel) -> Self {
+        self.earth_model = earth_model;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true if the graph is simplified, see [`PbfReader::simplify`]
+    pub fn is_simplified(&self) -> bool {
+        self.simplify
+    }
+
+    /// Hash of all options which change the resulting graph, e.g. to detect outdated caches.
+    /// Options which only change how the file is read, like [`PbfReader::parallel`], are not
+    /// included.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = FxHasher::default();
+        IMPORT_VERSION.hash(&mut hasher);
+        self.simplify.hash(&mut hasher);
+        format!("{:?}", self.clip).hash(&mut hasher);
+        self.earth_model.hash(&mut hasher);
+        // The maps of `serde_json::Value` are sorted, the hash maps of the profile are not
+        serde_json::to_value(&self.profile)
+            .map(|profile| profile.to_string())
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Empty graph with the earth model and the maximal speed of the profile
+    fn new_graph(&self) -> RoadGraph {
+        RoadGraph {
+            earth_model: self.earth_model,
+            max_speed: self.profile.max_speed(),
+            ..RoadGraph::new()
+        }
+    }
+
+    /// Reads the file into a graph. Files ending in `.osm` or `.xml` are parsed as OSM XML,
+    /// all others as pbf. XML files are meant for small networks, so they are always read on
+    /// a single thread and without streaming.
+    pub fn read(&self) -> anyhow::Result<RoadGraph> {
+        let is_xml = xml::is_xml(&self.path);
+        if self.streaming && !is_xml {
+            return streaming::read(self);
+        }
+
+        let now = std::time::Instant::now();
+        info!("BEGIN parsing {}", self.path.display());
+        let (mut roads, nodes) = if is_xml {
+            xml::read_roads(&self.path, &self.profile)?
+        } else if self.parallel {
+            read_roads_parallel(&self.path, &self.profile)?
+        } else {
+            read_roads(&self.path, &self.profile)?
+        };
+        info!("FINISHED parsing. Took {:?}", now.elapsed());
+
+        if let Some(clip) = &self.clip {
+            let num_roads = roads.len();
+            roads = roads
+                .into_iter()
+                .flat_map(|road| {
+                    clip.apply(&road.node_ids, &nodes)
+                        .into_iter()
+                        .map(move |node_ids| Road {
+                            way: road.way.clone(),
+                            node_ids,
+                            is_oneway: road.is_oneway,
+                            speed: road.speed,
+                        })
+                })
+                .collect();
+            info!("Clipped {} roads to {} parts", num_roads, roads.len());
+        }
+
+        if !self.simplify {
+            return build_graph(self, &roads, &nodes, |_| true);
+        }
+
+        let now = std::time::Instant::now();
+        info!("BEGIN graph simplification");
+        // Only keep nodes that are referenced more than once
+        let refs_count = count_refs(&roads, self.parallel);
+        let graph = build_graph(self, &roads, &nodes, |id| refs_count[&id] > 1)?;
+        info!("FINISHED graph simplification. Took {:?}", now.elapsed());
+
+        Ok(graph)
+    }
+}
+
+/// Road parsed from an OSM way
+struct Road {
+    way: WayInfo,
+    node_ids: Vec<i64>,
+    is_oneway: bool,
+    /// Speed in km/h, see [`SpeedProfile::speed`]
+    speed: f64,
+}
+
+impl Road {
+    /// Returns true for ways with a supported `highway` tag
+    fn is_road(way: &osmpbf::Way) -> bool {
+        way.tags()
+            .any(|(key, value)| key == "highway" && value.parse::<RoadType>().is_ok())
+    }
+
+    /// Must only be called for ways for which [`Road::is_road`] returns true
+    fn from_way(way: &osmpbf::Way, profile: &SpeedProfile) -> Self {
+        let tags = way.tags().collect::<Vec<_>>();
+        Road::from_tags(way.id(), way.refs().collect(), &tags, profile).expect("Way is no road")
+    }
+
+    /// Creates a road from the parts of a way. Returns `None` if the way has no supported
+    /// `highway` tag.
+    fn from_tags(
+        id: i64,
+        node_ids: Vec<i64>,
+        tags: &[(&str, &str)],
+        profile: &SpeedProfile,
+    ) -> Option<Self> {
+        // Find tag "highway" and extract value
+        let (_, road_type) = tags.iter().find(|(key, _)| *key == "highway")?;
+        let road_type = RoadType::from_str(road_type).ok()?;
+
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        };
+        let roundabout = matches!(tag("junction").as_deref(), Some("roundabout" | "circular"));
+
+        let is_oneway = {
+            if let Some((_, value)) = tags.iter().find(|(key, _)| *key == "oneway") {
+                match *value {
+                    // Tag always has prio if explicitly set
+                    "yes" => true,
+                    "no" => false,
+                    // If no tag is found check the road type
+                    _ => roundabout || road_type.is_oneway(),
+                }
+            } else {
+                // Roundabouts imply oneway=yes
+                roundabout
+            }
+        };
+
+        Some(Road {
+            way: WayInfo {
+                id,
+                name: tag("name"),
+                reference: tag("ref"),
+                road_type,
+                roundabout,
+            },
+            node_ids,
+            is_oneway,
+            speed: profile.speed(road_type, tags),
+        })
+    }
+}
+
+/// Reads all roads and the coordinates of their nodes
+fn read_roads(pbf_path: &Path, profile: &SpeedProfile) -> anyhow::Result<(Vec<Road>, Coordinates)> {
+    let mut reader = IndexedReader::from_path(pbf_path)?;
+
+    let mut roads = Vec::new();
+    let mut nodes: Coordinates = Default::default();
+
+    reader.read_ways_and_deps(Road::is_road, |element| match element {
+        Element::Way(way) => roads.push(Road::from_way(way, profile)),
+        Element::Node(node) => {
+            nodes.insert(node.id(), [node.lat(), node.lon()]);
+        }
+        Element::DenseNode(dense_node) => {
+            nodes.insert(dense_node.id(), [dense_node.lat(), dense_node.lon()]);
+        }
+        Element::Relation(_) => {}
+    })?;
+
+    Ok((roads, nodes))
+}
+
+/// Like [`read_roads`], but decodes the blobs on all available threads. Like
+/// [`IndexedReader::read_ways_and_deps`] the file is read twice: the first pass collects the
+/// roads, the second pass only keeps the coordinates of the nodes which are part of a road.
+fn read_roads_parallel(
+    pbf_path: &Path,
+    profile: &SpeedProfile,
+) -> anyhow::Result<(Vec<Road>, Coordinates)> {
+    let mut roads = BlobReader::from_path(pbf_path)?
+        .par_bridge()
+        .try_fold(Vec::new, |mut roads, blob| {
+            if let BlobDecode::OsmData(block) = blob?.decode()? {
+                for element in block.elements() {
+                    if let Element::Way(way) = element {
+                        if Road::is_road(&way) {
+                            roads.push(Road::from_way(&way, profile));
+                        }
+                    }
+                }
+            }
+            Ok::<_, osmpbf::Error>(roads)
+        })
+        .try_reduce(Vec::new, |a, b| Ok(merge(a, b)))?;
+    // The blobs are decoded in arbitrary order
+    roads.par_sort_unstable_by_key(|road| road.way.id);
+
+    let mut referenced: Vec<i64> = roads
+        .par_iter()
+        .flat_map_iter(|road| road.node_ids.iter().copied())
+        .collect();
+    referenced.par_sort_unstable();
+    referenced.dedup();
+    let is_referenced = |id: i64| referenced.binary_search(&id).is_ok();
+
+    let nodes = BlobReader::from_path(pbf_path)?
+        .par_bridge()
+        .try_fold(Vec::new, |mut nodes, blob| {
+            if let BlobDecode::OsmData(block) = blob?.decode()? {
+                for element in block.elements() {
+                    match element {
+                        Element::Node(node) if is_referenced(node.id()) => {
+                            nodes.push((node.id(), [node.lat(), node.lon()]))
+                        }
+                        Element::DenseNode(node) if is_referenced(node.id()) => {
+                            nodes.push((node.id(), [node.lat(), node.lon()]))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Ok::<_, osmpbf::Error>(nodes)
+        })
+        .try_reduce(Vec::new, |a, b| Ok(merge(a, b)))?;
+
+    Ok((roads, nodes.into_par_iter().collect()))
+}
+
+fn merge<T>(mut a: Vec<T>, mut b: Vec<T>) -> Vec<T> {
+    if a.len() < b.len() {
+        std::mem::swap(&mut a, &mut b);
+    }
+    a.append(&mut b);
+    a
+}
+
+/// Counts how often every node is referenced. Start and end of a road count twice, as they
+/// should always be included.
+fn count_refs(roads: &[Road], parallel: bool) -> FxHashMap<i64, usize> {
+    let count = |mut refs_count: FxHashMap<i64, usize>, road: &Road| {
+        let last = road.node_ids.len().saturating_sub(1);
+        for (i, node_id) in road.node_ids.iter().enumerate() {
+            *refs_count.entry(*node_id).or_insert(0) += if i == 0 || i == last { 2 } else { 1 };
+        }
+        refs_count
+    };
+
+    if !parallel {
+        return roads.iter().fold(FxHashMap::default(), count);
+    }
+
+    roads
+        .par_iter()
+        .fold(FxHashMap::default, count)
+        .reduce(FxHashMap::default, |mut a, mut b| {
+            if a.len() < b.len() {
+                std::mem::swap(&mut a, &mut b);
+            }
+            for (node_id, refs) in b {
+                *a.entry(node_id).or_insert(0) += refs;
+            }
+            a
+        })
+}
+
+/// Nodes and arcs of a single road
+type RoadParts = (Vec<(i64, [f64; 2])>, Vec<Arc>);
+
+impl RoadGraph {
+    /// Adds the parts of `road` returned by [`split_road`]
+    fn add_road(&mut self, road: &Road, road_nodes: Vec<(i64, [f64; 2])>, arcs: Vec<Arc>) {
+        for (id, [lat, lon]) in road_nodes {
+            self.add_node(id, lat, lon);
+        }
+        if arcs.is_empty() {
+            return;
+        }
+
+        self.arcs.extend(arcs);
+        self.ways
+            .entry(road.way.id)
+            .or_insert_with(|| road.way.clone());
+    }
+}
+
+/// Splits every road at the nodes for which `keep` returns true and connects them with arcs.
+/// The weight of an arc is the sum of the weights of the road segments it replaces.
+fn build_graph(
+    reader: &PbfReader,
+    roads: &[Road],
+    nodes: &Coordinates,
+    keep: impl Fn(i64) -> bool + Sync,
+) -> anyhow::Result<RoadGraph> {
+    let split =
+        |road: &Road| split_road(road, |id| coordinates(nodes, id), &keep, reader.earth_model);
+    let parts: Vec<RoadParts> = if reader.parallel {
+        roads.par_iter().map(split).collect::<anyhow::Result<_>>()?
+    } else {
+        roads.iter().map(split).collect::<anyhow::Result<_>>()?
+    };
+
+    let mut graph = reader.new_graph();
+    graph.arcs = Vec::with_capacity(parts.iter().map(|(_, arcs)| arcs.len()).sum());
+    for (road, (road_nodes, arcs)) in roads.iter().zip(parts) {
+        graph.add_road(road, road_nodes, arcs);
+    }
+
+    Ok(graph)
+}
+
+/// Splits a single road, see [`build_graph`]. `coordinates` looks up the `[lat, lon]` of a node.
+fn split_road(
+    road: &Road,
+    coordinates: impl Fn(i64) -> anyhow::Result<[f64; 2]>,
+    keep: impl Fn(i64) -> bool,
+    earth_model: EarthModel,
+) -> anyhow::Result<RoadParts> {
+    let node_ids = &road.node_ids;
+    let nodes_to_keep: Vec<usize> = (0..node_ids.len()).filter(|i| keep(node_ids[*i])).collect();
+
+    let mut road_nodes = Vec::with_capacity(nodes_to_keep.len());
+    for i in &nodes_to_keep {
+        road_nodes.push((node_ids[*i], coordinates(node_ids[*i])?));
+    }
+
+    let mut arcs = Vec::with_capacity(nodes_to_keep.len() * 2);
+    for pair in nodes_to_keep.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+
+        let mut total_weight = 0.0;
+        for j in from..to {
+            let [from_lat, from_lon] = coordinates(node_ids[j])?;
+            let [to_lat, to_lon] = coordinates(node_ids[j + 1])?;
+            let distance = earth_model.distance(from_lat, from_lon, to_lat, to_lon);
+
+            total_weight += weight(distance, road.speed);
+        }
+
+        arcs.push(Arc::new(
+            node_ids[from],
+            node_ids[to],
+            total_weight,
+            road.way.id,
+        ));
+        // If bidirectional add reverse edge
+        if !road.is_oneway {
+            arcs.push(Arc::new(
+                node_ids[to],
+                node_ids[from],
+                total_weight,
+                road.way.id,
+            ));
+        }
+    }
+
+    Ok((road_nodes, arcs))
+}
+
+fn coordinates(nodes: &Coordinates, node_id: i64) -> anyhow::Result<[f64; 2]> {
+    nodes
+        .get(&node_id)
+        .copied()
+        .with_context(|| format!("Node {} has no coordinates", node_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted_arcs(graph: &RoadGraph) -> Vec<(i64, i64, f64)> {
+        let mut arcs: Vec<_> = graph
+            .get_arcs()
+            .iter()
+            .map(|arc| (arc.source, arc.target, arc.weight))
+            .collect();
+        arcs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        arcs
+    }
+
+    #[test]
+    fn fingerprint_of_options() {
+        let path = Path::new("test.osm.pbf");
+        let reader = PbfReader::new(path);
+
+        assert_eq!(reader.fingerprint(), PbfReader::new(path).fingerprint());
+        assert_eq!(
+            reader.fingerprint(),
+            reader.clone().parallel(true).fingerprint()
+        );
+        assert_ne!(
+            reader.fingerprint(),
+            reader.clone().simplify(true).fingerprint()
+        );
+        assert_ne!(
+            reader.fingerprint(),
+            reader.clone().earth_model(EarthModel::Karney).fingerprint()
+        );
+
+        // The order of the speeds does not matter
+        let profile = |toml| SpeedProfile::from_toml(toml).unwrap();
+        let a = reader
+            .clone()
+            .profile(profile("[highway]\nmotorway = 100\nprimary = 80"));
+        let b = reader
+            .clone()
+            .profile(profile("[highway]\nprimary = 80\nmotorway = 100"));
+        let c = reader
+            .clone()
+            .profile(profile("[highway]\nprimary = 70\nmotorway = 100"));
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_ne!(a.fingerprint(), c.fingerprint());
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/node_refs.osm.pbf");
+
+        for simplify in [false, true] {
+            let reader = PbfReader::new(&path).simplify(simplify);
+            let sequential = reader.read().unwrap();
+            let parallel = reader.parallel(true).read().unwrap();
+
+            assert_eq!(sequential.get_nodes(), parallel.get_nodes());
+            assert_eq!(sorted_arcs(&sequential), sorted_arcs(&parallel));
+            assert_eq!(sequential.get_ways(), parallel.get_ways());
+        }
+    }
+
+    #[test]
+    fn count_refs_in_parallel() {
+        let road = |id, node_ids: Vec<i64>| Road {
+            way: WayInfo {
+                id,
+                name: None,
+                reference: None,
+                road_type: RoadType::Residential,
+                roundabout: false,
+            },
+            node_ids,
+            is_oneway: false,
+            speed: RoadType::Residential.velocity(),
+        };
+        let roads: Vec<_> = (0..100).map(|id| road(id, vec![1, id + 2, 0])).collect();
+
+        let refs_count = count_refs(&roads, true);
+        assert_eq!(refs_count, count_refs(&roads, false));
+        assert_eq!(200, refs_count[&1]);
+        assert_eq!(200, refs_count[&0]);
+        assert_eq!(1, refs_count[&2]);
+    }
+}
@@ -0,0 +1,369 @@
+//! Two-pass import with bounded memory, see [`PbfReader::streaming`].
+//!
+//! The first pass only looks at the ways and collects the ids of all referenced nodes. The ids
+//! are sorted in chunks, which are kept in memory or spilled to disk, and merged into a sorted
+//! array with the number of references of every node. The second pass stores the coordinates
+//! of the referenced nodes as fixed-point numbers next to that array and splits the ways into
+//! arcs as soon as they are read. Neither the ways nor a map of all coordinates is ever held in
+//! memory.
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context};
+use log::{debug, info};
+use osmpbf::{Element, ElementReader};
+use tempfile::NamedTempFile;
+
+use super::{split_road, PbfReader, Road};
+use crate::RoadGraph;
+
+/// Number of node ids which are sorted at once in the first pass (128 MiB)
+const CHUNK_SIZE: usize = 1 << 24;
+
+/// Fixed-point scale of the coordinates, the same precision as OSM uses (about 1 cm)
+const SCALE: f64 = 1e7;
+
+/// Marks nodes whose coordinates have not been read (yet). Not a valid coordinate, as the
+/// longitude is limited to `±1.8e9`.
+const MISSING: [i32; 2] = [i32::MIN; 2];
+
+pub(super) fn read(reader: &PbfReader) -> anyhow::Result<RoadGraph> {
+    if reader.clip.is_some() {
+        bail!("Clipping is not supported by the streaming import");
+    }
+
+    let now = std::time::Instant::now();
+    info!(
+        "BEGIN counting node references of {}",
+        reader.path.display()
+    );
+    let mut counter = RefCounter::new(CHUNK_SIZE, reader.spill_dir.clone());
+    let mut error = None;
+    ElementReader::from_path(&reader.path)?.for_each(|element| {
+        if let Element::Way(way) = element {
+            if error.is_none() && Road::is_road(&way) {
+                let node_ids: Vec<i64> = way.refs().collect();
+                error = counter.add(&node_ids).err();
+            }
+        }
+    })?;
+    if let Some(error) = error {
+        return Err(error).context("Failed to spill node references");
+    }
+    let refs = counter.finish()?;
+    info!(
+        "FINISHED counting references of {} nodes. Took {:?}",
+        refs.ids.len(),
+        now.elapsed()
+    );
+
+    let now = std::time::Instant::now();
+    info!("BEGIN building graph");
+    let simplify = reader.simplify;
+    let mut coordinates = vec![MISSING; refs.ids.len()];
+    let mut graph = reader.new_graph();
+    let mut error = None;
+    ElementReader::from_path(&reader.path)?.for_each(|element| match element {
+        Element::Node(node) => {
+            if let Some(pos) = refs.position(node.id()) {
+                coordinates[pos] = to_fixed(node.lat(), node.lon());
+            }
+        }
+        Element::DenseNode(node) => {
+            if let Some(pos) = refs.position(node.id()) {
+                coordinates[pos] = to_fixed(node.lat(), node.lon());
+            }
+        }
+        Element::Way(way) if error.is_none() && Road::is_road(&way) => {
+            let road = Road::from_way(&way, &reader.profile);
+            let lookup = |id: i64| {
+                refs.position(id)
+                    .map(|pos| coordinates[pos])
+                    .filter(|fixed| *fixed != MISSING)
+                    .map(to_degrees)
+                    .with_context(|| format!("Node {} has no coordinates, is the file sorted?", id))
+            };
+            // Only keep nodes that are referenced more than once
+            let keep =
+                |id: i64| !simplify || refs.position(id).is_some_and(|pos| refs.counts[pos] > 1);
+
+            match split_road(&road, lookup, keep, reader.earth_model) {
+                Ok((road_nodes, arcs)) => graph.add_road(&road, road_nodes, arcs),
+                Err(e) => error = Some(e.context(format!("Failed to split way {}", road.way.id))),
+            }
+        }
+        _ => {}
+    })?;
+    if let Some(error) = error {
+        return Err(error);
+    }
+    info!("FINISHED building graph. Took {:?}", now.elapsed());
+
+    Ok(graph)
+}
+
+fn to_fixed(lat: f64, lon: f64) -> [i32; 2] {
+    [(lat * SCALE).round() as i32, (lon * SCALE).round() as i32]
+}
+
+fn to_degrees([lat, lon]: [i32; 2]) -> [f64; 2] {
+    [lat as f64 / SCALE, lon as f64 / SCALE]
+}
+
+/// Sorted ids of all referenced nodes and how often they are referenced
+#[derive(Debug, Default, PartialEq, Eq)]
+struct NodeRefs {
+    ids: Vec<i64>,
+    /// Number of references of the node with the same index in `ids`, saturates at `u8::MAX`
+    counts: Vec<u8>,
+}
+
+impl NodeRefs {
+    fn position(&self, id: i64) -> Option<usize> {
+        self.ids.binary_search(&id).ok()
+    }
+}
+
+/// Counts the references of nodes in bounded memory. The ids are collected into a buffer,
+/// which is sorted into a run of unique ids with their counts once it is full. The runs are
+/// merged at the end.
+struct RefCounter {
+    buffer: Vec<i64>,
+    chunk_size: usize,
+    runs: Vec<Run>,
+    /// Writes the runs into this directory instead of keeping them in memory
+    spill_dir: Option<PathBuf>,
+}
+
+/// Sorted unique ids with their counts
+enum Run {
+    Memory(Vec<(i64, u8)>),
+    /// Temporary file with a unique name, which is removed when the run is dropped. This also
+    /// happens if the import fails, and concurrent imports can share the spill directory.
+    File(NamedTempFile),
+}
+
+impl RefCounter {
+    fn new(chunk_size: usize, spill_dir: Option<PathBuf>) -> Self {
+        RefCounter {
+            buffer: Vec::new(),
+            chunk_size,
+            runs: Vec::new(),
+            spill_dir,
+        }
+    }
+
+    /// Adds the nodes of a road. Start and end of a road count twice, as they should always be
+    /// included.
+    fn add(&mut self, node_ids: &[i64]) -> io::Result<()> {
+        let last = node_ids.len().saturating_sub(1);
+        for (i, node_id) in node_ids.iter().enumerate() {
+            self.buffer.push(*node_id);
+            if i == 0 || i == last {
+                self.buffer.push(*node_id);
+            }
+        }
+
+        if self.buffer.len() >= self.chunk_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        self.buffer.sort_unstable();
+        let mut run: Vec<(i64, u8)> = Vec::new();
+        for id in self.buffer.drain(..) {
+            match run.last_mut() {
+                Some((last, count)) if *last == id => *count = count.saturating_add(1),
+                _ => run.push((id, 1)),
+            }
+        }
+
+        let run = match &self.spill_dir {
+            Some(dir) => {
+                let mut file = tempfile::Builder::new()
+                    .prefix("osm_reader_refs_")
+                    .suffix(".bin")
+                    .tempfile_in(dir)?;
+                debug!(
+                    "Spilling {} node ids to {}",
+                    run.len(),
+                    file.path().display()
+                );
+                let mut writer = BufWriter::new(file.as_file_mut());
+                for (id, count) in run {
+                    writer.write_all(&id.to_le_bytes())?;
+                    writer.write_all(&[count])?;
+                }
+                writer.flush()?;
+                drop(writer);
+                Run::File(file)
+            }
+            None => Run::Memory(run),
+        };
+        self.runs.push(run);
+        Ok(())
+    }
+
+    /// Merges all runs into a single sorted array
+    fn finish(mut self) -> io::Result<NodeRefs> {
+        self.flush()?;
+        self.buffer = Vec::new();
+
+        let mut readers = Vec::with_capacity(self.runs.len());
+        for run in &mut self.runs {
+            readers.push(match run {
+                Run::Memory(run) => RunReader::Memory(std::mem::take(run).into_iter()),
+                Run::File(file) => RunReader::File(BufReader::new(file.reopen()?)),
+            });
+        }
+
+        let mut heap = BinaryHeap::with_capacity(readers.len());
+        for (i, reader) in readers.iter_mut().enumerate() {
+            if let Some((id, count)) = reader.next()? {
+                heap.push(Reverse((id, i, count)));
+            }
+        }
+
+        let mut refs = NodeRefs::default();
+        while let Some(Reverse((id, i, count))) = heap.pop() {
+            match (refs.ids.last(), refs.counts.last_mut()) {
+                (Some(last), Some(total)) if *last == id => *total = total.saturating_add(count),
+                _ => {
+                    refs.ids.push(id);
+                    refs.counts.push(count);
+                }
+            }
+            if let Some((id, count)) = readers[i].next()? {
+                heap.push(Reverse((id, i, count)));
+            }
+        }
+
+        Ok(refs)
+    }
+}
+
+enum RunReader {
+    Memory(std::vec::IntoIter<(i64, u8)>),
+    File(BufReader<File>),
+}
+
+impl RunReader {
+    fn next(&mut self) -> io::Result<Option<(i64, u8)>> {
+        match self {
+            RunReader::Memory(iter) => Ok(iter.next()),
+            RunReader::File(reader) => {
+                let mut record = [0; 9];
+                match reader.read_exact(&mut record) {
+                    Ok(()) => {
+                        let id = i64::from_le_bytes(record[..8].try_into().unwrap());
+                        Ok(Some((id, record[8])))
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn count(chunk_size: usize, spill_dir: Option<PathBuf>) -> NodeRefs {
+        let mut counter = RefCounter::new(chunk_size, spill_dir);
+        for id in 0..200 {
+            counter.add(&[1, id + 2, 0]).unwrap();
+        }
+        counter.add(&[5, 3, 4]).unwrap();
+        counter.finish().unwrap()
+    }
+
+    #[test]
+    fn merge_runs() {
+        let refs = count(7, None);
+        assert_eq!(refs, count(usize::MAX, None));
+        assert_eq!((0..202).collect::<Vec<_>>(), refs.ids);
+        // Saturates
+        assert_eq!(u8::MAX, refs.counts[0]);
+        assert_eq!(u8::MAX, refs.counts[1]);
+        assert_eq!(1, refs.counts[2]);
+        assert_eq!(2, refs.counts[3]);
+        assert_eq!(3, refs.counts[5]);
+        assert_eq!(None, refs.position(202));
+    }
+
+    #[test]
+    fn spill_runs_to_disk() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(
+            count(usize::MAX, None),
+            count(10, Some(dir.path().to_path_buf()))
+        );
+        // The runs are removed after merging
+        assert_eq!(0, std::fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn remove_runs_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Two counters spilling into the same directory don't overwrite each other's runs
+        let mut counter = RefCounter::new(2, Some(dir.path().to_path_buf()));
+        let mut other = RefCounter::new(2, Some(dir.path().to_path_buf()));
+        counter.add(&[3, 1]).unwrap();
+        other.add(&[2, 4]).unwrap();
+        assert_eq!(2, std::fs::read_dir(dir.path()).unwrap().count());
+        assert_eq!(vec![2, 4], other.finish().unwrap().ids);
+
+        // Dropping the counter before merging, e.g. after a failed import, removes its runs
+        drop(counter);
+        assert_eq!(0, std::fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn fixed_point_coordinates() {
+        let [lat, lon] = to_degrees(to_fixed(48.1234567, -179.9999999));
+        assert!((lat - 48.1234567).abs() < 1e-9);
+        assert!((lon + 179.9999999).abs() < 1e-9);
+        assert_ne!(MISSING, to_fixed(-90.0, -180.0));
+    }
+
+    #[test]
+    fn streaming_matches_default_import() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/node_refs.osm.pbf");
+
+        for simplify in [false, true] {
+            let reader = PbfReader::new(&path).simplify(simplify);
+            let expected = reader.read().unwrap();
+            let graph = reader.streaming(true).read().unwrap();
+
+            assert_eq!(expected.get_nodes().len(), graph.get_nodes().len());
+            for (id, [lat, lon]) in expected.get_nodes() {
+                let [streamed_lat, streamed_lon] = graph.get_nodes()[id];
+                assert!((lat - streamed_lat).abs() < 1e-7);
+                assert!((lon - streamed_lon).abs() < 1e-7);
+            }
+
+            assert_eq!(expected.get_arcs().len(), graph.get_arcs().len());
+            for (a, b) in expected.get_arcs().iter().zip(graph.get_arcs()) {
+                assert_eq!((a.source, a.target), (b.source, b.target));
+                assert!((a.weight - b.weight).abs() < 1e-6);
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! Reader for plain OSM XML files, e.g. small hand-written test networks exported from JOSM.
+//!
+//! The whole file is parsed in a single pass. Only the `node` and `way` elements are of
+//! interest, relations are skipped.
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use super::{Coordinates, Road};
+use crate::profile::SpeedProfile;
+
+/// Returns true for files ending in `.osm` or `.xml`
+pub(super) fn is_xml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("osm" | "xml")
+    )
+}
+
+/// Reads all roads and the coordinates of all nodes
+pub(super) fn read_roads(
+    path: &Path,
+    profile: &SpeedProfile,
+) -> anyhow::Result<(Vec<Road>, Coordinates)> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    parse_roads(BufReader::new(file), profile)
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Way which is currently parsed
+struct Way {
+    id: i64,
+    node_ids: Vec<i64>,
+    tags: Vec<(String, String)>,
+}
+
+impl Way {
+    fn into_road(self, profile: &SpeedProfile) -> Option<Road> {
+        let tags: Vec<_> = self
+            .tags
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        Road::from_tags(self.id, self.node_ids, &tags, profile)
+    }
+}
+
+fn parse_roads(
+    input: impl BufRead,
+    profile: &SpeedProfile,
+) -> anyhow::Result<(Vec<Road>, Coordinates)> {
+    let mut reader = Reader::from_reader(input);
+    let mut buf = Vec::new();
+
+    let mut roads = Vec::new();
+    let mut nodes = Coordinates::default();
+    let mut way: Option<Way> = None;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            // A way without nodes is no road
+            Event::Empty(element) if element.name().as_ref() == b"way" => {}
+            Event::Start(element) | Event::Empty(element) => match element.name().as_ref() {
+                b"node" => {
+                    let coordinates = [attribute(&element, "lat")?, attribute(&element, "lon")?];
+                    nodes.insert(attribute(&element, "id")?, coordinates);
+                }
+                b"way" => {
+                    way = Some(Way {
+                        id: attribute(&element, "id")?,
+                        node_ids: Vec::new(),
+                        tags: Vec::new(),
+                    });
+                }
+                b"nd" => {
+                    if let Some(way) = &mut way {
+                        way.node_ids.push(attribute(&element, "ref")?);
+                    }
+                }
+                b"tag" => {
+                    if let Some(way) = &mut way {
+                        way.tags
+                            .push((attribute(&element, "k")?, attribute(&element, "v")?));
+                    }
+                }
+                _ => {}
+            },
+            Event::End(element) if element.name().as_ref() == b"way" => {
+                roads.extend(way.take().and_then(|way| way.into_road(profile)));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok((roads, nodes))
+}
+
+/// Parses the value of the attribute `name` of `element`
+fn attribute<T: FromStr>(element: &BytesStart, name: &str) -> anyhow::Result<T> {
+    let value = element
+        .try_get_attribute(name)?
+        .with_context(|| format!("Missing attribute {}", name))?
+        .unescape_value()?;
+    value
+        .parse()
+        .map_err(|_| anyhow!("Invalid value {} of attribute {}", value, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clip::{BoundingBox, Clip},
+        geo,
+        reader::PbfReader,
+        road_types::RoadType,
+        weight, RoadGraph,
+    };
+
+    use super::*;
+
+    #[test]
+    fn detect_xml_files() {
+        assert!(is_xml(Path::new("test_data/minimal.osm")));
+        assert!(is_xml(Path::new("export.xml")));
+        assert!(!is_xml(Path::new("test_data/minimal.osm.pbf")));
+        assert!(!is_xml(Path::new("osm")));
+    }
+
+    #[test]
+    fn graph_from_xml_works() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/minimal.osm");
+
+        let graph = RoadGraph::from_pbf(&path).unwrap();
+
+        assert_eq!(graph.get_nodes().len(), 2);
+        assert_eq!(graph.get_arcs().len(), 2);
+        assert_eq!([-1.891758, 1.9275752], graph.get_nodes()[&8]);
+        assert!(graph.get_arcs().iter().all(|arc| arc.way_id == 12));
+        assert_eq!(RoadType::Secondary, graph.get_ways()[&12].road_type);
+    }
+
+    #[test]
+    fn simplify_xml() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/node_refs.osm");
+
+        let graph = RoadGraph::from_pbf(&path).unwrap();
+        assert_eq!(graph.get_nodes().len(), 10);
+        assert_eq!(graph.get_arcs().len(), 18);
+
+        let graph = RoadGraph::from_pbf_with_simplification(&path).unwrap();
+        assert_eq!(graph.get_nodes().len(), 8);
+        assert_eq!(graph.get_arcs().len(), 14);
+        let expected = weight(
+            geo::haversine(0., 0., 0., 1.) * 3.0,
+            RoadType::Secondary.velocity(),
+        );
+        let actual = graph
+            .get_arcs()
+            .iter()
+            .find(|arc| arc.source == 2 && arc.target == 5)
+            .unwrap()
+            .weight;
+        // The weight is the sum of three segments
+        assert!((expected - actual).abs() < 1e-9);
+    }
+
+    #[test]
+    fn keep_nodes_of_single_node_ways() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/single_node_way.osm");
+
+        let graph = RoadGraph::from_pbf(&path).unwrap();
+        // Node 3 is the only node of way 11, node 4 is no part of a road
+        let mut node_ids: Vec<_> = graph.get_nodes().keys().copied().collect();
+        node_ids.sort_unstable();
+        assert_eq!(vec![1, 2, 3], node_ids);
+        assert_eq!(2, graph.get_arcs().len());
+
+        let graph = RoadGraph::from_pbf_with_simplification(&path).unwrap();
+        assert!(graph.get_nodes().contains_key(&3));
+
+        let clip = Clip::new(BoundingBox::new(-1.0, -1.0, 2.0, 2.0));
+        let graph = RoadGraph::from_pbf_clipped(&path, &clip).unwrap();
+        assert_eq!(2, graph.get_nodes().len());
+        assert!(!graph.get_nodes().contains_key(&3));
+    }
+
+    #[test]
+    fn roundabouts_are_oneway() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/roundabout.osm");
+
+        let graph = RoadGraph::from_pbf(&path).unwrap();
+        let arcs_of = |way_id: i64| {
+            let mut arcs: Vec<_> = graph
+                .get_arcs()
+                .iter()
+                .filter(|arc| arc.way_id == way_id)
+                .map(|arc| (arc.source, arc.target))
+                .collect();
+            arcs.sort_unstable();
+            arcs
+        };
+
+        // Only in the direction of the way
+        assert_eq!(vec![(1, 2), (2, 3), (3, 1)], arcs_of(10));
+        assert!(graph.get_ways()[&10].roundabout);
+        // No roundabout
+        assert_eq!(vec![(3, 4), (4, 3)], arcs_of(11));
+        assert!(!graph.get_ways()[&11].roundabout);
+        // Explicitly tagged with oneway=no
+        assert_eq!(vec![(1, 5), (2, 5), (5, 1), (5, 2)], arcs_of(12));
+        assert!(graph.get_ways()[&12].roundabout);
+    }
+
+    #[test]
+    fn weights_of_profile() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/minimal.osm");
+        let profile = SpeedProfile::from_toml("[highway]\nsecondary = 120").unwrap();
+
+        let default = RoadGraph::from_pbf(&path).unwrap();
+        let graph = RoadGraph::from_pbf_with_profile(&path, &profile).unwrap();
+        for (a, b) in default.get_arcs().iter().zip(graph.get_arcs()) {
+            assert!((a.weight / 2.0 - b.weight).abs() < 1e-9);
+        }
+        assert_eq!(120.0, graph.get_max_speed());
+
+        let xml = r#"<osm version="0.6">
+            <way id="1">
+                <nd ref="1"/>
+                <nd ref="2"/>
+                <tag k="highway" v="primary"/>
+                <tag k="surface" v="gravel"/>
+            </way>
+        </osm>"#;
+        let profile = SpeedProfile::from_toml("[surface]\ngravel = 20").unwrap();
+        let (roads, _) = parse_roads(xml.as_bytes(), &profile).unwrap();
+        assert_eq!(20.0, roads[0].speed);
+    }
+
+    #[test]
+    fn lengths_of_earth_model() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/minimal.osm");
+
+        let graph = PbfReader::new(&path)
+            .earth_model(geo::EarthModel::Karney)
+            .read()
+            .unwrap();
+        assert_eq!(geo::EarthModel::Karney, graph.get_earth_model());
+
+        let [lat1, lon1] = graph.get_nodes()[&8];
+        let [lat2, lon2] = graph.get_nodes()[&10];
+        let expected = weight(
+            geo::karney(lat1, lon1, lat2, lon2),
+            RoadType::Secondary.velocity(),
+        );
+        assert!(graph.get_arcs().iter().all(|arc| arc.weight == expected));
+    }
+
+    #[test]
+    fn skip_ways_without_highway_tag() {
+        let xml = r#"<osm version="0.6">
+            <node id="1" lat="0.0" lon="0.0"/>
+            <node id="2" lat="0.0" lon="1.0">
+                <tag k="highway" v="traffic_signals"/>
+            </node>
+            <way id="3">
+                <nd ref="1"/>
+                <nd ref="2"/>
+                <tag k="highway" v="residential"/>
+                <tag k="oneway" v="yes"/>
+                <tag k="name" v="Hauptstraße"/>
+            </way>
+            <way id="4">
+                <nd ref="2"/>
+                <nd ref="1"/>
+                <tag k="building" v="yes"/>
+            </way>
+            <way id="5"/>
+            <relation id="6">
+                <member type="way" ref="3" role=""/>
+                <tag k="type" v="route"/>
+            </relation>
+        </osm>"#;
+
+        let (roads, nodes) = parse_roads(xml.as_bytes(), &SpeedProfile::default()).unwrap();
+        assert_eq!(2, nodes.len());
+        assert_eq!(1, roads.len());
+        assert_eq!(3, roads[0].way.id);
+        assert_eq!(vec![1, 2], roads[0].node_ids);
+        assert!(roads[0].is_oneway);
+        assert_eq!(Some("Hauptstraße"), roads[0].way.name.as_deref());
+        assert_eq!(None, roads[0].way.reference);
+    }
+
+    #[test]
+    fn missing_attributes_are_errors() {
+        let xml = r#"<osm><node id="1" lat="0.0"/></osm>"#;
+        assert!(parse_roads(xml.as_bytes(), &SpeedProfile::default()).is_err());
+
+        let xml = r#"<osm><node id="1" lat="north" lon="0.0"/></osm>"#;
+        assert!(parse_roads(xml.as_bytes(), &SpeedProfile::default()).is_err());
+    }
+}
@@ -1,7 +1,10 @@
-use std::str::FromStr;
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
 
 // Only this road types are inclued in the graph
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RoadType {
     Motorway,
     Trunk,
@@ -21,7 +24,27 @@ pub enum RoadType {
 }
 
 impl RoadType {
-    // Returns the average road velocity in km/h
+    /// All road types in the order of their declaration
+    pub const ALL: [RoadType; 15] = [
+        RoadType::Motorway,
+        RoadType::Trunk,
+        RoadType::Primary,
+        RoadType::Secondary,
+        RoadType::Tertiary,
+        RoadType::MotorwayLink,
+        RoadType::TrunkLink,
+        RoadType::PrimaryLink,
+        RoadType::SecondaryLink,
+        RoadType::Road,
+        RoadType::Unclassified,
+        RoadType::Residential,
+        RoadType::Unsurfaced,
+        RoadType::LivingStreet,
+        RoadType::Service,
+    ];
+
+    // Returns the average road velocity in km/h, the default of a
+    // [`SpeedProfile`](crate::profile::SpeedProfile)
     // From https://ad-wiki.informatik.uni-freiburg.de/teaching/EfficientRoutePlanningSS2011/RoadTypesAndSpeeds
     pub fn velocity(&self) -> f64 {
         match self {
@@ -75,3 +98,27 @@ impl FromStr for RoadType {
         }
     }
 }
+
+/// Writes the value of the `highway` tag
+impl Display for RoadType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            RoadType::Motorway => "motorway",
+            RoadType::Trunk => "trunk",
+            RoadType::Primary => "primary",
+            RoadType::Secondary => "secondary",
+            RoadType::Tertiary => "tertiary",
+            RoadType::MotorwayLink => "motorway_link",
+            RoadType::TrunkLink => "trunk_link",
+            RoadType::PrimaryLink => "primary_link",
+            RoadType::SecondaryLink => "secondary_link",
+            RoadType::Road => "road",
+            RoadType::Unclassified => "unclassified",
+            RoadType::Residential => "residential",
+            RoadType::Unsurfaced => "unsurfaced",
+            RoadType::LivingStreet => "living_street",
+            RoadType::Service => "service",
+        };
+        write!(f, "{}", value)
+    }
+}
@@ -2,76 +2,94 @@
 use std::{path::PathBuf, time::Duration};
 
 use ch_core::{
-    graph::{node_index, Graph},
+    graph::{node_index, NodeIndex},
     node_contraction::NodeContractor,
     overlay_graph::OverlayGraph,
-    search::{astar::AStar, shortest_path::ShortestPath},
-    search::{ch_search::CHSearch, dijkstra::Dijkstra, BidirDijkstra},
-    util::{cli, math::straight_line},
+    search::landmarks::{LandmarkSelection, Landmarks, DEFAULT_NUM_LANDMARKS},
+    search::{AStar, BidirAStar, BidirDijkstra, CHSearch, Dijkstra, ShortestPathAlgorithm},
+    serialization::Compression,
+    util::cli,
+    verification::{
+        PairSelection, Verification, DEFAULT_NUM_PAIRS, DEFAULT_SEED, DEFAULT_TOLERANCE,
+    },
 };
 use indicatif::ProgressBar;
-use reedline_repl_rs::clap::{value_parser, Arg, ArgMatches, Command};
+use reedline_repl_rs::clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 use reedline_repl_rs::{Repl, Result};
 
 /// Print graph info
 fn info(_args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
     context.graph.road_graph().print_info();
     context.graph.print_info();
+    println!("Metadata:\t{}", context.graph.metadata);
 
     Ok(None)
 }
 
+/// Creates the search algorithm selected by `name`
+fn algorithm<'a>(name: &str, context: &'a Context) -> Box<dyn ShortestPathAlgorithm + 'a> {
+    match name {
+        "dijk" => Box::new(Dijkstra::new(context.graph.road_graph())),
+        "astar" => Box::new(AStar::new(context.graph.road_graph())),
+        "ch" => Box::new(CHSearch::new(&context.graph)),
+        "bidir_dijk" => Box::new(BidirDijkstra::new(context.graph.road_graph())),
+        "bidir_astar" => Box::new(BidirAStar::new(context.graph.road_graph())),
+        "bidir_alt" => Box::new(BidirAStar::with_landmarks(
+            context.graph.road_graph(),
+            context.landmarks.as_ref().unwrap(),
+        )),
+        _ => unreachable!("Unknown algorithm"),
+    }
+}
+
 fn bench_algorithm(args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    let algo = args.get_one::<String>("algo").unwrap().as_str();
+    if algo == "bidir_alt" {
+        if let Err(e) = context.load_landmarks() {
+            return Ok(Some(format!("Error loading landmarks: {}", e)));
+        }
+    }
+
     let bench = Benchmark::new(
         context.graph.nodes().count(),
         *args.get_one::<usize>("iterations").unwrap(),
     );
 
-    match args.get_one::<String>("algo").unwrap().as_str() {
-        "dijk" => {
-            let mut d = Dijkstra::new(context.graph.road_graph());
-            println!("Bench: {}", d.bench(bench));
-        }
-        "astar" => {
-            let mut a = AStar::new(context.graph.road_graph());
-            println!("Bench: {}", a.bench(bench));
-        }
-        "ch" => {
-            let mut b = CHSearch::new(&context.graph);
-            println!("Bench: {}", b.bench(bench));
-        }
-        "bidir_dijk" => {
-            let mut b = BidirDijkstra::new(context.graph.road_graph());
-            println!("Bench: {}", b.bench(bench));
-        }
-        _ => println!("Unknown algorithm"),
-    }
+    let mut a = algorithm(algo, context);
+    println!("Bench: {}", bench.run(a.as_mut()));
     Ok(Some("Done.".to_string()))
 }
 
-fn run_algorithm(args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
-    let src = *args.get_one::<usize>("src").unwrap();
-    let dst = *args.get_one::<usize>("dst").unwrap();
+/// Resolves the node `id`, which is an OSM id if `osm` is set and an index otherwise
+fn resolve_node(context: &Context, id: usize, osm: bool) -> std::result::Result<NodeIndex, String> {
+    if !osm {
+        return Ok(node_index(id));
+    }
+    context
+        .graph
+        .node_by_osm_id(id)
+        .ok_or_else(|| format!("Unknown OSM node {}", id))
+}
 
-    let (sp, stats) = match args.get_one::<String>("algo").unwrap().as_str() {
-        "dijk" => {
-            let mut d = Dijkstra::new(context.graph.road_graph());
-            (d.run(src, dst), d.stats)
-        }
-        "astar" => {
-            let mut a = AStar::new(context.graph.road_graph());
-            (a.run(src, dst), a.stats)
-        }
-        "ch" => {
-            let mut b = CHSearch::new(&context.graph);
-            (b.run(src, dst), b.stats)
-        }
-        "bidir_dijk" => {
-            let mut b = BidirDijkstra::new(context.graph.road_graph());
-            (b.run(src, dst), b.stats)
-        }
-        _ => unreachable!("Unknown algorithm"),
+fn run_algorithm(args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    let osm = args.get_flag("osm");
+    let (src, dst) = match (
+        resolve_node(context, *args.get_one::<usize>("src").unwrap(), osm),
+        resolve_node(context, *args.get_one::<usize>("dst").unwrap(), osm),
+    ) {
+        (Ok(src), Ok(dst)) => (src, dst),
+        (Err(e), _) | (_, Err(e)) => return Ok(Some(e)),
     };
+    let algo = args.get_one::<String>("algo").unwrap().as_str();
+    if algo == "bidir_alt" {
+        if let Err(e) = context.load_landmarks() {
+            return Ok(Some(format!("Error loading landmarks: {}", e)));
+        }
+    }
+
+    let mut a = algorithm(algo, context);
+    let sp = a.search(src, dst);
+    let stats = a.stats();
 
     if let Some(sp) = sp {
         let mut path = String::new();
@@ -80,7 +98,14 @@ fn run_algorithm(args: ArgMatches, context: &mut Context) -> Result<Option<Strin
         // }
         path.push_str(&format!(
             "{:?}\n",
-            sp.nodes.iter().map(|n| n.index()).collect::<Vec<_>>()
+            sp.nodes
+                .iter()
+                .map(|&n| if osm {
+                    context.graph.osm_id(n).unwrap()
+                } else {
+                    n.index()
+                })
+                .collect::<Vec<_>>()
         ));
         path.push_str(&format!(
             "Took: {:?} / {} nodes settled",
@@ -92,9 +117,36 @@ fn run_algorithm(args: ArgMatches, context: &mut Context) -> Result<Option<Strin
     }
 }
 
+/// Compare CH queries with Dijkstra
+fn verify(args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    // Missing arguments fall back to the defaults of the verification
+    let selection = args
+        .get_one::<String>("selection")
+        .map(|selection| selection.parse::<PairSelection>().unwrap())
+        .unwrap_or_default();
+
+    let report = Verification::new(&context.graph)
+        .num_pairs(args.get_one("pairs").copied().unwrap_or(DEFAULT_NUM_PAIRS))
+        .seed(args.get_one("seed").copied().unwrap_or(DEFAULT_SEED))
+        .tolerance(
+            args.get_one("tolerance")
+                .copied()
+                .unwrap_or(DEFAULT_TOLERANCE),
+        )
+        .selection(selection)
+        .run();
+
+    Ok(Some(report.to_string()))
+}
+
 fn save_graph(args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
     let path = args.get_one::<PathBuf>("path").unwrap();
-    match context.graph.encode(path) {
+    let compression = args
+        .get_one::<String>("compression")
+        .unwrap()
+        .parse::<Compression>()
+        .unwrap();
+    match context.graph.encode_with_compression(path, compression) {
         Ok(bytes_written) => Ok(Some(format!("Graph saved ({} Bytes)", bytes_written))),
         Err(e) => Ok(Some(format!("Error saving graph: {}", e))),
     }
@@ -102,11 +154,30 @@ fn save_graph(args: ArgMatches, context: &mut Context) -> Result<Option<String>>
 
 struct Context {
     graph: OverlayGraph,
+    graph_path: PathBuf,
+    landmarks: Option<Landmarks>,
 }
 
 impl Context {
-    fn new(graph: OverlayGraph) -> Context {
-        Self { graph }
+    fn new(graph: OverlayGraph, graph_path: PathBuf) -> Context {
+        Self {
+            graph,
+            graph_path,
+            landmarks: None,
+        }
+    }
+
+    /// Loads the landmarks stored next to the graph file or computes them on first use
+    fn load_landmarks(&mut self) -> anyhow::Result<()> {
+        if self.landmarks.is_none() {
+            self.landmarks = Some(Landmarks::load_or_compute(
+                self.graph.road_graph(),
+                &self.graph_path,
+                DEFAULT_NUM_LANDMARKS,
+                LandmarkSelection::Avoid,
+            )?);
+        }
+        Ok(())
     }
 }
 
@@ -146,26 +217,24 @@ impl Benchmark {
             iterations,
         }
     }
-}
 
-trait Runnable {
-    fn run(&mut self, src: usize, dst: usize) -> Option<ShortestPath>;
-    fn bench(&mut self, b: Benchmark) -> BenchmarkResult {
+    /// Runs `algo` on random queries
+    pub fn run(&self, algo: &mut dyn ShortestPathAlgorithm) -> BenchmarkResult {
         use rand::{rngs::StdRng, Rng};
 
         let mut rng: StdRng = rand::SeedableRng::seed_from_u64(187);
-        let mut timings = Vec::with_capacity(b.iterations);
-        let mut nodes_settled = Vec::with_capacity(b.iterations);
+        let mut timings = Vec::with_capacity(self.iterations);
+        let mut nodes_settled = Vec::with_capacity(self.iterations);
 
-        let pb = ProgressBar::new(b.iterations as u64);
-        for _ in 0..b.iterations {
-            let src = rng.gen_range(0..b.num_nodes);
-            let dst = rng.gen_range(0..b.num_nodes);
+        let pb = ProgressBar::new(self.iterations as u64);
+        for _ in 0..self.iterations {
+            let src = rng.gen_range(0..self.num_nodes);
+            let dst = rng.gen_range(0..self.num_nodes);
 
-            self.run(src, dst);
+            algo.search(node_index(src), node_index(dst));
 
-            timings.push(self.stats().duration.unwrap().as_secs_f64());
-            nodes_settled.push(self.stats().nodes_settled as f64);
+            timings.push(algo.stats().duration.unwrap().as_secs_f64());
+            nodes_settled.push(algo.stats().nodes_settled as f64);
             pb.inc(1);
         }
 
@@ -194,64 +263,19 @@ trait Runnable {
             median_nodes_settled,
         }
     }
-
-    fn stats(&self) -> &ch_core::statistics::SearchStats;
-}
-
-impl Runnable for Dijkstra<'_> {
-    fn run(&mut self, src: usize, dst: usize) -> Option<ShortestPath> {
-        self.search(node_index(src), node_index(dst))
-    }
-
-    fn stats(&self) -> &ch_core::statistics::SearchStats {
-        &self.stats
-    }
-}
-
-impl Runnable for BidirDijkstra<'_> {
-    fn run(&mut self, src: usize, dst: usize) -> Option<ShortestPath> {
-        self.search(node_index(src), node_index(dst))
-    }
-
-    fn stats(&self) -> &ch_core::statistics::SearchStats {
-        &self.stats
-    }
-}
-
-impl Runnable for AStar<'_> {
-    fn run(&mut self, src: usize, dst: usize) -> Option<ShortestPath> {
-        self.search(node_index(src), node_index(dst), straight_line)
-    }
-
-    fn stats(&self) -> &ch_core::statistics::SearchStats {
-        &self.stats
-    }
-}
-
-impl Runnable for CHSearch<'_> {
-    fn run(&mut self, src: usize, dst: usize) -> Option<ShortestPath> {
-        self.search(node_index(src), node_index(dst))
-    }
-
-    fn stats(&self) -> &ch_core::statistics::SearchStats {
-        &self.stats
-    }
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     let cfg = cli::parse();
 
-    let mut graph = if cfg.simplify {
-        Graph::from_pbf_with_simplification(&cfg.pbf_file).unwrap()
-    } else {
-        Graph::from_pbf(&cfg.pbf_file).unwrap()
-    };
+    let mut graph = cfg.read_graph().unwrap();
+    graph.filter_components(cfg.components);
 
     let mut contractor = NodeContractor::new_with_params(&mut graph, cfg.params);
     let overlay_graph = contractor.run_with_strategy(cfg.strategy);
 
-    let context = Context::new(overlay_graph);
+    let context = Context::new(overlay_graph, cfg.pbf_file);
 
     let mut repl = Repl::new(context)
         .with_name("Pathfinder")
@@ -264,7 +288,14 @@ fn main() -> Result<()> {
             Command::new("bench")
                 .arg(
                     Arg::new("algo")
-                        .value_parser(["dijk", "astar", "ch", "bidir_dijk"])
+                        .value_parser([
+                            "dijk",
+                            "astar",
+                            "ch",
+                            "bidir_dijk",
+                            "bidir_astar",
+                            "bidir_alt",
+                        ])
                         .default_value("dijk")
                         .required(true)
                         .help("Name of algorithm"),
@@ -283,7 +314,14 @@ fn main() -> Result<()> {
             Command::new("run")
                 .arg(
                     Arg::new("algo")
-                        .value_parser(["dijk", "astar", "ch", "bidir_dijk"])
+                        .value_parser([
+                            "dijk",
+                            "astar",
+                            "ch",
+                            "bidir_dijk",
+                            "bidir_astar",
+                            "bidir_alt",
+                        ])
                         .default_value("dijk")
                         .required(true)
                         .help("Name of algorithm"),
@@ -300,9 +338,56 @@ fn main() -> Result<()> {
                         .required(true)
                         .help("ID of destination node"),
                 )
+                .arg(
+                    Arg::new("osm")
+                        .long("osm")
+                        .action(ArgAction::SetTrue)
+                        .help("Use OSM node ids instead of node indices for src, dst and the path"),
+                )
                 .about("Run the selected algorithm"),
             run_algorithm,
         )
+        .with_command(
+            Command::new("verify")
+                .arg(
+                    Arg::new("pairs")
+                        .value_parser(value_parser!(usize))
+                        .required(false)
+                        .help(format!(
+                            "Number of verified pairs [default: {}]",
+                            DEFAULT_NUM_PAIRS
+                        )),
+                )
+                .arg(
+                    Arg::new("selection")
+                        .long("selection")
+                        .value_parser(["random", "rank"])
+                        .help(format!(
+                            "Select random pairs or stratify them by Dijkstra rank [default: {}]",
+                            PairSelection::default()
+                        )),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_parser(value_parser!(u64))
+                        .help(format!(
+                            "Seed of the first pair [default: {}]",
+                            DEFAULT_SEED
+                        )),
+                )
+                .arg(
+                    Arg::new("tolerance")
+                        .long("tolerance")
+                        .value_parser(value_parser!(f64))
+                        .help(format!(
+                            "Maximal difference of equal weights [default: {:e}]",
+                            DEFAULT_TOLERANCE
+                        )),
+                )
+                .about("Verify CH queries against Dijkstra"),
+            verify,
+        )
         .with_command(
             Command::new("save")
                 .arg(
@@ -311,6 +396,13 @@ fn main() -> Result<()> {
                         .required(true)
                         .help("Path to save graph to"),
                 )
+                .arg(
+                    Arg::new("compression")
+                        .value_parser(["none", "zstd", "lz4"])
+                        .default_value("none")
+                        .required(false)
+                        .help("Compression of the saved file"),
+                )
                 .about("Save graph to file"),
             save_graph,
         );